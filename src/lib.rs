@@ -0,0 +1,3975 @@
+//! Keystroke statistics as recorded by the `keystr` monitor.
+//!
+//! The `keystr` binary is a thin CLI over this crate, so other programs can
+//! read the same data without shelling out. Paths resolve exactly as they do
+//! for the CLI, honoring `KEYSTR_DATA_DIR` and the configured storage.
+//!
+//! ```no_run
+//! let data = keystr::load_data()?;
+//! println!("This week: {}", data.get_weekly_stats());
+//! println!("Last week: {}", data.get_previous_week_stats());
+//! for record in data.get_daily_stats(7) {
+//!     println!("{}  {}", keystr::format_day(record.day()), record.count);
+//! }
+//! # Ok::<(), String>(())
+//! ```
+
+use rdev::{Key, ListenError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// UTC offsets are whole quarter hours, so they can only change on one.
+const OFFSET_CHANGE_STEP: u64 = 15 * 60;
+
+/// Version of the `data.json` layout written by this build.
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeystrokeData {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub total_count: u64,
+    pub daily_records: Vec<DailyRecord>,
+    #[serde(default)]
+    pub hourly_counts: [u64; 24],
+    #[serde(default)]
+    pub total_clicks: u64,
+    /// Most keystrokes seen in any 60-second window.
+    #[serde(default)]
+    pub peak_kpm: u64,
+    /// When `peak_kpm` was reached, as a Unix timestamp.
+    #[serde(default)]
+    pub peak_timestamp: u64,
+    /// Keystrokes per `KeyCategory`, keyed by category name.
+    #[serde(default)]
+    category_counts: BTreeMap<String, u64>,
+    /// Keystrokes counted while `filter_app` was set, keyed by its value.
+    // Always written, since the binary format can't tell a skipped field.
+    #[serde(default)]
+    app_counts: BTreeMap<String, u64>,
+    /// Every keypress, including auto-repeats, whether or not it was counted.
+    #[serde(default)]
+    pub raw_total: u64,
+    /// Keypresses that weren't auto-repeats.
+    #[serde(default)]
+    pub distinct_total: u64,
+    /// Keystrokes by day of week (Monday first) and local hour.
+    #[serde(default)]
+    pub weekday_hour_counts: [[u64; 24]; 7],
+    /// Lifetime totals reached so far, oldest first.
+    #[serde(default)]
+    pub achievements: Vec<Achievement>,
+    #[serde(skip)]
+    today_cache: Option<TodayCache>,
+}
+
+/// A lifetime total `total_count` has passed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Achievement {
+    pub total: u64,
+    /// When it was reached, as a Unix timestamp.
+    pub timestamp: u64,
+}
+
+/// Where today's record sits in `daily_records` and when today ends, so
+/// counting a keypress doesn't rescan the records or reformat the date.
+#[derive(Debug, Clone, Copy)]
+struct TodayCache {
+    day: u64,
+    index: usize,
+    offset_seconds: i64,
+    /// When to look again: the start of the next day, or sooner at the next
+    /// quarter hour, which is when a daylight saving change can take effect.
+    until: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DailyRecord {
+    pub date: String,
+    pub count: u64,
+    pub timestamp: u64,
+    #[serde(default)]
+    pub clicks: u64,
+    /// Seconds spent typing, excluding gaps longer than the idle threshold.
+    #[serde(default)]
+    pub active_seconds: u64,
+    /// Sum of the gaps between keystrokes, each capped at the idle threshold.
+    #[serde(default)]
+    pub typing_seconds: u64,
+    /// Stretches of typing separated by pauses longer than the idle
+    /// threshold. Records from before sessions were tracked count as one.
+    #[serde(default = "one_session")]
+    pub sessions: u32,
+    /// Longest pause between two of the day's sessions, in seconds.
+    #[serde(default)]
+    pub longest_gap: u64,
+    /// Unix timestamps of the day's first and latest keystroke; 0 for
+    /// records written before these were tracked.
+    #[serde(default)]
+    pub first_seen: u64,
+    #[serde(default)]
+    pub last_seen: u64,
+}
+
+fn one_session() -> u32 {
+    1
+}
+
+impl DailyRecord {
+    /// The record's day as days since the Unix epoch.
+    pub fn day(&self) -> u64 {
+        self.date.parse().unwrap_or(self.timestamp / 86400)
+    }
+
+    /// First and latest keystroke, falling back to the record's creation
+    /// time for older records.
+    pub fn seen_range(&self) -> (u64, u64) {
+        let first = if self.first_seen > 0 {
+            self.first_seen
+        } else {
+            self.timestamp
+        };
+        (first, self.last_seen.max(first))
+    }
+}
+
+impl Default for KeystrokeData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeystrokeData {
+    pub fn new() -> Self {
+        KeystrokeData {
+            schema_version: SCHEMA_VERSION,
+            total_count: 0,
+            daily_records: Vec::new(),
+            hourly_counts: [0; 24],
+            total_clicks: 0,
+            peak_kpm: 0,
+            peak_timestamp: 0,
+            category_counts: BTreeMap::new(),
+            app_counts: BTreeMap::new(),
+            raw_total: 0,
+            distinct_total: 0,
+            weekday_hour_counts: [[0; 24]; 7],
+            achievements: Vec::new(),
+            today_cache: None,
+        }
+    }
+
+    /// Finds (or creates) the record for the day `now` falls on, reusing the
+    /// cached position while it's still valid.
+    fn today_slot(&mut self, now: u64) -> TodayCache {
+        if let Some(cache) = self.today_cache
+            && now < cache.until
+            && self
+                .daily_records
+                .get(cache.index)
+                .is_some_and(|r| r.day() == cache.day)
+        {
+            return cache;
+        }
+
+        let offset_seconds = utc_offset_at(now);
+        let day = day_with_offset(now, offset_seconds);
+        let index = match self.daily_records.iter().position(|r| r.day() == day) {
+            Some(index) => index,
+            None => {
+                self.daily_records.push(DailyRecord {
+                    date: day.to_string(),
+                    count: 0,
+                    timestamp: now,
+                    clicks: 0,
+                    active_seconds: 0,
+                    typing_seconds: 0,
+                    sessions: 0,
+                    longest_gap: 0,
+                    first_seen: 0,
+                    last_seen: 0,
+                });
+                self.daily_records.len() - 1
+            }
+        };
+        let cache = TodayCache {
+            day,
+            index,
+            offset_seconds,
+            until: ((day + 1) * 86400)
+                .saturating_add_signed(-offset_seconds)
+                .min(now - now % OFFSET_CHANGE_STEP + OFFSET_CHANGE_STEP),
+        };
+        self.today_cache = Some(cache);
+        cache
+    }
+
+    /// Returns today's record, creating an empty one if there is none yet.
+    pub fn today_record(&mut self) -> &mut DailyRecord {
+        let index = self.today_slot(current_timestamp()).index;
+        &mut self.daily_records[index]
+    }
+
+    pub fn increment(&mut self) {
+        self.increment_n(1);
+    }
+
+    /// Counts `n` keystrokes at once, touching today's record a single time.
+    pub fn increment_n(&mut self, n: u64) {
+        let now = current_timestamp();
+        let slot = self.today_slot(now);
+        let hour = hour_with_offset(now, slot.offset_seconds);
+        self.total_count += n;
+        self.hourly_counts[hour] += n;
+        self.weekday_hour_counts[weekday_from_day(slot.day)][hour] += n;
+        let record = &mut self.daily_records[slot.index];
+        record.count += n;
+        if record.first_seen == 0 {
+            record.first_seen = now;
+        }
+        record.last_seen = now;
+    }
+
+    /// Records every power of ten from 1,000 up, and every one of
+    /// `milestones`, that `total_count` passed since it was `before`, and
+    /// returns them. Each total is only ever earned once.
+    pub fn check_achievements(&mut self, before: u64, milestones: &[u64]) -> Vec<u64> {
+        let after = self.total_count;
+        if after <= before {
+            return Vec::new();
+        }
+        let mut crossed: Vec<u64> = std::iter::successors(Some(1000u64), |p| p.checked_mul(10))
+            .take_while(|&p| p <= after)
+            .chain(milestones.iter().copied())
+            .filter(|&total| total > before && total <= after)
+            .filter(|&total| !self.achievements.iter().any(|a| a.total == total))
+            .collect();
+        crossed.sort_unstable();
+        crossed.dedup();
+        let timestamp = current_timestamp();
+        self.achievements.extend(
+            crossed
+                .iter()
+                .map(|&total| Achievement { total, timestamp }),
+        );
+        crossed
+    }
+
+    /// Adds a keypress to the raw and distinct totals. Whether it also
+    /// counts towards `total_count` depends on `count_repeats`.
+    pub fn record_press(&mut self, repeat: bool) {
+        self.raw_total += 1;
+        if !repeat {
+            self.distinct_total += 1;
+        }
+    }
+
+    pub fn increment_click(&mut self) {
+        self.total_clicks += 1;
+        self.today_record().clicks += 1;
+    }
+
+    pub fn increment_category(&mut self, category: KeyCategory) {
+        *self
+            .category_counts
+            .entry(category.name().to_string())
+            .or_insert(0) += 1;
+    }
+
+    pub fn increment_app(&mut self, filter: &str) {
+        *self.app_counts.entry(filter.to_string()).or_insert(0) += 1;
+    }
+
+    /// Keystrokes counted under each `filter_app` value, by name.
+    pub fn get_app_stats(&self) -> Vec<(String, u64)> {
+        self.app_counts
+            .iter()
+            .map(|(app, count)| (app.clone(), *count))
+            .collect()
+    }
+
+    pub fn get_category_stats(&self) -> Vec<(KeyCategory, u64)> {
+        KeyCategory::ALL
+            .iter()
+            .map(|&category| {
+                let count = self.category_counts.get(category.name()).copied();
+                (category, count.unwrap_or(0))
+            })
+            .collect()
+    }
+
+    /// Keeps `kpm` as the personal best if it beats the current one.
+    pub fn record_kpm(&mut self, kpm: u64, timestamp: u64) {
+        if kpm > self.peak_kpm {
+            self.peak_kpm = kpm;
+            self.peak_timestamp = timestamp;
+        }
+    }
+
+    pub fn add_active_seconds(&mut self, seconds: u64) {
+        if seconds > 0 {
+            self.today_record().active_seconds += seconds;
+        }
+    }
+
+    pub fn add_typing_seconds(&mut self, seconds: u64) {
+        if seconds > 0 {
+            self.today_record().typing_seconds += seconds;
+        }
+    }
+
+    /// Starts a new session when `gap`, the seconds since the previous
+    /// keystroke, is over `idle_threshold`. `None` means there was no
+    /// previous keystroke to measure from, e.g. after a restart or sleep.
+    pub fn record_gap(&mut self, gap: Option<u64>, idle_threshold: u64) {
+        let today = self.today_record();
+        let first_of_day = today.sessions == 0;
+        match gap {
+            Some(gap) if gap <= idle_threshold => {
+                if first_of_day {
+                    today.sessions = 1;
+                }
+            }
+            _ => {
+                today.sessions += 1;
+                // A break that began yesterday isn't one of today's.
+                if let Some(gap) = gap.filter(|_| !first_of_day) {
+                    today.longest_gap = today.longest_gap.max(gap);
+                }
+            }
+        }
+    }
+
+    pub fn get_today_record(&self) -> Option<&DailyRecord> {
+        let today = format_date_storage();
+        self.daily_records.iter().find(|r| r.date == today)
+    }
+
+    pub fn get_today_stats(&self) -> u64 {
+        self.get_today_record().map_or(0, |r| r.count)
+    }
+
+    pub fn get_daily_stats(&self, days: usize) -> Vec<DailyRecord> {
+        let mut records = self.daily_records.clone();
+        records.sort_by_key(|r| std::cmp::Reverse(r.day()));
+        records.into_iter().take(days).collect()
+    }
+
+    /// Keystrokes over the last `days` local days, including today.
+    fn sum_last_days(&self, days: u64) -> u64 {
+        self.sum_days_ago(0, days)
+    }
+
+    /// Keystrokes over `days` local days ending `ago` days before today.
+    fn sum_days_ago(&self, ago: u64, days: u64) -> u64 {
+        let Some(last_day) = current_day().checked_sub(ago) else {
+            return 0;
+        };
+        let first_day = (last_day + 1).saturating_sub(days);
+        self.daily_records
+            .iter()
+            .filter(|r| (first_day..=last_day).contains(&r.day()))
+            .map(|r| r.count)
+            .sum()
+    }
+
+    pub fn get_weekly_stats(&self) -> u64 {
+        self.sum_last_days(7)
+    }
+
+    /// Keystrokes in the 7 days before the current week.
+    pub fn get_previous_week_stats(&self) -> u64 {
+        self.sum_days_ago(7, 7)
+    }
+
+    /// Keystrokes in the 30 days before the current month.
+    pub fn get_previous_month_stats(&self) -> u64 {
+        self.sum_days_ago(30, 30)
+    }
+
+    /// Records for days in the inclusive `from..=to` range (days since epoch),
+    /// in chronological order.
+    pub fn get_range_stats(&self, from: u64, to: u64) -> Vec<DailyRecord> {
+        let mut records: Vec<DailyRecord> = self
+            .daily_records
+            .iter()
+            .filter(|r| (from..=to).contains(&r.day()))
+            .cloned()
+            .collect();
+        records.sort_by_key(|r| r.day());
+        records
+    }
+
+    /// Days typed on after `since` (a Unix timestamp), oldest first. Today's
+    /// record keeps qualifying while it's still growing.
+    pub fn get_changed_since(&self, since: u64) -> Vec<DailyRecord> {
+        let mut records: Vec<DailyRecord> = self
+            .daily_records
+            .iter()
+            .filter(|r| r.seen_range().1 > since)
+            .cloned()
+            .collect();
+        records.sort_by_key(|r| r.day());
+        records
+    }
+
+    /// The `n` busiest days, most keystrokes first; ties go to the earlier day.
+    pub fn get_top_days(&self, n: usize) -> Vec<DailyRecord> {
+        let mut records = self.daily_records.clone();
+        records.sort_by(|a, b| b.count.cmp(&a.count).then(a.day().cmp(&b.day())));
+        records.truncate(n);
+        records
+    }
+
+    /// Sorted, de-duplicated days (since epoch) that have at least one keystroke.
+    fn active_days(&self) -> Vec<u64> {
+        let mut days: Vec<u64> = self
+            .daily_records
+            .iter()
+            .filter(|r| r.count > 0)
+            .map(|r| r.day())
+            .collect();
+        days.sort_unstable();
+        days.dedup();
+        days
+    }
+
+    /// Consecutive active days ending today. A streak that ran through
+    /// yesterday still counts until today ends without a keystroke.
+    pub fn current_streak(&self) -> u32 {
+        let days = self.active_days();
+        let today = current_day();
+        let Some(&last) = days.last() else {
+            return 0;
+        };
+        if last + 1 < today {
+            return 0;
+        }
+
+        let mut streak = 1;
+        for pair in days.windows(2).rev() {
+            if pair[0] + 1 != pair[1] {
+                break;
+            }
+            streak += 1;
+        }
+        streak
+    }
+
+    pub fn longest_streak(&self) -> u32 {
+        let days = self.active_days();
+        if days.is_empty() {
+            return 0;
+        }
+
+        let (mut longest, mut run) = (1, 1);
+        for pair in days.windows(2) {
+            run = if pair[0] + 1 == pair[1] { run + 1 } else { 1 };
+            longest = longest.max(run);
+        }
+        longest
+    }
+
+    /// Folds another history into this one, summing counts for days present
+    /// in both and appending the rest.
+    pub fn merge(&mut self, other: &KeystrokeData) -> MergeSummary {
+        let mut summary = MergeSummary {
+            days_merged: 0,
+            days_added: 0,
+            keystrokes_added: other.total_count,
+        };
+
+        self.total_count += other.total_count;
+        self.total_clicks += other.total_clicks;
+        self.raw_total += other.raw_total;
+        self.distinct_total += other.distinct_total;
+        self.record_kpm(other.peak_kpm, other.peak_timestamp);
+        for (category, count) in &other.category_counts {
+            *self.category_counts.entry(category.clone()).or_insert(0) += count;
+        }
+        for (app, count) in &other.app_counts {
+            *self.app_counts.entry(app.clone()).or_insert(0) += count;
+        }
+        for (total, added) in self.hourly_counts.iter_mut().zip(other.hourly_counts) {
+            *total += added;
+        }
+        for (totals, added) in self
+            .weekday_hour_counts
+            .iter_mut()
+            .zip(other.weekday_hour_counts)
+        {
+            for (total, added) in totals.iter_mut().zip(added) {
+                *total += added;
+            }
+        }
+
+        for record in &other.daily_records {
+            let day = record.day();
+            if let Some(existing) = self.daily_records.iter_mut().find(|r| r.day() == day) {
+                existing.count += record.count;
+                existing.clicks += record.clicks;
+                existing.active_seconds += record.active_seconds;
+                existing.typing_seconds += record.typing_seconds;
+                existing.sessions += record.sessions;
+                existing.longest_gap = existing.longest_gap.max(record.longest_gap);
+                let (first, last) = existing.seen_range();
+                let (other_first, other_last) = record.seen_range();
+                existing.first_seen = first.min(other_first);
+                existing.last_seen = last.max(other_last);
+                existing.timestamp = existing.timestamp.min(record.timestamp);
+                summary.days_merged += 1;
+            } else {
+                self.daily_records.push(record.clone());
+                summary.days_added += 1;
+            }
+        }
+        self.daily_records.sort_by_key(|r| r.day());
+        for achievement in &other.achievements {
+            if !self
+                .achievements
+                .iter()
+                .any(|a| a.total == achievement.total)
+            {
+                self.achievements.push(achievement.clone());
+            }
+        }
+        self.achievements.sort_by_key(|a| a.total);
+
+        summary
+    }
+
+    /// What `self` counted on top of `base`, shaped so that merging it into
+    /// `base` gives `self` back. `None` if any count went down, which a
+    /// delta can't express.
+    pub fn delta_since(&self, base: &KeystrokeData) -> Option<KeystrokeData> {
+        let mut delta = KeystrokeData::new();
+        delta.total_count = self.total_count.checked_sub(base.total_count)?;
+        delta.total_clicks = self.total_clicks.checked_sub(base.total_clicks)?;
+        delta.raw_total = self.raw_total.checked_sub(base.raw_total)?;
+        delta.distinct_total = self.distinct_total.checked_sub(base.distinct_total)?;
+        if self.peak_kpm < base.peak_kpm {
+            return None;
+        }
+        if self.peak_kpm > base.peak_kpm {
+            delta.peak_kpm = self.peak_kpm;
+            delta.peak_timestamp = self.peak_timestamp;
+        }
+        for (hour, added) in delta.hourly_counts.iter_mut().enumerate() {
+            *added = self.hourly_counts[hour].checked_sub(base.hourly_counts[hour])?;
+        }
+        for (weekday, hours) in delta.weekday_hour_counts.iter_mut().enumerate() {
+            for (hour, added) in hours.iter_mut().enumerate() {
+                *added = self.weekday_hour_counts[weekday][hour]
+                    .checked_sub(base.weekday_hour_counts[weekday][hour])?;
+            }
+        }
+        for (category, before) in &base.category_counts {
+            self.category_counts.get(category)?.checked_sub(*before)?;
+        }
+        for (category, count) in &self.category_counts {
+            let before = base.category_counts.get(category).copied().unwrap_or(0);
+            if *count > before {
+                delta
+                    .category_counts
+                    .insert(category.clone(), count - before);
+            }
+        }
+        for (app, before) in &base.app_counts {
+            self.app_counts.get(app)?.checked_sub(*before)?;
+        }
+        for (app, count) in &self.app_counts {
+            let before = base.app_counts.get(app).copied().unwrap_or(0);
+            if *count > before {
+                delta.app_counts.insert(app.clone(), count - before);
+            }
+        }
+
+        if !base
+            .achievements
+            .iter()
+            .all(|earned| self.achievements.contains(earned))
+        {
+            return None;
+        }
+        delta.achievements = self
+            .achievements
+            .iter()
+            .filter(|a| !base.achievements.contains(a))
+            .cloned()
+            .collect();
+
+        let mut before: BTreeMap<u64, &DailyRecord> =
+            base.daily_records.iter().map(|r| (r.day(), r)).collect();
+        for record in &self.daily_records {
+            let Some(previous) = before.remove(&record.day()) else {
+                delta.daily_records.push(record.clone());
+                continue;
+            };
+            let added = DailyRecord {
+                count: record.count.checked_sub(previous.count)?,
+                clicks: record.clicks.checked_sub(previous.clicks)?,
+                active_seconds: record.active_seconds.checked_sub(previous.active_seconds)?,
+                typing_seconds: record.typing_seconds.checked_sub(previous.typing_seconds)?,
+                sessions: record.sessions.checked_sub(previous.sessions)?,
+                ..record.clone()
+            };
+            if added.count > 0
+                || added.clicks > 0
+                || added.active_seconds > 0
+                || added.typing_seconds > 0
+                || added.sessions > 0
+                || added.longest_gap != previous.longest_gap
+                || added.seen_range() != previous.seen_range()
+            {
+                delta.daily_records.push(added);
+            }
+        }
+        // Days that disappeared were pruned or reset.
+        before.is_empty().then_some(delta)
+    }
+
+    /// Whether this holds no counts at all, as a delta.
+    pub fn is_empty(&self) -> bool {
+        self.total_count == 0
+            && self.total_clicks == 0
+            && self.raw_total == 0
+            && self.peak_kpm == 0
+            && self.daily_records.is_empty()
+            && self.achievements.is_empty()
+    }
+
+    /// Drops daily records older than `retention_days`, returning how many
+    /// were removed. `total_count` is left alone so the lifetime total stays
+    /// accurate.
+    pub fn prune(&mut self, retention_days: u64) -> usize {
+        let first_day = (current_day() + 1).saturating_sub(retention_days);
+        let before = self.daily_records.len();
+        self.daily_records.retain(|r| r.day() >= first_day);
+        before - self.daily_records.len()
+    }
+
+    /// Deletes the records for days `from` through `to` and takes their
+    /// keystrokes off `total_count`, returning what was removed.
+    pub fn remove_days(&mut self, from: u64, to: u64) -> Vec<DailyRecord> {
+        let (removed, kept): (Vec<DailyRecord>, Vec<DailyRecord>) =
+            std::mem::take(&mut self.daily_records)
+                .into_iter()
+                .partition(|r| (from..=to).contains(&r.day()));
+        self.daily_records = kept;
+        let keystrokes: u64 = removed.iter().map(|r| r.count).sum();
+        self.total_count = self.total_count.saturating_sub(keystrokes);
+        removed
+    }
+
+    /// Sums keystrokes by day of week, indexed Monday (0) through Sunday (6).
+    pub fn get_weekday_stats(&self) -> [u64; 7] {
+        let mut totals = [0; 7];
+        for record in &self.daily_records {
+            totals[weekday_from_day(record.day())] += record.count;
+        }
+        totals
+    }
+
+    pub fn get_monthly_stats(&self) -> u64 {
+        self.sum_last_days(30)
+    }
+
+    /// Mean keystrokes over the days that have a record, or 0.0 with none.
+    pub fn average_daily(&self) -> f64 {
+        if self.daily_records.is_empty() {
+            return 0.0;
+        }
+        let total: u64 = self.daily_records.iter().map(|r| r.count).sum();
+        total as f64 / self.daily_records.len() as f64
+    }
+
+    /// Counts of the days that were typed on, smallest first. Days without a
+    /// record, and records left at zero (e.g. clicks only), are skipped so
+    /// `daily_min`, `daily_median` and `daily_max` all describe the same days.
+    fn typed_day_counts(&self) -> Vec<u64> {
+        let mut counts: Vec<u64> = self
+            .daily_records
+            .iter()
+            .map(|r| r.count)
+            .filter(|&count| count > 0)
+            .collect();
+        counts.sort_unstable();
+        counts
+    }
+
+    /// Fewest keystrokes on a day that was typed on, or `None` with none.
+    pub fn daily_min(&self) -> Option<u64> {
+        self.typed_day_counts().first().copied()
+    }
+
+    /// Most keystrokes on a day that was typed on, or `None` with none.
+    pub fn daily_max(&self) -> Option<u64> {
+        self.typed_day_counts().last().copied()
+    }
+
+    /// Median over the days that were typed on. With an even number of days
+    /// this is the mean of the two middle ones.
+    pub fn daily_median(&self) -> Option<f64> {
+        let counts = self.typed_day_counts();
+        let mid = counts.len() / 2;
+        match counts.len() {
+            0 => None,
+            len if len.is_multiple_of(2) => Some((counts[mid - 1] + counts[mid]) as f64 / 2.0),
+            _ => Some(counts[mid] as f64),
+        }
+    }
+
+    /// How many days fall into each `DISTRIBUTION_BOUNDS` bucket, labelled
+    /// like `501–1000` with `range` between the bounds.
+    pub fn get_distribution(&self, range: &str) -> Vec<(String, u64)> {
+        let mut buckets = vec![0; DISTRIBUTION_BOUNDS.len() + 1];
+        for record in &self.daily_records {
+            let index = DISTRIBUTION_BOUNDS
+                .iter()
+                .position(|&upper| record.count <= upper)
+                .unwrap_or(DISTRIBUTION_BOUNDS.len());
+            buckets[index] += 1;
+        }
+
+        let mut lower = 0;
+        let mut labels = Vec::new();
+        for &upper in DISTRIBUTION_BOUNDS {
+            labels.push(if lower == upper {
+                upper.to_string()
+            } else {
+                format!("{}{}{}", lower, range, upper)
+            });
+            lower = upper + 1;
+        }
+        labels.push(format!("{}+", lower));
+
+        labels.into_iter().zip(buckets).collect()
+    }
+
+    pub fn get_yearly_stats(&self) -> u64 {
+        self.sum_last_days(365)
+    }
+
+    /// Keystrokes per calendar month as `("YYYY-MM", count)`, oldest first.
+    pub fn get_months_stats(&self) -> Vec<(String, u64)> {
+        let mut months = std::collections::BTreeMap::new();
+        for record in &self.daily_records {
+            let (year, month, _) = civil_from_days(record.day() as i64);
+            *months.entry((year, month)).or_insert(0) += record.count;
+        }
+        months
+            .into_iter()
+            .map(|((year, month), count)| (year_month_key(year, month), count))
+            .collect()
+    }
+}
+
+/// Inclusive upper bounds of the `stats --distribution` buckets; anything
+/// above the last one lands in a final open-ended bucket.
+const DISTRIBUTION_BOUNDS: &[u64] = &[0, 500, 1000, 2000];
+
+/// Coarse kind of key, the only detail kept about what was pressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyCategory {
+    Alphanumeric,
+    Modifier,
+    Navigation,
+    Function,
+    Whitespace,
+    Other,
+}
+
+impl KeyCategory {
+    const ALL: [KeyCategory; 6] = [
+        KeyCategory::Alphanumeric,
+        KeyCategory::Modifier,
+        KeyCategory::Navigation,
+        KeyCategory::Function,
+        KeyCategory::Whitespace,
+        KeyCategory::Other,
+    ];
+
+    pub fn of(key: &Key) -> Self {
+        use Key::*;
+        match key {
+            KeyA | KeyB | KeyC | KeyD | KeyE | KeyF | KeyG | KeyH | KeyI | KeyJ | KeyK | KeyL
+            | KeyM | KeyN | KeyO | KeyP | KeyQ | KeyR | KeyS | KeyT | KeyU | KeyV | KeyW | KeyX
+            | KeyY | KeyZ | Num0 | Num1 | Num2 | Num3 | Num4 | Num5 | Num6 | Num7 | Num8 | Num9
+            | Kp0 | Kp1 | Kp2 | Kp3 | Kp4 | Kp5 | Kp6 | Kp7 | Kp8 | Kp9 => {
+                KeyCategory::Alphanumeric
+            }
+            Alt | AltGr | ControlLeft | ControlRight | MetaLeft | MetaRight | ShiftLeft
+            | ShiftRight | CapsLock | Function => KeyCategory::Modifier,
+            UpArrow | DownArrow | LeftArrow | RightArrow | Home | End | PageUp | PageDown => {
+                KeyCategory::Navigation
+            }
+            F1 | F2 | F3 | F4 | F5 | F6 | F7 | F8 | F9 | F10 | F11 | F12 | Escape | PrintScreen
+            | ScrollLock | Pause | NumLock | Insert => KeyCategory::Function,
+            Space | Tab | Return | KpReturn => KeyCategory::Whitespace,
+            _ => KeyCategory::Other,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyCategory::Alphanumeric => "alphanumeric",
+            KeyCategory::Modifier => "modifier",
+            KeyCategory::Navigation => "navigation",
+            KeyCategory::Function => "function",
+            KeyCategory::Whitespace => "whitespace",
+            KeyCategory::Other => "other",
+        }
+    }
+}
+
+/// User settings read from `config.toml` in the config directory.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Save the data file after this many counted events.
+    pub save_every: u64,
+    /// Save unsaved counts at least this often, in seconds.
+    pub save_interval_secs: u64,
+    /// Never write more often than this, in seconds, however fast counts
+    /// come in. Stopping the daemon always saves.
+    pub min_save_interval_secs: u64,
+    /// How often the daemon writes its heartbeat, in seconds. `status`
+    /// calls it stale after three intervals without one.
+    pub heartbeat_interval_secs: u64,
+    /// Daily keystroke target shown by `stats`, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daily_goal: Option<u64>,
+    /// Fixed offset from UTC used to decide which day a keystroke belongs to.
+    /// Falls back to the system time zone when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utc_offset_seconds: Option<i64>,
+    /// Storage backend for keystroke history: `json` or `sqlite`.
+    pub storage: StorageKind,
+    /// Drop daily records older than this many days. Keeps everything when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention_days: Option<u64>,
+    /// Gaps between keystrokes longer than this many seconds don't count as
+    /// active typing time.
+    pub idle_threshold_secs: u64,
+    /// Rotate `daemon.log` to `daemon.log.1` once it grows past this size.
+    pub log_max_bytes: u64,
+    /// Encrypt `data.json` with a passphrase. Only applies to the JSON backend.
+    pub encrypt: bool,
+    /// Events that add to the keystroke total.
+    pub count: Vec<CountedEvent>,
+    /// Count auto-repeated presses from a held key. When off, only distinct
+    /// presses go into the totals.
+    pub count_repeats: bool,
+    /// `human` (`02 Mar 2024`) or `iso` (`2024-03-02`) dates in output.
+    pub date_format: DateFormat,
+    /// Color scheme for `stats` and `init`.
+    pub theme: ThemeKind,
+    /// Run `on_command` each time today's count passes a multiple of this.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_daily_milestone: Option<u64>,
+    /// Shell command the daemon runs on a milestone or a new best day, with
+    /// `KEYSTR_MILESTONE` set to the count.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_command: Option<String>,
+    /// Lifetime totals to celebrate on top of every power of ten from 1,000.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lifetime_milestones: Vec<u64>,
+    /// Only count while the focused window's app name, class or title
+    /// contains this (case-insensitive). Counts everything when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter_app: Option<String>,
+    /// Local times of day when counting is allowed, like `"09:00-17:30"`.
+    /// Counts at any time when empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduleWindow>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            save_every: 10,
+            save_interval_secs: 30,
+            min_save_interval_secs: 5,
+            heartbeat_interval_secs: 5,
+            storage: StorageKind::Json,
+            daily_goal: None,
+            utc_offset_seconds: None,
+            retention_days: None,
+            idle_threshold_secs: 60,
+            log_max_bytes: 1024 * 1024,
+            encrypt: false,
+            count: vec![CountedEvent::Keys],
+            count_repeats: true,
+            date_format: DateFormat::Human,
+            theme: ThemeKind::Default,
+            on_daily_milestone: None,
+            on_command: None,
+            lifetime_milestones: Vec::new(),
+            filter_app: None,
+            schedule: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Rejects settings that parse but would do damage.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.retention_days == Some(0) {
+            return Err("retention_days must be at least 1; 0 would delete today's record".into());
+        }
+        // `data.db` and `events.jsonl` would keep the history in plain text.
+        if self.encrypt && matches!(self.storage, StorageKind::Sqlite | StorageKind::Jsonl) {
+            return Err("encrypt only works with storage = \"json\" or \"bincode\"".into());
+        }
+        Ok(())
+    }
+}
+
+/// Snapshot the daemon writes to `live.json` once per second.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LiveStats {
+    pub kpm: u64,
+    pub updated: u64,
+}
+
+/// Outcome of `KeystrokeData::merge`.
+pub struct MergeSummary {
+    /// Days that existed in both histories and were summed.
+    pub days_merged: usize,
+    /// Days only present in the imported history.
+    pub days_added: usize,
+    pub keystrokes_added: u64,
+}
+
+/// Profile selected with `--profile`; unset means the default profile.
+pub static PROFILE: OnceLock<String> = OnceLock::new();
+
+pub fn active_profile() -> Option<&'static str> {
+    PROFILE.get().map(String::as_str)
+}
+
+/// File given with `--data-file`. Beats `KEYSTR_DATA_DIR` and the
+/// configured storage, and is never passed on to a daemon.
+pub static DATA_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn parse_profile_name(input: &str) -> Result<String, String> {
+    let valid = !input.is_empty()
+        && input
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(input.to_string())
+    } else {
+        Err("profile names may only contain letters, digits, '-' and '_'".to_string())
+    }
+}
+
+/// Resolves the directory holding the default profile, honoring `KEYSTR_DATA_DIR`.
+///
+/// Otherwise it lives in the platform config directory, which on Linux is
+/// `$XDG_CONFIG_HOME` when that's set to an absolute path and `~/.config`
+/// otherwise.
+pub fn get_base_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("KEYSTR_DATA_DIR").filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
+    }
+
+    base_dir_from(dirs::config_dir(), dirs::home_dir())
+}
+
+/// `<config>/keystroke`, or `.keystr` in the home (or current) directory on
+/// minimal systems without a config directory.
+pub fn base_dir_from(config_dir: Option<PathBuf>, home_dir: Option<PathBuf>) -> PathBuf {
+    match config_dir {
+        Some(config_dir) => config_dir.join("keystroke"),
+        None => home_dir.unwrap_or_default().join(".keystr"),
+    }
+}
+
+pub fn get_profiles_dir() -> PathBuf {
+    let mut path = get_base_dir();
+    path.push("profiles");
+    path
+}
+
+/// Resolves the directory holding the active profile's state.
+pub fn get_config_dir() -> PathBuf {
+    match active_profile() {
+        Some(name) => get_profiles_dir().join(name),
+        None => get_base_dir(),
+    }
+}
+
+/// Names of the profiles under `profiles_dir`, sorted.
+pub fn list_profiles(profiles_dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(profiles_dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+pub fn get_data_file() -> PathBuf {
+    if let Some(path) = DATA_FILE.get() {
+        return path.clone();
+    }
+    let mut path = get_config_dir();
+    path.push("data.json");
+    path
+}
+
+/// Append-only log used by the `jsonl` backend.
+pub fn get_events_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("events.jsonl");
+    path
+}
+
+pub fn get_database_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("data.db");
+    path
+}
+
+/// Data file used by the `bincode` backend.
+pub fn get_binary_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("data.bin");
+    path
+}
+
+pub fn get_config_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("config.toml");
+    path
+}
+
+/// JSON config written by earlier versions, still read if no TOML exists.
+fn get_legacy_config_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("config.json");
+    path
+}
+
+pub fn get_live_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("live.json");
+    path
+}
+
+pub fn get_pid_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("daemon.pid");
+    path
+}
+
+/// Held exclusively by the running daemon for its whole lifetime.
+pub fn get_daemon_lock_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("daemon.lock");
+    path
+}
+
+/// When `export --incremental` last ran, as a Unix timestamp.
+pub fn get_export_marker_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("export.marker");
+    path
+}
+
+/// The last incremental export's timestamp, or `None` before the first one.
+pub fn read_export_marker() -> Option<u64> {
+    fs::read_to_string(get_export_marker_file())
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+pub fn get_log_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("daemon.log");
+    path
+}
+
+/// Loads the active profile's statistics from its configured storage.
+pub fn load_data() -> Result<KeystrokeData, String> {
+    storage().load()
+}
+
+/// Reads and validates a keystr data file, upgrading older layouts.
+pub fn read_data_file(path: &std::path::Path) -> Result<KeystrokeData, String> {
+    fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| decode_data(&content))
+}
+
+/// Upgrades a parsed data file to `SCHEMA_VERSION`, one version at a time.
+///
+/// Files written before versioning was introduced have no `schema_version`
+/// field and are treated as version 0.
+pub fn migrate_data(mut value: serde_json::Value) -> Result<KeystrokeData, serde_json::Error> {
+    use serde::de::Error;
+    use serde_json::{Value, json};
+
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| serde_json::Error::custom("data file is not a JSON object"))?;
+    let mut version = object
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as u32;
+
+    if version == 0 {
+        // v1 added hourly buckets and mouse clicks.
+        object
+            .entry("hourly_counts")
+            .or_insert_with(|| json!(vec![0u64; 24]));
+        object.entry("total_clicks").or_insert_with(|| json!(0));
+        if let Some(records) = object
+            .get_mut("daily_records")
+            .and_then(Value::as_array_mut)
+        {
+            for record in records.iter_mut().filter_map(Value::as_object_mut) {
+                record.entry("clicks").or_insert_with(|| json!(0));
+            }
+        }
+        version = 1;
+    }
+
+    if version == 1 {
+        // v2 split raw presses from distinct ones. Older counts can't be told
+        // apart, so both start from the total.
+        let total = object.get("total_count").cloned().unwrap_or(json!(0));
+        object.entry("raw_total").or_insert_with(|| total.clone());
+        object.entry("distinct_total").or_insert(total);
+        version = 2;
+    }
+
+    object.insert("schema_version".to_string(), json!(version));
+    serde_json::from_value(value)
+}
+
+/// Loads the config file, falling back to defaults when there isn't one.
+/// A file that doesn't parse is an error, never a quiet reset of every
+/// setting.
+pub fn read_config() -> Result<Config, String> {
+    read_config_in(&get_config_dir())
+}
+
+/// `read_config` for the profile kept in `dir`.
+pub fn read_config_in(dir: &std::path::Path) -> Result<Config, String> {
+    let mut path = dir.join("config.toml");
+    let config: Config = if let Ok(content) = fs::read_to_string(&path) {
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?
+    } else {
+        path = dir.join("config.json");
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            Err(_) => return Ok(Config::default()),
+        }
+    };
+    config
+        .validate()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(config)
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Reads the settings for this invocation, so a broken `config.toml` can be
+/// reported before anything needs them.
+pub fn init_config() -> Result<&'static Config, String> {
+    if let Some(config) = CONFIG.get() {
+        return Ok(config);
+    }
+    let config = read_config()?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// Settings for this invocation, read from disk the first time they're needed.
+///
+/// Panics if `config.toml` is invalid; call `init_config` first to handle that.
+pub fn config() -> &'static Config {
+    init_config().unwrap_or_else(|error| panic!("Invalid config: {}", error))
+}
+
+pub fn save_config(config: &Config) -> Result<(), String> {
+    let content = toml::to_string_pretty(config).expect("Failed to serialize config");
+    fs::write(get_config_file(), content)
+        .map_err(|e| format!("{}: {}", get_config_file().display(), e))?;
+    let _ = fs::remove_file(get_legacy_config_file());
+    Ok(())
+}
+
+pub fn save_data(data: &KeystrokeData) -> Result<(), String> {
+    storage().save(data)
+}
+
+/// Where keystroke history is persisted, chosen by the `storage` config key.
+pub trait Storage {
+    /// File backing this store.
+    fn path(&self) -> PathBuf;
+    fn load(&self) -> Result<KeystrokeData, String>;
+    fn save(&self, data: &KeystrokeData) -> Result<(), String>;
+    /// Persists `day`'s record and the running totals, leaving other days
+    /// untouched. Backends that can't update a single day just save it all.
+    fn increment_day(&self, data: &KeystrokeData, day: u64) -> Result<(), String> {
+        let _ = day;
+        self.save(data)
+    }
+    /// File that changes as the daemon counts, for `watch --watch-file`.
+    fn watch_path(&self) -> PathBuf {
+        self.path()
+    }
+}
+
+/// How dates are shown by `stats`, `export` and `report`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DateFormat {
+    /// `02 Mar 2024` (the default).
+    #[default]
+    Human,
+    /// `2024-03-02`, which sorts correctly as text.
+    Iso,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeKind {
+    /// Bright cyan and green on a dark terminal (the default).
+    #[default]
+    Default,
+    /// Bold and dim only, no hues.
+    Monochrome,
+    /// The Solarized accents, readable on light and dark backgrounds.
+    Solarized,
+}
+
+/// A kind of input event that can add to the keystroke total.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CountedEvent {
+    /// Key presses.
+    Keys,
+    /// Mouse button presses, which are tallied as clicks either way.
+    Mouse,
+    /// Scroll wheel steps.
+    Scroll,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    /// A single `data.json` file (the default).
+    #[default]
+    Json,
+    /// A `data.db` SQLite database.
+    Sqlite,
+    /// A `data.json` snapshot plus an append-only `events.jsonl`.
+    Jsonl,
+    /// A compact binary `data.bin`.
+    Bincode,
+}
+
+pub fn storage() -> Box<dyn Storage> {
+    if DATA_FILE.get().is_some() {
+        return Box::new(JsonStorage {
+            path: get_data_file(),
+        });
+    }
+    storage_for(config().storage)
+}
+
+/// The store for `kind`, whatever the config says.
+pub fn storage_for(kind: StorageKind) -> Box<dyn Storage> {
+    match kind {
+        StorageKind::Json => Box::new(JsonStorage {
+            path: get_data_file(),
+        }),
+        StorageKind::Sqlite => Box::new(SqliteStorage {
+            path: get_database_file(),
+        }),
+        StorageKind::Jsonl => Box::new(JsonlStorage::new()),
+        StorageKind::Bincode => Box::new(BincodeStorage {
+            path: get_binary_file(),
+        }),
+    }
+}
+
+pub struct JsonStorage {
+    pub path: PathBuf,
+}
+
+impl JsonStorage {
+    fn lock(&self, exclusive: bool) -> Option<fs::File> {
+        lock_sidecar(&self.path, exclusive)
+    }
+}
+
+/// Takes an advisory lock on `<path>.lock`, released when the handle is
+/// dropped. Data files are replaced on every save, so they can't carry the
+/// lock themselves. Locking is best effort: if the lock file can't be opened
+/// we carry on unlocked.
+fn lock_sidecar(path: &std::path::Path, exclusive: bool) -> Option<fs::File> {
+    let mut lock_path = path.to_path_buf().into_os_string();
+    lock_path.push(".lock");
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)
+        .ok()?;
+    let locked = if exclusive {
+        file.lock()
+    } else {
+        file.lock_shared()
+    };
+    locked.ok().map(|_| file)
+}
+
+impl Storage for JsonStorage {
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn load(&self) -> Result<KeystrokeData, String> {
+        let _lock = self.lock(false);
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(KeystrokeData::new());
+            }
+            Err(error) => return Err(format!("{}: {}", self.path.display(), error)),
+        };
+        match decode_data(&content) {
+            Ok(data) => Ok(data),
+            // Resetting would overwrite the encrypted history on the next save.
+            Err(error) if is_encrypted(&content) => Err(format!(
+                "Could not decrypt {}: {}",
+                self.path.display(),
+                error
+            )),
+            Err(_) => {
+                // Keep the unreadable file around instead of silently discarding it.
+                let mut backup = self.path.clone().into_os_string();
+                backup.push(".bak");
+                let _ = fs::copy(&self.path, backup);
+                Ok(KeystrokeData::new())
+            }
+        }
+    }
+
+    fn save(&self, data: &KeystrokeData) -> Result<(), String> {
+        let bytes = encode_data(data)?;
+
+        // Write a sibling file and rename it over the original so readers
+        // only ever see a complete file.
+        let _lock = self.lock(true);
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        fs::write(&temp, bytes)
+            .and_then(|()| fs::rename(&temp, &self.path))
+            .map_err(|error| format!("{}: {}", self.path.display(), error))
+    }
+}
+
+/// One line of `events.jsonl`.
+#[derive(Serialize, Deserialize)]
+struct LoggedEvent {
+    /// When the line was written, as a Unix timestamp.
+    ts: u64,
+    /// Local day the line was written on.
+    day: u64,
+    /// Everything counted since the previous line, merged in on replay.
+    delta: KeystrokeData,
+}
+
+/// What this process last read from or wrote to an event log, so a save
+/// only has to append the difference.
+pub static JSONL_BASE: Mutex<Option<(PathBuf, KeystrokeData)>> = Mutex::new(None);
+
+/// The `jsonl` backend. Saves append what changed to `events.jsonl` instead
+/// of rewriting the whole history; `data.json` only changes on `compact`, or
+/// when a save takes counts away (reset, prune, restore).
+pub struct JsonlStorage {
+    pub snapshot: JsonStorage,
+    pub log: PathBuf,
+}
+
+impl Default for JsonlStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonlStorage {
+    pub fn new() -> Self {
+        JsonlStorage {
+            snapshot: JsonStorage {
+                path: get_data_file(),
+            },
+            log: get_events_file(),
+        }
+    }
+
+    /// The snapshot with every event merged in. Lines that don't parse, like
+    /// a last line torn by a crash mid-append, are skipped.
+    fn replay(&self) -> Result<KeystrokeData, String> {
+        let mut data = self.snapshot.load()?;
+        let content = match fs::read_to_string(&self.log) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(format!("{}: {}", self.log.display(), error)),
+        };
+        for line in content.lines() {
+            if let Ok(event) = serde_json::from_str::<LoggedEvent>(line) {
+                data.merge(&event.delta);
+            }
+        }
+        Ok(data)
+    }
+
+    /// Writes `data` as the new snapshot and empties the log. The snapshot
+    /// goes first: a crash in between replays events twice rather than
+    /// losing them.
+    fn write_snapshot(&self, data: &KeystrokeData) -> Result<(), String> {
+        self.snapshot.save(data)?;
+        fs::write(&self.log, "").map_err(|error| format!("{}: {}", self.log.display(), error))
+    }
+
+    /// Folds the log into `data.json`, returning how many events it held.
+    pub fn compact(&self) -> Result<usize, String> {
+        let _lock = lock_sidecar(&self.log, true);
+        let events = fs::read_to_string(&self.log)
+            .map(|content| content.lines().count())
+            .unwrap_or(0);
+        let data = self.replay()?;
+        self.write_snapshot(&data)?;
+        *JSONL_BASE.lock().unwrap() = Some((self.log.clone(), data));
+        Ok(events)
+    }
+}
+
+impl Storage for JsonlStorage {
+    fn path(&self) -> PathBuf {
+        self.snapshot.path.clone()
+    }
+
+    fn watch_path(&self) -> PathBuf {
+        self.log.clone()
+    }
+
+    fn load(&self) -> Result<KeystrokeData, String> {
+        let data = {
+            let _lock = lock_sidecar(&self.log, false);
+            self.replay()?
+        };
+        *JSONL_BASE.lock().unwrap() = Some((self.log.clone(), data.clone()));
+        Ok(data)
+    }
+
+    fn save(&self, data: &KeystrokeData) -> Result<(), String> {
+        use std::io::Write;
+
+        let _lock = lock_sidecar(&self.log, true);
+        let mut base = JSONL_BASE.lock().unwrap();
+        if base.as_ref().is_none_or(|(log, _)| *log != self.log) {
+            *base = Some((self.log.clone(), self.replay()?));
+        }
+        let (_, previous) = base.as_mut().unwrap();
+
+        let delta = data.delta_since(previous);
+        match delta {
+            Some(delta) if self.snapshot.path.exists() => {
+                if delta.is_empty() {
+                    return Ok(());
+                }
+                let event = LoggedEvent {
+                    ts: current_timestamp(),
+                    day: current_day(),
+                    delta,
+                };
+                let mut line = serde_json::to_string(&event).expect("Failed to serialize event");
+                line.push('\n');
+                fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.log)
+                    .and_then(|mut file| file.write_all(line.as_bytes()))
+                    .map_err(|error| format!("{}: {}", self.log.display(), error))?;
+            }
+            // Nothing to append to yet, or counts went down.
+            _ => self.write_snapshot(data)?,
+        }
+        *previous = data.clone();
+        Ok(())
+    }
+}
+
+/// Prefix of an encrypted data file, followed by the salt, nonce and
+/// ChaCha20-Poly1305 ciphertext of the JSON.
+const ENCRYPTED_MAGIC: &[u8] = b"KEYSTR-ENC1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Passphrase for this invocation: `KEYSTR_PASSPHRASE`, a prompt, or for the
+/// daemon the line `start` writes to its stdin. Never written to disk.
+static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+/// Last derived key, so saving doesn't rerun Argon2 every time.
+static DERIVED_KEY: Mutex<Option<DerivedKey>> = Mutex::new(None);
+
+struct DerivedKey {
+    passphrase: String,
+    salt: [u8; SALT_LEN],
+    key: [u8; 32],
+}
+
+pub fn passphrase() -> Result<&'static str, String> {
+    if let Some(passphrase) = PASSPHRASE.get() {
+        return Ok(passphrase);
+    }
+    let passphrase = match std::env::var("KEYSTR_PASSPHRASE") {
+        Ok(passphrase) if !passphrase.is_empty() => passphrase,
+        _ => rpassword::prompt_password("  Passphrase: ")
+            .map_err(|e| format!("Could not read passphrase: {}", e))?,
+    };
+    Ok(PASSPHRASE.get_or_init(|| passphrase))
+}
+
+/// Reads the passphrase `start` pipes to the daemon, which keeps it out of
+/// the daemon's environment where other processes could read it. The line
+/// it arrived in is wiped once it's copied out.
+pub fn receive_passphrase(mut input: impl std::io::BufRead) -> Result<(), String> {
+    use zeroize::Zeroize;
+
+    let mut line = String::new();
+    let read = input.read_line(&mut line).map_err(|e| e.to_string());
+    let received = line.trim_end_matches(['\r', '\n']).to_string();
+    line.zeroize();
+    read?;
+    if received.is_empty() {
+        return Err("no passphrase was handed over; start it with keystr start".to_string());
+    }
+    let _ = PASSPHRASE.set(received);
+    Ok(())
+}
+
+/// Asks for a new passphrase twice and uses it for the rest of the run.
+/// `KEYSTR_PASSPHRASE` skips the prompt for scripted setups.
+pub fn prompt_new_passphrase() -> Result<(), String> {
+    if std::env::var("KEYSTR_PASSPHRASE").is_ok_and(|p| !p.is_empty()) {
+        return passphrase().map(|_| ());
+    }
+
+    let read = |prompt: &str| rpassword::prompt_password(prompt).unwrap_or_default();
+    let first = read("  New passphrase: ");
+    if first.is_empty() || first != read("  Repeat passphrase: ") {
+        return Err("Passphrases were empty or did not match".to_string());
+    }
+    let _ = PASSPHRASE.set(first);
+    Ok(())
+}
+
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(ENCRYPTED_MAGIC)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8; SALT_LEN]) -> [u8; 32] {
+    let mut cached = DERIVED_KEY.lock().unwrap();
+    if let Some(derived) = cached.as_ref()
+        && derived.passphrase == passphrase
+        && derived.salt == *salt
+    {
+        return derived.key;
+    }
+
+    let mut key = [0; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .expect("Argon2 accepts a 16-byte salt and 32-byte key");
+    *cached = Some(DerivedKey {
+        passphrase: passphrase.to_string(),
+        salt: *salt,
+        key,
+    });
+    key
+}
+
+pub fn encrypt_bytes(plaintext: &[u8], passphrase: &str) -> Vec<u8> {
+    use chacha20poly1305::ChaCha20Poly1305;
+    use chacha20poly1305::aead::rand_core::RngCore;
+    use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+
+    // Reuse the salt of the last key for this passphrase so only the nonce
+    // changes between saves.
+    let cached_salt = DERIVED_KEY
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|derived| derived.passphrase == passphrase)
+        .map(|derived| derived.salt);
+    let salt = cached_salt.unwrap_or_else(|| {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        salt
+    });
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt).into());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .expect("Encrypting in memory cannot fail");
+
+    let mut bytes = ENCRYPTED_MAGIC.to_vec();
+    bytes.extend_from_slice(&salt);
+    bytes.extend_from_slice(&nonce);
+    bytes.extend_from_slice(&ciphertext);
+    bytes
+}
+
+pub fn decrypt_bytes(bytes: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    use chacha20poly1305::ChaCha20Poly1305;
+    use chacha20poly1305::aead::{Aead, KeyInit};
+
+    let body = bytes
+        .strip_prefix(ENCRYPTED_MAGIC)
+        .filter(|body| body.len() >= SALT_LEN + NONCE_LEN)
+        .ok_or("not an encrypted keystr file")?;
+    let (salt, rest) = body.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let salt: &[u8; SALT_LEN] = salt.try_into().expect("split at SALT_LEN");
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt).into());
+    cipher
+        .decrypt(nonce.into(), ciphertext)
+        .map_err(|_| "wrong passphrase or damaged file".to_string())
+}
+
+/// Key `keystr share` signs tokens with. Created by `keystr init` and never
+/// leaves this machine.
+pub fn get_share_key_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("share.key");
+    path
+}
+
+const SHARE_KEY_LEN: usize = 32;
+/// Bytes of the HMAC kept in a token; 128 bits is plenty to stop forgery
+/// and keeps tokens short.
+const SHARE_MAC_LEN: usize = 16;
+const SHARE_TOKEN_PREFIX: &str = "ks1";
+
+/// Writes a fresh random share key unless one exists, returning whether it
+/// did. The file is private to the user where the platform allows.
+pub fn ensure_share_key() -> Result<bool, String> {
+    use chacha20poly1305::aead::OsRng;
+    use chacha20poly1305::aead::rand_core::RngCore;
+    use std::io::Write;
+
+    let path = get_share_key_file();
+    if path.exists() {
+        return Ok(false);
+    }
+    let mut key = [0; SHARE_KEY_LEN];
+    OsRng.fill_bytes(&mut key);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(&key))
+        .map_err(|error| format!("{}: {}", path.display(), error))?;
+    Ok(true)
+}
+
+pub fn load_share_key() -> Result<Vec<u8>, String> {
+    let path = get_share_key_file();
+    match fs::read(&path) {
+        Ok(key) if key.len() == SHARE_KEY_LEN => Ok(key),
+        Ok(_) => Err(format!("{}: not a keystr share key", path.display())),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            Err("no share key yet; run keystr init to create one".to_string())
+        }
+        Err(error) => Err(format!("{}: {}", path.display(), error)),
+    }
+}
+
+/// The headline numbers in a `keystr share` token. Nothing per-day.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ShareSummary {
+    pub total: u64,
+    pub weekly: u64,
+    pub streak: u32,
+    /// The day it was made, `YYYY-MM-DD`.
+    pub date: String,
+}
+
+impl ShareSummary {
+    pub fn of(data: &KeystrokeData, today: u64) -> Self {
+        ShareSummary {
+            total: data.total_count,
+            weekly: data.get_weekly_stats(),
+            streak: data.current_streak(),
+            date: format_iso_day(today),
+        }
+    }
+}
+
+fn share_mac(payload: &str, key: &[u8]) -> hmac::Hmac<sha2::Sha256> {
+    use hmac::Mac;
+
+    let mut mac =
+        hmac::Hmac::<sha2::Sha256>::new_from_slice(key).expect("HMAC takes any key length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// `ks1.<summary>.<mac>`, both parts unpadded URL-safe base64 so the token
+/// survives chat apps and URLs.
+pub fn sign_share(summary: &ShareSummary, key: &[u8]) -> String {
+    use base64ct::{Base64UrlUnpadded, Encoding};
+    use hmac::Mac;
+
+    let json = serde_json::to_string(summary).expect("Failed to serialize summary");
+    let payload = Base64UrlUnpadded::encode_string(json.as_bytes());
+    let tag = share_mac(&payload, key).finalize().into_bytes();
+    format!(
+        "{}.{}.{}",
+        SHARE_TOKEN_PREFIX,
+        payload,
+        Base64UrlUnpadded::encode_string(&tag[..SHARE_MAC_LEN])
+    )
+}
+
+/// The summary in `token`, if `key` signed it and nothing was changed.
+pub fn verify_share(token: &str, key: &[u8]) -> Result<ShareSummary, String> {
+    use base64ct::{Base64UrlUnpadded, Encoding};
+    use hmac::Mac;
+
+    let malformed = || "not a keystr share token".to_string();
+    let mut parts = token.trim().split('.');
+    let (Some(SHARE_TOKEN_PREFIX), Some(payload), Some(tag), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(malformed());
+    };
+    let tag = Base64UrlUnpadded::decode_vec(tag).map_err(|_| malformed())?;
+    if tag.len() != SHARE_MAC_LEN || share_mac(payload, key).verify_truncated_left(&tag).is_err() {
+        return Err(
+            "signature doesn't match: the token was changed or signed with another key".to_string(),
+        );
+    }
+    let json = Base64UrlUnpadded::decode_vec(payload).map_err(|_| malformed())?;
+    serde_json::from_slice(&json).map_err(|_| malformed())
+}
+
+/// Parses a data file, decrypting it first if needed and upgrading older layouts.
+fn decode_data(bytes: &[u8]) -> Result<KeystrokeData, String> {
+    let decrypted;
+    let json = if is_encrypted(bytes) {
+        decrypted = decrypt_bytes(bytes, passphrase()?)?;
+        &decrypted[..]
+    } else {
+        bytes
+    };
+    serde_json::from_slice(json)
+        .and_then(migrate_data)
+        .map_err(|e| e.to_string())
+}
+
+/// Serializes data for a data file, encrypted when `encrypt` is on.
+pub fn encode_data(data: &KeystrokeData) -> Result<Vec<u8>, String> {
+    let json = serde_json::to_string_pretty(data).expect("Failed to serialize data");
+    if config().encrypt {
+        Ok(encrypt_bytes(json.as_bytes(), passphrase()?))
+    } else {
+        Ok(json.into_bytes())
+    }
+}
+
+/// Marks a `data.bin` file, followed by its schema version.
+const BINARY_MAGIC: &[u8] = b"KSTRBIN\0";
+
+/// Packs `data` for `data.bin`: the magic, `SCHEMA_VERSION` as four
+/// little-endian bytes, then the data in bincode.
+pub fn encode_binary(data: &KeystrokeData) -> Vec<u8> {
+    let mut bytes = BINARY_MAGIC.to_vec();
+    bytes.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+    let payload = bincode::serde::encode_to_vec(data, bincode::config::standard())
+        .expect("Failed to serialize data");
+    bytes.extend(payload);
+    bytes
+}
+
+/// Unpacks a `data.bin` file written by `encode_binary`.
+///
+/// Unlike JSON, bincode says nothing about which fields it holds, so the
+/// version up front is what tells layouts apart. A schema bump has to keep
+/// decoding the previous layout here and upgrade it.
+pub fn decode_binary(bytes: &[u8]) -> Result<KeystrokeData, String> {
+    let rest = bytes
+        .strip_prefix(BINARY_MAGIC)
+        .ok_or("not a keystr binary data file")?;
+    let (version, payload) = rest
+        .split_first_chunk::<4>()
+        .ok_or("binary data file is truncated")?;
+    match u32::from_le_bytes(*version) {
+        SCHEMA_VERSION => {
+            let (data, read): (KeystrokeData, usize) =
+                bincode::serde::decode_from_slice(payload, bincode::config::standard())
+                    .map_err(|e| e.to_string())?;
+            if read != payload.len() {
+                return Err("binary data file has trailing bytes".to_string());
+            }
+            Ok(data)
+        }
+        version if version > SCHEMA_VERSION => Err(format!(
+            "binary data file uses schema {}, newer than this keystr supports ({})",
+            version, SCHEMA_VERSION
+        )),
+        version => Err(format!(
+            "binary data file uses schema {}, which has no binary layout",
+            version
+        )),
+    }
+}
+
+/// Keeps everything in one bincode `data.bin`, smaller and quicker to read
+/// than `data.json` once history grows long. Encrypted as a whole when
+/// `encrypt` is on, like `data.json`.
+pub struct BincodeStorage {
+    pub path: PathBuf,
+}
+
+impl Storage for BincodeStorage {
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn load(&self) -> Result<KeystrokeData, String> {
+        let _lock = lock_sidecar(&self.path, false);
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(KeystrokeData::new());
+            }
+            Err(error) => return Err(format!("{}: {}", self.path.display(), error)),
+        };
+        let decrypted;
+        let bytes = if is_encrypted(&content) {
+            decrypted = passphrase()
+                .and_then(|passphrase| decrypt_bytes(&content, passphrase))
+                .map_err(|error| format!("{}: {}", self.path.display(), error))?;
+            &decrypted[..]
+        } else {
+            &content[..]
+        };
+        decode_binary(bytes).map_err(|error| format!("{}: {}", self.path.display(), error))
+    }
+
+    fn save(&self, data: &KeystrokeData) -> Result<(), String> {
+        let mut bytes = encode_binary(data);
+        if config().encrypt {
+            bytes = encrypt_bytes(&bytes, passphrase()?);
+        }
+        let _lock = lock_sidecar(&self.path, true);
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        fs::write(&temp, bytes)
+            .and_then(|()| fs::rename(&temp, &self.path))
+            .map_err(|error| format!("{}: {}", self.path.display(), error))
+    }
+}
+
+/// Stores one row per day in a `daily` table so history can be queried
+/// directly. Everything else lives in `meta`: the lifetime total as its own
+/// row, plus the remaining fields as a JSON `state` row.
+pub struct SqliteStorage {
+    pub path: PathBuf,
+}
+
+impl SqliteStorage {
+    fn open(&self) -> rusqlite::Result<rusqlite::Connection> {
+        let conn = rusqlite::Connection::open(&self.path)?;
+        // `user_version` records that the tables exist, so they're only
+        // created the first time rather than on every save.
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version == 0 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS daily (
+                     day INTEGER PRIMARY KEY,
+                     count INTEGER NOT NULL,
+                     record TEXT NOT NULL
+                 );
+                 CREATE TABLE IF NOT EXISTS meta (
+                     key TEXT PRIMARY KEY,
+                     value
+                 );
+                 PRAGMA user_version = 1;",
+            )?;
+        }
+        Ok(conn)
+    }
+
+    fn write_day(conn: &rusqlite::Connection, record: &DailyRecord) -> rusqlite::Result<()> {
+        let json = serde_json::to_string(record).expect("Failed to serialize record");
+        conn.execute(
+            "INSERT INTO daily (day, count, record) VALUES (?1, ?2, ?3)
+             ON CONFLICT(day) DO UPDATE SET count = excluded.count, record = excluded.record",
+            rusqlite::params![record.day() as i64, record.count as i64, json],
+        )?;
+        Ok(())
+    }
+
+    fn write_meta(conn: &rusqlite::Connection, data: &KeystrokeData) -> rusqlite::Result<()> {
+        let state = KeystrokeData {
+            daily_records: Vec::new(),
+            ..data.clone()
+        };
+        let json = serde_json::to_string(&state).expect("Failed to serialize data");
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('total_count', ?1), ('state', ?2)",
+            rusqlite::params![data.total_count as i64, json],
+        )?;
+        Ok(())
+    }
+
+    fn try_load(&self) -> rusqlite::Result<KeystrokeData> {
+        let conn = self.open()?;
+        let state: Option<String> = conn
+            .query_row("SELECT value FROM meta WHERE key = 'state'", [], |row| {
+                row.get(0)
+            })
+            .ok();
+        let mut data = state
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .and_then(|value| migrate_data(value).ok())
+            .unwrap_or_default();
+
+        let mut statement = conn.prepare("SELECT count, record FROM daily ORDER BY day")?;
+        let rows = statement.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for (count, json) in rows.flatten() {
+            if let Ok(mut record) = serde_json::from_str::<DailyRecord>(&json) {
+                // The column is authoritative so ad-hoc SQL edits stick.
+                record.count = count as u64;
+                data.daily_records.push(record);
+            }
+        }
+
+        if let Ok(total) = conn.query_row(
+            "SELECT value FROM meta WHERE key = 'total_count'",
+            [],
+            |row| row.get::<_, i64>(0),
+        ) {
+            data.total_count = total as u64;
+        }
+        Ok(data)
+    }
+
+    fn try_save(&self, data: &KeystrokeData) -> rusqlite::Result<()> {
+        let mut conn = self.open()?;
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM daily", [])?;
+        for record in &data.daily_records {
+            Self::write_day(&tx, record)?;
+        }
+        Self::write_meta(&tx, data)?;
+        tx.commit()
+    }
+
+    fn try_increment_day(&self, data: &KeystrokeData, day: u64) -> rusqlite::Result<()> {
+        let mut conn = self.open()?;
+        let tx = conn.transaction()?;
+        if let Some(record) = data.daily_records.iter().find(|r| r.day() == day) {
+            Self::write_day(&tx, record)?;
+        }
+        Self::write_meta(&tx, data)?;
+        tx.commit()
+    }
+
+    fn describe(&self, error: rusqlite::Error) -> String {
+        format!("{}: {}", self.path.display(), error)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn load(&self) -> Result<KeystrokeData, String> {
+        if !self.path.exists() {
+            // Switched to `sqlite` without running `init`: carry on from the
+            // JSON history instead of starting again from nothing. The first
+            // save copies it into the database.
+            let json = JsonStorage {
+                path: self.path.with_file_name("data.json"),
+            };
+            if json.path.exists() {
+                eprintln!(
+                    "note: {} doesn't exist yet, so history is read from {}",
+                    self.path.display(),
+                    json.path.display()
+                );
+                return json.load();
+            }
+            return Ok(KeystrokeData::new());
+        }
+        self.try_load().map_err(|error| self.describe(error))
+    }
+
+    fn save(&self, data: &KeystrokeData) -> Result<(), String> {
+        self.try_save(data).map_err(|error| self.describe(error))
+    }
+
+    fn increment_day(&self, data: &KeystrokeData, day: u64) -> Result<(), String> {
+        self.try_increment_day(data, day)
+            .map_err(|error| self.describe(error))
+    }
+}
+
+/// Converts days since the Unix epoch into a (year, month, day) civil date.
+///
+/// Based on Howard Hinnant's `civil_from_days` algorithm, which is exact for
+/// the proleptic Gregorian calendar (including the 100/400 year leap rules).
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Converts a civil date into days since the Unix epoch.
+///
+/// The inverse of `civil_from_days`, from the same paper by Howard Hinnant.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parses a `YYYY-MM-DD` date into days since the Unix epoch.
+pub fn parse_date(input: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid date '{}', expected YYYY-MM-DD", input);
+
+    let mut parts = input.trim().splitn(3, '-');
+    let year: i64 = parts
+        .next()
+        .and_then(|p| p.parse().ok())
+        .ok_or_else(invalid)?;
+    let month: u32 = parts
+        .next()
+        .and_then(|p| p.parse().ok())
+        .ok_or_else(invalid)?;
+    let day: u32 = parts
+        .next()
+        .and_then(|p| p.parse().ok())
+        .ok_or_else(invalid)?;
+
+    let days = days_from_civil(year, month, day);
+    // Reject dates like 2024-02-30 that don't round-trip.
+    if !(1..=12).contains(&month) || civil_from_days(days) != (year, month, day) {
+        return Err(invalid());
+    }
+    u64::try_from(days).map_err(|_| format!("date '{}' is before 1970-01-01", input))
+}
+
+/// Hours of the day from `start` up to, but not including, `end`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HourRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl HourRange {
+    /// `09:00–17:00`, with `range` between the hours.
+    pub fn label(self, range: &str) -> String {
+        format!("{:02}:00{}{:02}:00", self.start, range, self.end)
+    }
+}
+
+/// Parses `START-END` hours like `9-17`, where `END` is exclusive and may
+/// be 24.
+pub fn parse_hour_range(input: &str) -> Result<HourRange, String> {
+    let invalid = || format!("invalid hour range '{}', expected e.g. 9-17", input);
+    let (start, end) = input.trim().split_once('-').ok_or_else(invalid)?;
+    let start: usize = start.trim().parse().map_err(|_| invalid())?;
+    let end: usize = end.trim().parse().map_err(|_| invalid())?;
+    if start >= end || end > 24 {
+        return Err(format!(
+            "invalid hour range '{}': hours run 0-24 and the start must come before the end",
+            input
+        ));
+    }
+    Ok(HourRange { start, end })
+}
+
+/// Keystrokes within `range`, and all keystrokes with an hour recorded.
+pub fn work_hour_totals(hourly_counts: &[u64; 24], range: HourRange) -> (u64, u64) {
+    let within = hourly_counts[range.start..range.end].iter().sum();
+    (within, hourly_counts.iter().sum())
+}
+
+/// Keystrokes on each of the `days` days ending with `today`, oldest first.
+pub fn daily_counts(data: &KeystrokeData, today: u64, days: u64) -> Vec<u64> {
+    if days == 0 {
+        return Vec::new();
+    }
+    (today.saturating_sub(days - 1)..=today)
+        .map(|day| {
+            data.daily_records
+                .iter()
+                .filter(|r| r.day() == day)
+                .map(|r| r.count)
+                .sum()
+        })
+        .collect()
+}
+
+/// Keystrokes per ISO week (`2024-W09`), oldest first.
+pub fn weekly_breakdown(data: &KeystrokeData) -> Vec<(String, u64)> {
+    let mut weeks = std::collections::BTreeMap::new();
+    for record in &data.daily_records {
+        *weeks.entry(iso_week(record.day())).or_insert(0) += record.count;
+    }
+    weeks
+        .into_iter()
+        .map(|((year, week), count)| (format!("{:04}-W{:02}", year, week), count))
+        .collect()
+}
+
+/// `records` with each count replaced by the running total up to that day.
+pub fn cumulative_records(records: &[DailyRecord]) -> Vec<DailyRecord> {
+    let mut total = 0;
+    records
+        .iter()
+        .map(|record| {
+            total += record.count;
+            DailyRecord {
+                count: total,
+                ..record.clone()
+            }
+        })
+        .collect()
+}
+
+/// Centered moving average of `values` over `window` entries. Near the
+/// edges the window shrinks to the entries that exist.
+pub fn moving_average(values: &[u64], window: usize) -> Vec<u64> {
+    let before = window.saturating_sub(1) / 2;
+    let after = window / 2;
+    (0..values.len())
+        .map(|i| {
+            let slice = &values[i.saturating_sub(before)..(i + after + 1).min(values.len())];
+            (slice.iter().sum::<u64>() as f64 / slice.len() as f64).round() as u64
+        })
+        .collect()
+}
+
+/// Each count's share of the total in tenths of a percent. Rounds by
+/// largest remainder, so the shares always add up to exactly 100.0%.
+pub fn percent_tenths(counts: &[u64]) -> Vec<u64> {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return vec![0; counts.len()];
+    }
+    let exact: Vec<u128> = counts.iter().map(|&c| c as u128 * 1000).collect();
+    let mut shares: Vec<u64> = exact.iter().map(|&e| (e / total as u128) as u64).collect();
+    let mut order: Vec<usize> = (0..counts.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(exact[i] % total as u128));
+    let short = 1000 - shares.iter().sum::<u64>();
+    for &i in order.iter().take(short as usize) {
+        shares[i] += 1;
+    }
+    shares
+}
+
+/// One of `levels` per value, scaled so the largest gets the last.
+pub fn sparkline(values: &[u64], levels: &[char; 8]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&value| {
+            let level = if max == 0 {
+                0
+            } else {
+                (value * 7).div_ceil(max) as usize
+            };
+            levels[level]
+        })
+        .collect()
+}
+
+/// A stretch of the day, `start` up to but not including `end`, in minutes
+/// after local midnight. A window whose end comes before its start, like
+/// `22:00-02:00`, runs past midnight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ScheduleWindow {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl ScheduleWindow {
+    pub fn contains(self, minute: u32) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl std::str::FromStr for ScheduleWindow {
+    type Err = String;
+
+    /// Parses `HH:MM-HH:MM`, where `24:00` may close a window.
+    fn from_str(input: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "invalid schedule window '{}', expected e.g. 09:00-17:30",
+                input
+            )
+        };
+        let (start, end) = input.trim().split_once(['-', '–']).ok_or_else(invalid)?;
+        let minutes = |time: &str| -> Option<u32> {
+            let (hours, minutes) = time.trim().split_once(':')?;
+            let hours: u32 = hours.parse().ok()?;
+            let minutes: u32 = minutes.parse().ok()?;
+            (minutes < 60 && hours * 60 + minutes <= 24 * 60).then_some(hours * 60 + minutes)
+        };
+        let start = minutes(start).filter(|&start| start < 24 * 60);
+        let (Some(start), Some(end)) = (start, minutes(end)) else {
+            return Err(invalid());
+        };
+        if start == end % (24 * 60) {
+            return Err(format!(
+                "invalid schedule window '{}': it starts and ends at the same time",
+                input
+            ));
+        }
+        Ok(ScheduleWindow { start, end })
+    }
+}
+
+impl TryFrom<String> for ScheduleWindow {
+    type Error = String;
+
+    fn try_from(input: String) -> Result<Self, String> {
+        input.parse()
+    }
+}
+
+impl From<ScheduleWindow> for String {
+    fn from(window: ScheduleWindow) -> String {
+        window.to_string()
+    }
+}
+
+impl fmt::Display for ScheduleWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+/// Whether counting is allowed at `minute` after local midnight. Windows
+/// may overlap, in which case any one of them is enough; no windows at all
+/// means no schedule, so counting is always on.
+pub fn schedule_allows(schedule: &[ScheduleWindow], minute: u32) -> bool {
+    schedule.is_empty() || schedule.iter().any(|window| window.contains(minute))
+}
+
+/// Minutes after local midnight at `timestamp`.
+pub fn local_minute(timestamp: u64) -> u32 {
+    let seconds = timestamp.saturating_add_signed(utc_offset_at(timestamp)) % 86400;
+    (seconds / 60) as u32
+}
+
+/// Parses a duration like `90d`, `12w`, `6mo` or `1y` into seconds. A month
+/// counts as 30 days and a year as 365.
+pub fn parse_duration(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| {
+        format!(
+            "invalid duration '{}', expected e.g. 7d, 2w, 1mo or 1y",
+            input
+        )
+    })?;
+    let days = match unit {
+        "d" => 1,
+        "w" => 7,
+        "mo" => 30,
+        "y" => 365,
+        _ => {
+            return Err(format!(
+                "invalid duration unit '{}' in '{}', expected d, w, mo or y",
+                unit, input
+            ));
+        }
+    };
+    amount
+        .checked_mul(days * 86400)
+        .ok_or_else(|| format!("duration '{}' is too long", input))
+}
+
+/// The local day `seconds` before `now`, as the first day `--since` keeps.
+pub fn since_day(seconds: u64, now: u64) -> u64 {
+    local_day(now.saturating_sub(seconds))
+}
+
+/// Formats a day (days since the Unix epoch) as `YYYY-MM-DD`.
+pub fn format_iso_day(day: u64) -> String {
+    let (year, month, day) = civil_from_days(day as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Formats a day (days since the Unix epoch) for display, in the
+/// configured `date_format`.
+pub fn format_day(day: u64) -> String {
+    format_day_as(day, config().date_format)
+}
+
+pub fn format_day_as(day: u64, format: DateFormat) -> String {
+    match format {
+        DateFormat::Human => format_date_display(&(UNIX_EPOCH + Duration::from_secs(day * 86400))),
+        DateFormat::Iso => format_iso_day(day),
+    }
+}
+
+pub const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+pub const WEEKDAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+pub fn format_date_display(time: &SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let days_since_epoch = (secs / 86400) as i64;
+    let (year, month, day) = civil_from_days(days_since_epoch);
+
+    format!("{:02} {} {}", day, MONTH_NAMES[month as usize - 1], year)
+}
+
+pub fn year_month_key(year: i64, month: u32) -> String {
+    format!("{:04}-{:02}", year, month)
+}
+
+/// The twelve calendar months ending with the one containing `day`, oldest first.
+pub fn last_twelve_months(day: u64) -> Vec<(i64, u32)> {
+    let (year, month, _) = civil_from_days(day as i64);
+    let current = year * 12 + i64::from(month) - 1;
+    (current - 11..=current)
+        .map(|index| (index.div_euclid(12), index.rem_euclid(12) as u32 + 1))
+        .collect()
+}
+
+/// Day of week for a day since the epoch, with Monday as 0 and Sunday as 6.
+pub fn weekday_from_day(days: u64) -> usize {
+    // (days + 4) % 7 counts from Sunday, as 1970-01-01 was a Thursday.
+    let from_sunday = (days + 4) % 7;
+    ((from_sunday + 6) % 7) as usize
+}
+
+/// ISO 8601 (year, week) of a day since the Unix epoch. Weeks start on
+/// Monday, and week 1 is the one holding the year's first Thursday.
+pub fn iso_week(day: u64) -> (i64, u32) {
+    let thursday = day as i64 - weekday_from_day(day) as i64 + 3;
+    let (year, _, _) = civil_from_days(thursday);
+    let week = (thursday - days_from_civil(year, 1, 1)) / 7 + 1;
+    (year, week as u32)
+}
+
+/// First and last day (Monday and Sunday) of the week before `today`'s.
+pub fn last_complete_week(today: u64) -> (u64, u64) {
+    let monday = today - weekday_from_day(today) as u64;
+    (monday - 7, monday - 1)
+}
+
+pub fn format_date_storage() -> String {
+    current_day().to_string()
+}
+
+/// Today as local days since the Unix epoch.
+pub fn current_day() -> u64 {
+    local_day(current_timestamp())
+}
+
+/// Local calendar day (days since the epoch) that `timestamp` falls on.
+pub fn local_day(timestamp: u64) -> u64 {
+    day_with_offset(timestamp, utc_offset_at(timestamp))
+}
+
+pub fn day_with_offset(timestamp: u64, offset_seconds: i64) -> u64 {
+    timestamp.saturating_add_signed(offset_seconds) / 86400
+}
+
+pub fn hour_with_offset(timestamp: u64, offset_seconds: i64) -> usize {
+    ((timestamp.saturating_add_signed(offset_seconds) % 86400) / 3600) as usize
+}
+
+/// Seconds east of UTC used to bucket `timestamp` into local days.
+///
+/// `utc_offset_seconds` in the config wins; otherwise the system time zone
+/// is asked, so daylight saving changes are picked up as they happen.
+pub fn utc_offset_at(timestamp: u64) -> i64 {
+    config()
+        .utc_offset_seconds
+        .unwrap_or_else(|| system_utc_offset(timestamp))
+}
+
+#[cfg(unix)]
+fn system_utc_offset(timestamp: u64) -> i64 {
+    let time = timestamp as libc::time_t;
+    // SAFETY: localtime_r only writes into the zeroed tm we hand it.
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            0
+        } else {
+            tm.tm_gmtoff as i64
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn system_utc_offset(_timestamp: u64) -> i64 {
+    0
+}
+
+static LAST_GOOD_TIMESTAMP: AtomicU64 = AtomicU64::new(0);
+static CLOCK_BEFORE_EPOCH: AtomicBool = AtomicBool::new(false);
+
+pub fn current_timestamp() -> u64 {
+    timestamp_at(SystemTime::now())
+}
+
+/// Seconds since the Unix epoch. A clock reading before 1970 (a bad RTC, or
+/// an NTP step still in progress) repeats the last good reading instead of
+/// panicking, and is remembered for [`take_clock_warning`].
+pub fn timestamp_at(time: SystemTime) -> u64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => {
+            let secs = duration.as_secs();
+            LAST_GOOD_TIMESTAMP.store(secs, Ordering::Relaxed);
+            secs
+        }
+        Err(_) => {
+            CLOCK_BEFORE_EPOCH.store(true, Ordering::Relaxed);
+            LAST_GOOD_TIMESTAMP.load(Ordering::Relaxed)
+        }
+    }
+}
+
+/// Whether the clock has read before 1970 since the last call, so the caller
+/// can warn about it once.
+pub fn take_clock_warning() -> bool {
+    CLOCK_BEFORE_EPOCH.swap(false, Ordering::Relaxed)
+}
+
+pub const CLOCK_WARNING: &str =
+    "system clock reads before 1970; reusing the last good time until it is corrected";
+
+/// Local time as `YYYY-MM-DD HH:MM:SS` for log lines.
+pub fn format_log_time(timestamp: u64, offset_seconds: i64) -> String {
+    let local = timestamp.saturating_add_signed(offset_seconds);
+    let (year, month, day) = civil_from_days((local / 86400) as i64);
+    let seconds = local % 86400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Local `HH:MM` for `timestamp`.
+pub fn format_clock(timestamp: u64) -> String {
+    format_clock_with_offset(timestamp, utc_offset_at(timestamp))
+}
+
+pub fn format_clock_with_offset(timestamp: u64, offset_seconds: i64) -> String {
+    let seconds = timestamp.saturating_add_signed(offset_seconds) % 86400;
+    format!("{:02}:{:02}", seconds / 3600, seconds % 3600 / 60)
+}
+
+/// What the PID file says about the background daemon.
+enum DaemonState {
+    Running(u32),
+    /// A PID file exists but its process is gone or the PID was reused.
+    Stale(u32),
+    Stopped,
+}
+
+/// Contents of the PID file: the daemon's PID and the time it started.
+pub fn pid_file_contents() -> String {
+    format!("{}\n{}\n", std::process::id(), current_timestamp())
+}
+
+fn daemon_state() -> DaemonState {
+    let pid_file = get_pid_file();
+    let Ok(content) = fs::read_to_string(&pid_file) else {
+        return DaemonState::Stopped;
+    };
+
+    let mut lines = content.lines();
+    let Some(pid) = lines.next().and_then(|l| l.trim().parse::<u32>().ok()) else {
+        let _ = fs::remove_file(&pid_file);
+        return DaemonState::Stopped;
+    };
+    // Older PID files only hold the PID, so the start time is optional.
+    let started = lines.next().and_then(|l| l.trim().parse::<u64>().ok());
+
+    if process_alive(pid) && started.is_none_or(|ts| process_started_near(pid, ts)) {
+        DaemonState::Running(pid)
+    } else {
+        DaemonState::Stale(pid)
+    }
+}
+
+pub fn is_running() -> Option<u32> {
+    match daemon_state() {
+        DaemonState::Running(pid) => Some(pid),
+        DaemonState::Stale(_) => {
+            let _ = fs::remove_file(get_pid_file());
+            None
+        }
+        DaemonState::Stopped => None,
+    }
+}
+
+/// Removes a stale PID file, returning the PID it named.
+pub fn clear_stale_pid_file() -> Option<u32> {
+    let DaemonState::Stale(pid) = daemon_state() else {
+        return None;
+    };
+    let _ = fs::remove_file(get_pid_file());
+    Some(pid)
+}
+
+#[cfg(unix)]
+pub fn process_alive(pid: u32) -> bool {
+    Command::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[cfg(windows)]
+pub fn process_alive(pid: u32) -> bool {
+    let Ok(output) = Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+        .output()
+    else {
+        return false;
+    };
+
+    // Matching rows look like: "keystr.exe","1234",...
+    String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Checks that `pid` started around `started`, catching PIDs reused by other
+/// processes. Returns true when the start time cannot be determined.
+#[cfg(unix)]
+fn process_started_near(pid: u32, started: u64) -> bool {
+    let Ok(output) = Command::new("ps")
+        .args(["-o", "etime=", "-p", &pid.to_string()])
+        .output()
+    else {
+        return true;
+    };
+
+    match parse_elapsed_time(String::from_utf8_lossy(&output.stdout).trim()) {
+        Some(elapsed) => {
+            let process_start = current_timestamp().saturating_sub(elapsed);
+            process_start.abs_diff(started) <= 10
+        }
+        None => true,
+    }
+}
+
+#[cfg(not(unix))]
+fn process_started_near(_pid: u32, _started: u64) -> bool {
+    true
+}
+
+/// Parses `ps` elapsed time in the `[[dd-]hh:]mm:ss` format into seconds.
+pub fn parse_elapsed_time(etime: &str) -> Option<u64> {
+    let (days, clock) = match etime.split_once('-') {
+        Some((days, clock)) => (days.parse::<u64>().ok()?, clock),
+        None => (0, etime),
+    };
+
+    let mut seconds = 0;
+    let mut parts = 0;
+    for part in clock.split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+        parts += 1;
+    }
+    if !(2..=3).contains(&parts) {
+        return None;
+    }
+
+    Some(days * 86400 + seconds)
+}
+
+#[cfg(target_os = "linux")]
+pub fn is_autostart_enabled() -> bool {
+    let service_name = "keystr";
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .arg("is-enabled")
+        .arg(service_name)
+        .output();
+
+    // No systemctl at all just means there's nothing enabled.
+    output.is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "enabled")
+}
+
+#[cfg(target_os = "macos")]
+pub fn is_autostart_enabled() -> bool {
+    dirs::home_dir().is_some_and(|home| {
+        home.join("Library/LaunchAgents/com.keystr.daemon.plist")
+            .exists()
+    })
+}
+
+#[cfg(windows)]
+pub fn is_autostart_enabled() -> bool {
+    Command::new("schtasks")
+        .args(["/Query", "/TN", "keystr"])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn is_autostart_enabled() -> bool {
+    false
+}
+
+/// Whether macOS lets this process observe input events. Without the
+/// Accessibility permission the event tap can't be created.
+#[cfg(target_os = "macos")]
+pub fn has_input_permission() -> bool {
+    #[link(name = "ApplicationServices", kind = "framework")]
+    unsafe extern "C" {
+        fn AXIsProcessTrusted() -> u8;
+    }
+
+    // SAFETY: AXIsProcessTrusted takes no arguments and only reads state.
+    unsafe { AXIsProcessTrusted() != 0 }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn has_input_permission() -> bool {
+    true
+}
+
+pub const MACOS_PERMISSION_HINT: &str = "grant Accessibility and Input Monitoring access to your \
+     terminal (or keystr) in System Settings > Privacy & Security";
+
+/// Whether this looks like a Wayland session, where keystr can only see
+/// input through XWayland.
+pub fn on_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some_and(|v| !v.is_empty())
+        || std::env::var("XDG_SESSION_TYPE").is_ok_and(|v| v == "wayland")
+}
+
+pub const WAYLAND_HINT: &str = "Wayland doesn't let apps read global keyboard input, so keystr \
+     needs an X11 session, or XWayland with DISPLAY set";
+
+/// Turns a `listen` failure into a message the user can act on.
+pub fn describe_listen_error(error: &ListenError, wayland: bool) -> String {
+    match error {
+        ListenError::EventTapError => format!(
+            "macOS refused to create the input event tap; {}",
+            MACOS_PERMISSION_HINT
+        ),
+        // rdev reports a display it can't open as a keyboard error.
+        ListenError::MissingDisplayError | ListenError::KeyboardError if wayland => {
+            format!("no X display found. {}", WAYLAND_HINT)
+        }
+        ListenError::MissingDisplayError | ListenError::KeyboardError => {
+            "no X display found; make sure DISPLAY is set for the daemon".to_string()
+        }
+        ListenError::XRecordExtensionError
+        | ListenError::RecordContextError
+        | ListenError::RecordContextEnablingError
+            if wayland =>
+        {
+            format!("XWayland won't record keyboard input. {}", WAYLAND_HINT)
+        }
+        ListenError::XRecordExtensionError => {
+            "the X server doesn't support the RECORD extension keystr listens through".to_string()
+        }
+        other => format!("could not listen for input events: {:?}", other),
+    }
+}
+
+/// How far the daemon got, for `start` to check: `listening`, or `error: `
+/// followed by the reason it gave up.
+pub fn get_status_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("daemon.status");
+    path
+}
+
+/// Rewritten by the running daemon every `heartbeat_interval_secs` with the
+/// current Unix time.
+pub fn get_heartbeat_file() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("daemon.heartbeat");
+    path
+}
+
+pub fn write_heartbeat(timestamp: u64) {
+    let _ = fs::write(get_heartbeat_file(), timestamp.to_string());
+}
+
+/// Seconds since the daemon's last heartbeat, or `None` if there isn't one,
+/// as with a daemon from before heartbeats.
+pub fn heartbeat_age(now: u64) -> Option<u64> {
+    let beat: u64 = fs::read_to_string(get_heartbeat_file())
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(now.saturating_sub(beat))
+}
+
+pub fn write_daemon_status(status: &str) {
+    let _ = fs::write(get_status_file(), status);
+}
+
+/// Waits for a freshly spawned daemon to report in. `Ok` once it's
+/// listening, `Err` with its reason if it gave up, or `Ok` after the timeout
+/// if it's running but quiet (an older binary, say).
+pub fn wait_for_daemon_status() -> Result<(), String> {
+    const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+    let deadline = Instant::now() + START_TIMEOUT;
+    loop {
+        let status = fs::read_to_string(get_status_file()).unwrap_or_default();
+        if status == "listening" {
+            return Ok(());
+        }
+        if let Some(reason) = status.strip_prefix("error: ") {
+            return Err(reason.to_string());
+        }
+        if Instant::now() >= deadline {
+            return match is_running() {
+                Some(_) => Ok(()),
+                None => Err("the monitor exited without saying why".to_string()),
+            };
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("keystr-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn display_at(secs: u64) -> String {
+        format_date_display(&(UNIX_EPOCH + Duration::from_secs(secs)))
+    }
+
+    #[test]
+    fn format_date_display_epoch() {
+        assert_eq!(display_at(0), "01 Jan 1970");
+        assert_eq!(display_at(86399), "01 Jan 1970");
+    }
+
+    #[test]
+    fn format_date_display_leap_days() {
+        assert_eq!(display_at(951_782_400), "29 Feb 2000");
+        assert_eq!(display_at(951_868_800), "01 Mar 2000");
+        assert_eq!(display_at(1_709_164_800), "29 Feb 2024");
+    }
+
+    #[test]
+    fn format_day_honors_the_date_format() {
+        assert_eq!(format_day_as(19_784, DateFormat::Human), "02 Mar 2024");
+        assert_eq!(format_day_as(19_784, DateFormat::Iso), "2024-03-02");
+        assert_eq!(format_day_as(0, DateFormat::Iso), "1970-01-01");
+
+        let config: Config = toml::from_str("date_format = \"iso\"").unwrap();
+        assert_eq!(config.date_format, DateFormat::Iso);
+        assert_eq!(Config::default().date_format, DateFormat::Human);
+    }
+
+    #[test]
+    fn format_date_display_recent_dates() {
+        assert_eq!(display_at(1_704_067_200), "01 Jan 2024");
+        assert_eq!(display_at(1_735_603_200), "31 Dec 2024");
+        assert_eq!(display_at(1_759_795_200), "07 Oct 2025");
+    }
+
+    #[test]
+    fn old_data_without_new_fields_loads() {
+        let json = r#"{"total_count": 3, "daily_records": [{"date": "20000", "count": 3, "timestamp": 1728000000}]}"#;
+        let data: KeystrokeData = serde_json::from_str(json).unwrap();
+        assert_eq!(data.total_count, 3);
+        assert_eq!(data.hourly_counts, [0; 24]);
+        assert_eq!(data.total_clicks, 0);
+        assert_eq!(data.daily_records[0].clicks, 0);
+        assert_eq!(data.daily_records[0].active_seconds, 0);
+        assert_eq!(data.daily_records[0].sessions, 1);
+        assert_eq!(data.peak_kpm, 0);
+        assert_eq!(data.peak_timestamp, 0);
+    }
+
+    #[test]
+    fn lifetime_totals_are_earned_once() {
+        let mut data = KeystrokeData::new();
+        data.total_count = 999;
+        assert!(data.check_achievements(998, &[]).is_empty());
+        data.total_count = 1000;
+        assert_eq!(data.check_achievements(999, &[]), [1000]);
+        data.total_count = 1001;
+        assert!(data.check_achievements(1000, &[]).is_empty());
+
+        // A jump past several thresholds earns them all, custom ones included.
+        data.total_count = 12_000;
+        assert_eq!(data.check_achievements(1001, &[5000, 1000]), [5000, 10_000]);
+
+        // Counting back up after a reset doesn't earn a total twice.
+        data.total_count = 1000;
+        assert!(data.check_achievements(0, &[]).is_empty());
+        let totals: Vec<u64> = data.achievements.iter().map(|a| a.total).collect();
+        assert_eq!(totals, [1000, 5000, 10_000]);
+
+        let old: KeystrokeData =
+            serde_json::from_str(r#"{"total_count": 3, "daily_records": []}"#).unwrap();
+        assert!(old.achievements.is_empty());
+    }
+
+    #[test]
+    fn removing_days_takes_them_off_the_total() {
+        let mut data = data_with_days(&[10, 11, 12, 13]);
+        data.daily_records[1].count = 40;
+        data.daily_records[2].count = 2;
+        data.total_count = 44;
+
+        let removed = data.remove_days(11, 12);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(data.total_count, 2);
+        let left: Vec<u64> = data.daily_records.iter().map(|r| r.day()).collect();
+        assert_eq!(left, [10, 13]);
+
+        assert!(data.remove_days(20, 30).is_empty());
+        assert_eq!(data.total_count, 2);
+    }
+
+    #[test]
+    fn idle_gaps_split_the_day_into_sessions() {
+        let mut data = KeystrokeData::new();
+        // The break leading into the day's first keystroke began yesterday.
+        data.record_gap(Some(8 * 3600), 60);
+        data.record_gap(Some(2), 60);
+        data.record_gap(Some(60), 60);
+        data.record_gap(Some(600), 60);
+        data.record_gap(None, 60);
+        data.record_gap(Some(3120), 60);
+        let today = data.get_today_record().unwrap();
+        assert_eq!(today.sessions, 4);
+        assert_eq!(today.longest_gap, 3120);
+
+        let mut fresh = KeystrokeData::new();
+        fresh.record_gap(Some(5), 60);
+        assert_eq!(fresh.get_today_record().unwrap().sessions, 1);
+        assert_eq!(fresh.get_today_record().unwrap().longest_gap, 0);
+    }
+
+    #[test]
+    fn distribution_buckets_days_by_count() {
+        let mut data = data_with_days(&[1, 2, 3, 4, 5, 6]);
+        for (record, count) in data
+            .daily_records
+            .iter_mut()
+            .zip([0, 1, 500, 501, 2000, 9000])
+        {
+            record.count = count;
+        }
+
+        assert_eq!(
+            data.get_distribution("–"),
+            [
+                ("0".to_string(), 1),
+                ("1–500".to_string(), 2),
+                ("501–1000".to_string(), 1),
+                ("1001–2000".to_string(), 1),
+                ("2001+".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn keys_are_bucketed_into_categories() {
+        assert_eq!(KeyCategory::of(&Key::KeyA), KeyCategory::Alphanumeric);
+        assert_eq!(KeyCategory::of(&Key::Kp7), KeyCategory::Alphanumeric);
+        assert_eq!(KeyCategory::of(&Key::ShiftLeft), KeyCategory::Modifier);
+        assert_eq!(KeyCategory::of(&Key::PageDown), KeyCategory::Navigation);
+        assert_eq!(KeyCategory::of(&Key::F5), KeyCategory::Function);
+        assert_eq!(KeyCategory::of(&Key::Return), KeyCategory::Whitespace);
+        assert_eq!(KeyCategory::of(&Key::Comma), KeyCategory::Other);
+        assert_eq!(KeyCategory::of(&Key::Unknown(42)), KeyCategory::Other);
+    }
+
+    #[test]
+    fn category_counts_are_totals_only() {
+        let mut data = KeystrokeData::new();
+        for key in [Key::KeyH, Key::KeyI, Key::Space, Key::KeyH] {
+            data.increment_category(KeyCategory::of(&key));
+        }
+
+        let json = serde_json::to_value(&data).unwrap();
+        assert_eq!(
+            json["category_counts"],
+            serde_json::json!({"alphanumeric": 3, "whitespace": 1})
+        );
+        let stats = data.get_category_stats();
+        assert_eq!(stats[0], (KeyCategory::Alphanumeric, 3));
+        assert_eq!(stats[5], (KeyCategory::Other, 0));
+    }
+
+    #[test]
+    fn peak_kpm_keeps_the_best_burst() {
+        let mut data = KeystrokeData::new();
+        data.record_kpm(120, 1_000);
+        data.record_kpm(310, 2_000);
+        data.record_kpm(200, 3_000);
+        assert_eq!((data.peak_kpm, data.peak_timestamp), (310, 2_000));
+
+        let mut other = KeystrokeData::new();
+        other.record_kpm(400, 4_000);
+        data.merge(&other);
+        assert_eq!((data.peak_kpm, data.peak_timestamp), (400, 4_000));
+    }
+
+    #[test]
+    fn clicks_are_counted_separately_from_keystrokes() {
+        let mut data = KeystrokeData::new();
+        data.increment();
+        data.increment_click();
+        data.increment_click();
+
+        assert_eq!(data.total_count, 1);
+        assert_eq!(data.total_clicks, 2);
+        assert_eq!(data.daily_records.len(), 1);
+        assert_eq!(data.daily_records[0].count, 1);
+        assert_eq!(data.daily_records[0].clicks, 2);
+    }
+
+    #[test]
+    fn profile_names_reject_paths() {
+        assert_eq!(parse_profile_name("work-2_a"), Ok("work-2_a".to_string()));
+        assert!(parse_profile_name("").is_err());
+        assert!(parse_profile_name("../work").is_err());
+        assert!(parse_profile_name("a/b").is_err());
+    }
+
+    #[test]
+    fn list_profiles_returns_sorted_directories() {
+        let dir = temp_dir("profiles");
+        assert!(list_profiles(&dir).is_empty());
+
+        fs::create_dir_all(dir.join("work")).unwrap();
+        fs::create_dir_all(dir.join("personal")).unwrap();
+        fs::write(dir.join("stray.txt"), "").unwrap();
+        assert_eq!(list_profiles(&dir), vec!["personal", "work"]);
+    }
+
+    #[test]
+    fn migrate_upgrades_unversioned_data() {
+        let v0 = serde_json::json!({
+            "total_count": 42,
+            "daily_records": [{"date": "20000", "count": 42, "timestamp": 1728000000}]
+        });
+
+        let data = migrate_data(v0).unwrap();
+
+        assert_eq!(data.schema_version, SCHEMA_VERSION);
+        assert_eq!(data.total_count, 42);
+        assert_eq!(data.daily_records.len(), 1);
+        assert_eq!(data.daily_records[0].count, 42);
+        assert_eq!(data.daily_records[0].clicks, 0);
+        assert_eq!(data.hourly_counts, [0; 24]);
+        assert_eq!(data.raw_total, 42);
+        assert_eq!(data.distinct_total, 42);
+    }
+
+    #[test]
+    fn seen_range_falls_back_to_the_record_timestamp() {
+        let mut record = data_with_days(&[20_000]).daily_records.remove(0);
+        assert_eq!(record.seen_range(), (20_000 * 86400, 20_000 * 86400));
+
+        record.first_seen = 20_000 * 86400 + 9 * 3600 + 14 * 60;
+        record.last_seen = 20_000 * 86400 + 18 * 3600 + 42 * 60;
+        let (first, last) = record.seen_range();
+        assert_eq!(format_clock_with_offset(first, 0), "09:14");
+        assert_eq!(format_clock_with_offset(last, 0), "18:42");
+        assert_eq!(format_clock_with_offset(last, -3600), "17:42");
+
+        let json = r#"{"date": "20000", "count": 1, "timestamp": 1728000000}"#;
+        let old: DailyRecord = serde_json::from_str(json).unwrap();
+        assert_eq!(old.seen_range(), (1_728_000_000, 1_728_000_000));
+    }
+
+    #[test]
+    #[ignore = "timing comparison; run with --ignored on a quiet machine"]
+    fn cached_increment_beats_scanning_a_year_of_records() {
+        /// The per-keypress path before `today_slot`: reformat the date
+        /// and scan every record.
+        fn increment_by_scan(data: &mut KeystrokeData) {
+            data.total_count += 1;
+            let now = current_timestamp();
+            data.hourly_counts[hour_with_offset(now, utc_offset_at(now))] += 1;
+            let today = format_date_storage();
+            let index = data.daily_records.iter().position(|r| r.date == today);
+            data.daily_records[index.unwrap()].count += 1;
+        }
+
+        let today = current_day();
+        let year: Vec<u64> = (today - 364..=today).collect();
+        let mut scanned = data_with_days(&year);
+        let mut cached = scanned.clone();
+        const PRESSES: u64 = 20_000;
+
+        let start = Instant::now();
+        for _ in 0..PRESSES {
+            increment_by_scan(&mut scanned);
+        }
+        let scan_time = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..PRESSES {
+            cached.increment();
+        }
+        let cached_time = start.elapsed();
+        assert!(cached_time < scan_time);
+    }
+
+    #[test]
+    fn cached_increment_counts_into_today() {
+        let today = current_day();
+        let year: Vec<u64> = (today - 364..=today).collect();
+        let mut data = data_with_days(&year);
+        for _ in 0..1000 {
+            data.increment();
+        }
+        assert_eq!(data.get_today_stats(), 1001);
+        assert_eq!(data.daily_records.len(), 365);
+
+        data.increment_n(500);
+        assert_eq!(data.total_count, 1500);
+        assert_eq!(data.get_today_stats(), 1501);
+    }
+
+    #[test]
+    fn today_cache_rechecks_the_offset_every_quarter_hour() {
+        let mut data = KeystrokeData::new();
+        let now = 20_000 * 86400 + 12 * 3600 + 100;
+        let cache = data.today_slot(now);
+        assert!(cache.until > now);
+        assert!(cache.until <= 20_000 * 86400 + 12 * 3600 + 900);
+        // Past that point the offset, and with it the day, is looked up again.
+        let later = data.today_slot(cache.until);
+        assert!(later.until > cache.until);
+        assert_eq!(data.daily_records.len(), 1);
+    }
+
+    #[test]
+    fn today_cache_follows_changes_to_the_records() {
+        let mut data = KeystrokeData::new();
+        data.increment();
+        data.daily_records
+            .insert(0, data_with_days(&[1]).daily_records.remove(0));
+        data.increment();
+        assert_eq!(data.get_today_stats(), 2);
+        assert_eq!(data.daily_records.len(), 2);
+    }
+
+    #[test]
+    fn corrupt_data_is_backed_up_before_reset() {
+        let dir = temp_dir("corrupt");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("data.json"), "{ not json").unwrap();
+
+        let data = JsonStorage {
+            path: dir.join("data.json"),
+        }
+        .load()
+        .unwrap();
+
+        assert_eq!(data.total_count, 0);
+        assert_eq!(
+            fs::read_to_string(dir.join("data.json.bak")).unwrap(),
+            "{ not json"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unwritable_storage_reports_errors() {
+        let dir = temp_dir("unwritable");
+        fs::create_dir_all(&dir).unwrap();
+        // A regular file where a directory should be fails even for root,
+        // unlike permission bits.
+        let blocker = dir.join("not-a-dir");
+        fs::write(&blocker, "").unwrap();
+
+        let json = JsonStorage {
+            path: blocker.join("data.json"),
+        };
+        let error = json.save(&KeystrokeData::new()).unwrap_err();
+        assert!(error.contains("not-a-dir/data.json"));
+        assert!(json.load().is_err());
+
+        let sqlite = SqliteStorage {
+            path: blocker.join("data.db"),
+        };
+        assert!(sqlite.save(&KeystrokeData::new()).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn base_dir_falls_back_without_a_config_dir() {
+        let config = Some(PathBuf::from("/home/a/.config"));
+        let home = Some(PathBuf::from("/home/a"));
+        assert_eq!(
+            base_dir_from(config, home.clone()),
+            PathBuf::from("/home/a/.config/keystroke")
+        );
+        assert_eq!(base_dir_from(None, home), PathBuf::from("/home/a/.keystr"));
+        assert_eq!(base_dir_from(None, None), PathBuf::from(".keystr"));
+    }
+
+    #[test]
+    fn interleaved_json_saves_and_loads_never_see_partial_data() {
+        let dir = temp_dir("json-stress");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.json");
+
+        let mut data = data_with_days(&(19_000..19_400).collect::<Vec<_>>());
+        data.total_count = 1;
+        JsonStorage { path: path.clone() }.save(&data).unwrap();
+
+        let writer_path = path.clone();
+        let writer = std::thread::spawn(move || {
+            let store = JsonStorage { path: writer_path };
+            for total in 2..=200 {
+                data.total_count = total;
+                store.save(&data).unwrap();
+            }
+        });
+
+        let store = JsonStorage { path: path.clone() };
+        let mut last_seen = 0;
+        for _ in 0..200 {
+            let loaded = store.load().unwrap();
+            assert!(loaded.total_count >= last_seen.max(1));
+            assert_eq!(loaded.daily_records.len(), 400);
+            last_seen = loaded.total_count;
+        }
+        writer.join().unwrap();
+
+        assert_eq!(store.load().unwrap().total_count, 200);
+        assert!(!dir.join("data.json.bak").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn weekday_from_known_days() {
+        // 1970-01-01 was a Thursday, 2025-10-07 a Tuesday, 2024-03-03 a Sunday.
+        assert_eq!(weekday_from_day(0), 3);
+        assert_eq!(weekday_from_day(1_759_795_200 / 86400), 1);
+        assert_eq!(weekday_from_day(1_709_424_000 / 86400), 6);
+    }
+
+    #[test]
+    fn utc_offset_moves_late_night_keystrokes_to_the_local_day() {
+        // 2025-10-08 03:30 UTC is still 7 Oct at 22:30 in UTC-5.
+        let timestamp = 1_759_894_200;
+        assert_eq!(day_with_offset(timestamp, 0), 20_369);
+        assert_eq!(day_with_offset(timestamp, -5 * 3600), 20_368);
+        assert_eq!(hour_with_offset(timestamp, -5 * 3600), 22);
+        assert_eq!(
+            format_day(day_with_offset(timestamp, -5 * 3600)),
+            "07 Oct 2025"
+        );
+        // And early UTC+10 mornings belong to the next day.
+        assert_eq!(day_with_offset(1_759_795_200 - 3600, 10 * 3600), 20_368);
+    }
+
+    #[test]
+    fn weekday_stats_bucket_by_record_day() {
+        let mut data = data_with_days(&[20_368, 20_361, 19_785]);
+        for (record, count) in data.daily_records.iter_mut().zip([5, 7, 2]) {
+            record.count = count;
+        }
+
+        assert_eq!(data.get_weekday_stats(), [0, 12, 0, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn config_falls_back_to_defaults() {
+        let dir = temp_dir("config");
+        fs::create_dir_all(&dir).unwrap();
+
+        assert_eq!(read_config_in(&dir).unwrap().save_every, 10);
+
+        fs::write(dir.join("config.json"), r#"{"save_every": 50}"#).unwrap();
+        assert_eq!(read_config_in(&dir).unwrap().save_every, 50);
+
+        fs::write(
+            dir.join("config.toml"),
+            "save_every = 25\ndaily_goal = 1000\n",
+        )
+        .unwrap();
+        let config = read_config_in(&dir).unwrap();
+        assert_eq!(config.save_every, 25);
+        assert_eq!(config.save_interval_secs, 30);
+        assert_eq!(config.daily_goal, Some(1000));
+        assert_eq!(config.utc_offset_seconds, None);
+
+        fs::write(dir.join("config.toml"), "save_every = \"often\"\n").unwrap();
+        let error = read_config_in(&dir).unwrap_err();
+        assert!(error.contains("config.toml"), "{}", error);
+        assert!(error.contains("save_every"), "{}", error);
+        fs::write(dir.join("config.toml"), "retention_days = 0\n").unwrap();
+        assert!(read_config_in(&dir).unwrap_err().contains("retention_days"));
+        fs::write(
+            dir.join("config.toml"),
+            "encrypt = true\nstorage = \"sqlite\"\n",
+        )
+        .unwrap();
+        assert!(read_config_in(&dir).unwrap_err().contains("encrypt"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn receive_passphrase_reads_one_line() {
+        assert!(receive_passphrase(&b""[..]).is_err());
+        assert!(receive_passphrase(&b"\n"[..]).is_err());
+        receive_passphrase(&b"correct horse\nrest\n"[..]).unwrap();
+        assert_eq!(passphrase(), Ok("correct horse"));
+    }
+
+    #[test]
+    fn parse_date_round_trips_civil_dates() {
+        assert_eq!(parse_date("1970-01-01"), Ok(0));
+        assert_eq!(parse_date("2000-02-29"), Ok(11_016));
+        assert_eq!(parse_date("2025-10-07"), Ok(20_368));
+        assert!(parse_date("2023-02-29").is_err());
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("1969-12-31").is_err());
+        assert!(parse_date("yesterday").is_err());
+    }
+
+    #[test]
+    fn schedule_windows_wrap_past_midnight_and_overlap() {
+        let window = |input: &str| input.parse::<ScheduleWindow>().unwrap();
+        assert_eq!(
+            window("09:00-17:30"),
+            ScheduleWindow {
+                start: 540,
+                end: 1050
+            }
+        );
+        assert_eq!(window("22:00 – 02:00").to_string(), "22:00-02:00");
+        assert_eq!(
+            window("18:00-24:00"),
+            ScheduleWindow {
+                start: 1080,
+                end: 1440
+            }
+        );
+        for bad in [
+            "9-17",
+            "09:00-09:00",
+            "00:00-24:00",
+            "24:00-01:00",
+            "09:60-10:00",
+            "09:00",
+        ] {
+            assert!(bad.parse::<ScheduleWindow>().is_err(), "{}", bad);
+        }
+
+        let night = [window("22:00-02:00")];
+        assert!(schedule_allows(&night, 23 * 60));
+        assert!(schedule_allows(&night, 60));
+        assert!(!schedule_allows(&night, 2 * 60));
+        assert!(!schedule_allows(&night, 12 * 60));
+
+        let overlapping = [window("09:00-13:00"), window("12:00-17:00")];
+        assert!(schedule_allows(&overlapping, 12 * 60 + 30));
+        assert!(schedule_allows(&overlapping, 16 * 60));
+        assert!(!schedule_allows(&overlapping, 17 * 60));
+        assert!(schedule_allows(&[], 3 * 60));
+
+        let config: Config = toml::from_str("schedule = [\"22:00-02:00\"]").unwrap();
+        assert_eq!(config.count, [CountedEvent::Keys]);
+        assert_eq!(config.schedule, night);
+        assert!(toml::from_str::<Config>("schedule = [\"nine to five\"]").is_err());
+    }
+
+    #[test]
+    fn count_lists_the_events_in_the_total() {
+        let config: Config = toml::from_str("count = [\"keys\", \"scroll\"]").unwrap();
+        assert_eq!(config.count, [CountedEvent::Keys, CountedEvent::Scroll]);
+        let config: Config = toml::from_str("count = []").unwrap();
+        assert!(config.count.is_empty());
+        assert!(toml::from_str::<Config>("count = [\"trackpad\"]").is_err());
+    }
+
+    #[test]
+    fn work_hours_sum_the_hours_in_range() {
+        assert_eq!(
+            parse_hour_range("9-17"),
+            Ok(HourRange { start: 9, end: 17 })
+        );
+        assert_eq!(
+            parse_hour_range("0-24"),
+            Ok(HourRange { start: 0, end: 24 })
+        );
+        for bad in ["17-9", "9-9", "9-25", "9", "nine-five", "-3-4"] {
+            assert!(parse_hour_range(bad).is_err(), "{}", bad);
+        }
+
+        let mut hourly = [0; 24];
+        hourly[8] = 5;
+        hourly[9] = 10;
+        hourly[16] = 20;
+        hourly[17] = 65;
+        let range = parse_hour_range("9-17").unwrap();
+        assert_eq!(work_hour_totals(&hourly, range), (30, 100));
+        assert_eq!(range.label("–"), "09:00–17:00");
+    }
+
+    #[test]
+    fn parse_duration_understands_each_unit() {
+        assert_eq!(parse_duration("90d"), Ok(90 * 86400));
+        assert_eq!(parse_duration("12w"), Ok(84 * 86400));
+        assert_eq!(parse_duration("6mo"), Ok(180 * 86400));
+        assert_eq!(parse_duration("1y"), Ok(365 * 86400));
+        assert_eq!(parse_duration("0d"), Ok(0));
+    }
+
+    #[test]
+    fn parse_duration_rejects_bad_input() {
+        let error = parse_duration("3h").unwrap_err();
+        assert!(error.contains("unit 'h'"), "{}", error);
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("-7d").is_err());
+        assert!(parse_duration("99999999999999999y").is_err());
+    }
+
+    #[test]
+    fn since_day_counts_back_from_now() {
+        let now = current_timestamp();
+        assert_eq!(since_day(0, now), local_day(now));
+        assert_eq!(since_day(7 * 86400, now), local_day(now - 7 * 86400));
+        assert_eq!(since_day(u64::MAX, now), local_day(0));
+    }
+
+    #[test]
+    fn range_stats_are_inclusive_and_sorted() {
+        let data = data_with_days(&[20_005, 20_001, 20_003, 20_010]);
+
+        let days: Vec<u64> = data
+            .get_range_stats(20_001, 20_005)
+            .iter()
+            .map(|r| r.day())
+            .collect();
+        assert_eq!(days, [20_001, 20_003, 20_005]);
+        assert!(data.get_range_stats(20_006, 20_009).is_empty());
+    }
+
+    #[test]
+    fn top_days_sort_by_count_and_clamp() {
+        let mut data = data_with_days(&[20_001, 20_002, 20_003, 20_004]);
+        for (record, count) in data.daily_records.iter_mut().zip([5, 9, 5, 1]) {
+            record.count = count;
+        }
+
+        let days: Vec<u64> = data.get_top_days(3).iter().map(|r| r.day()).collect();
+        assert_eq!(days, [20_002, 20_001, 20_003]);
+        assert_eq!(data.get_top_days(10).len(), 4);
+        assert!(KeystrokeData::new().get_top_days(5).is_empty());
+    }
+
+    #[test]
+    fn average_daily_over_recorded_days() {
+        assert_eq!(KeystrokeData::new().average_daily(), 0.0);
+        let mut data = data_with_days(&[20_000, 20_005]);
+        data.daily_records[1].count = 5;
+        assert_eq!(data.average_daily(), 3.0);
+    }
+
+    #[test]
+    fn daily_spread_skips_untyped_days() {
+        let empty = KeystrokeData::new();
+        assert_eq!(
+            (empty.daily_min(), empty.daily_median(), empty.daily_max()),
+            (None, None, None)
+        );
+
+        let mut data = data_with_days(&[20_000, 20_001, 20_002, 20_003, 20_004]);
+        for (record, count) in data.daily_records.iter_mut().zip([40, 0, 10, 30, 20]) {
+            record.count = count;
+        }
+        assert_eq!(data.daily_min(), Some(10));
+        assert_eq!(data.daily_max(), Some(40));
+        assert_eq!(data.daily_median(), Some(25.0));
+
+        data.daily_records[1].count = 50;
+        assert_eq!(data.daily_median(), Some(30.0));
+    }
+
+    #[test]
+    fn months_stats_keep_years_apart() {
+        // 2024-01-15, 2024-01-20, 2025-01-15
+        let mut data = data_with_days(&[19_737, 19_742, 20_103]);
+        for (record, count) in data.daily_records.iter_mut().zip([3, 4, 5]) {
+            record.count = count;
+        }
+        assert_eq!(
+            data.get_months_stats(),
+            [("2024-01".to_string(), 7), ("2025-01".to_string(), 5)]
+        );
+    }
+
+    #[test]
+    fn last_twelve_months_wraps_the_year() {
+        // 2025-03-10
+        let months = last_twelve_months(20_157);
+        assert_eq!(months.len(), 12);
+        assert_eq!(months[0], (2024, 4));
+        assert_eq!(months[8], (2024, 12));
+        assert_eq!(months[9], (2025, 1));
+        assert_eq!(months[11], (2025, 3));
+    }
+
+    fn data_with_days(days: &[u64]) -> KeystrokeData {
+        let mut data = KeystrokeData::new();
+        for &day in days {
+            data.daily_records.push(DailyRecord {
+                date: day.to_string(),
+                count: 1,
+                timestamp: day * 86400,
+                clicks: 0,
+                active_seconds: 0,
+                typing_seconds: 0,
+                sessions: 1,
+                longest_gap: 0,
+                first_seen: 0,
+                last_seen: 0,
+            });
+        }
+        data
+    }
+
+    #[test]
+    fn streaks_for_contiguous_days() {
+        let today = current_day();
+        let data = data_with_days(&[today - 3, today - 2, today - 1, today]);
+        assert_eq!(data.current_streak(), 4);
+        assert_eq!(data.longest_streak(), 4);
+    }
+
+    #[test]
+    fn streaks_break_on_a_gap() {
+        let today = current_day();
+        let data = data_with_days(&[today - 6, today - 5, today - 4, today - 2, today - 1]);
+        assert_eq!(data.current_streak(), 2);
+        assert_eq!(data.longest_streak(), 3);
+
+        let lapsed = data_with_days(&[today - 4, today - 3]);
+        assert_eq!(lapsed.current_streak(), 0);
+        assert_eq!(lapsed.longest_streak(), 2);
+    }
+
+    #[test]
+    fn streaks_for_single_day_history() {
+        let data = data_with_days(&[current_day()]);
+        assert_eq!(data.current_streak(), 1);
+        assert_eq!(data.longest_streak(), 1);
+        assert_eq!(KeystrokeData::new().longest_streak(), 0);
+    }
+
+    fn jsonl_store(dir: &std::path::Path) -> JsonlStorage {
+        fs::create_dir_all(dir).unwrap();
+        JsonlStorage {
+            snapshot: JsonStorage {
+                path: dir.join("data.json"),
+            },
+            log: dir.join("events.jsonl"),
+        }
+    }
+
+    /// Loads `store` as a fresh process would, without the cached base.
+    fn reload(store: &JsonlStorage) -> KeystrokeData {
+        *JSONL_BASE.lock().unwrap() = None;
+        store.load().unwrap()
+    }
+
+    fn log_lines(store: &JsonlStorage) -> usize {
+        fs::read_to_string(&store.log).map_or(0, |log| log.lines().count())
+    }
+
+    #[test]
+    fn delta_since_merges_back_into_the_same_data() {
+        let mut base = data_with_days(&[20_000]);
+        base.total_count = 1;
+        let mut data = base.clone();
+        data.increment();
+        data.increment_click();
+        data.increment_category(KeyCategory::Alphanumeric);
+        data.increment_app("Code");
+        data.record_kpm(80, 5);
+
+        let delta = data.delta_since(&base).unwrap();
+        assert_eq!(delta.get_app_stats(), [("Code".to_string(), 1)]);
+        assert_eq!(delta.total_count, 1);
+        assert_eq!(delta.daily_records.len(), 1);
+        base.merge(&delta);
+        assert_eq!(
+            serde_json::to_value(&base).unwrap(),
+            serde_json::to_value(&data).unwrap()
+        );
+
+        assert!(data.delta_since(&data).unwrap().is_empty());
+        data.daily_records.clear();
+        assert!(data.delta_since(&base).is_none());
+    }
+
+    #[test]
+    fn jsonl_appends_each_save_and_replays_on_load() {
+        let dir = temp_dir("jsonl-append");
+        let store = jsonl_store(&dir);
+        let mut data = KeystrokeData::new();
+        data.increment();
+        store.save(&data).unwrap();
+        // The first save has no snapshot to append to yet.
+        assert!(store.snapshot.path.exists());
+        assert_eq!(log_lines(&store), 0);
+
+        data.increment();
+        data.increment();
+        store.save(&data).unwrap();
+        data.increment_click();
+        store.save(&data).unwrap();
+        store.save(&data).unwrap();
+        assert_eq!(log_lines(&store), 2);
+        assert_eq!(store.snapshot.load().unwrap().total_count, 1);
+
+        let loaded = reload(&store);
+        assert_eq!(loaded.total_count, 3);
+        assert_eq!(loaded.total_clicks, 1);
+        assert_eq!(loaded.get_today_stats(), 3);
+
+        // A line torn by a crash mid-append is skipped.
+        let mut log = fs::read_to_string(&store.log).unwrap();
+        log.push_str("{\"ts\": 17");
+        fs::write(&store.log, log).unwrap();
+        assert_eq!(reload(&store).total_count, 3);
+    }
+
+    #[test]
+    fn jsonl_rewrites_the_snapshot_when_counts_drop() {
+        let dir = temp_dir("jsonl-reset");
+        let store = jsonl_store(&dir);
+        let mut data = data_with_days(&[20_000, 20_001]);
+        store.save(&data).unwrap();
+        data.increment();
+        store.save(&data).unwrap();
+        assert_eq!(log_lines(&store), 1);
+
+        data.daily_records.remove(0);
+        store.save(&data).unwrap();
+        assert_eq!(log_lines(&store), 0);
+        assert_eq!(reload(&store).daily_records.len(), 2);
+    }
+
+    #[test]
+    fn compact_folds_the_log_into_the_snapshot() {
+        let dir = temp_dir("jsonl-compact");
+        let store = jsonl_store(&dir);
+        let mut data = KeystrokeData::new();
+        store.save(&data).unwrap();
+        for _ in 0..3 {
+            data.increment();
+            store.save(&data).unwrap();
+        }
+
+        assert_eq!(store.compact().unwrap(), 3);
+        assert_eq!(log_lines(&store), 0);
+        assert_eq!(store.snapshot.load().unwrap().total_count, 3);
+        assert_eq!(reload(&store).total_count, 3);
+    }
+
+    #[test]
+    fn merge_sums_shared_days_and_appends_new_ones() {
+        let mut data = data_with_days(&[20_000, 20_001]);
+        data.total_count = 2;
+        let mut other = data_with_days(&[20_001, 19_999]);
+        other.total_count = 2;
+        other.hourly_counts[9] = 2;
+
+        let summary = data.merge(&other);
+
+        assert_eq!(summary.days_merged, 1);
+        assert_eq!(summary.days_added, 1);
+        assert_eq!(summary.keystrokes_added, 2);
+        assert_eq!(data.total_count, 4);
+        assert_eq!(data.hourly_counts[9], 2);
+        let counts: Vec<(u64, u64)> = data
+            .daily_records
+            .iter()
+            .map(|r| (r.day(), r.count))
+            .collect();
+        assert_eq!(counts, [(19_999, 1), (20_000, 1), (20_001, 2)]);
+    }
+
+    #[test]
+    fn listen_errors_are_actionable() {
+        let message = describe_listen_error(&ListenError::EventTapError, false);
+        assert!(message.contains("System Settings > Privacy & Security"));
+        let x11 = describe_listen_error(&ListenError::MissingDisplayError, false);
+        assert!(x11.contains("DISPLAY") && !x11.contains("Wayland"));
+        let wayland = describe_listen_error(&ListenError::MissingDisplayError, true);
+        assert!(wayland.contains("Wayland") && wayland.contains("X11 session"));
+        let record = describe_listen_error(&ListenError::XRecordExtensionError, true);
+        assert!(record.contains("XWayland"));
+        assert!(describe_listen_error(&ListenError::KeyboardError, false).contains("DISPLAY"));
+        assert!(
+            describe_listen_error(&ListenError::LoopSourceError, false).contains("LoopSourceError")
+        );
+    }
+
+    #[test]
+    fn clock_before_epoch_reuses_the_last_good_reading() {
+        assert_eq!(timestamp_at(UNIX_EPOCH + Duration::from_secs(5000)), 5000);
+        // Other tests may store a newer reading in between, never an older one.
+        assert!(timestamp_at(UNIX_EPOCH - Duration::from_secs(10)) >= 5000);
+        assert!(take_clock_warning());
+    }
+
+    #[test]
+    fn share_tokens_verify_only_untouched_with_the_same_key() {
+        let summary = ShareSummary {
+            total: 123_456,
+            weekly: 7_890,
+            streak: 12,
+            date: "2025-10-07".to_string(),
+        };
+        let key = [7; SHARE_KEY_LEN];
+        let token = sign_share(&summary, &key);
+        assert!(token.starts_with("ks1."));
+        assert!(token.len() < 120, "{}", token);
+        assert_eq!(verify_share(&token, &key), Ok(summary.clone()));
+
+        assert!(verify_share(&token, &[8; SHARE_KEY_LEN]).is_err());
+        let mut forged = summary;
+        forged.total = 999_999;
+        let (_, tag) = token.rsplit_once('.').unwrap();
+        let payload = sign_share(&forged, &[8; SHARE_KEY_LEN]);
+        let (payload, _) = payload.rsplit_once('.').unwrap();
+        assert!(verify_share(&format!("{}.{}", payload, tag), &key).is_err());
+        assert!(verify_share("hello", &key).is_err());
+    }
+
+    #[test]
+    fn format_log_time_is_sortable() {
+        assert_eq!(format_log_time(1728034200, 0), "2024-10-04 09:30:00");
+        assert_eq!(format_log_time(1728034200, 3600), "2024-10-04 10:30:00");
+    }
+
+    #[test]
+    fn encryption_round_trips_and_rejects_wrong_passphrase() {
+        let plaintext = br#"{"total_count": 7, "daily_records": []}"#;
+        let encrypted = encrypt_bytes(plaintext, "correct horse");
+
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.windows(11).any(|w| w == b"total_count"));
+        assert_eq!(
+            decrypt_bytes(&encrypted, "correct horse").unwrap(),
+            plaintext
+        );
+        assert!(decrypt_bytes(&encrypted, "battery staple").is_err());
+        assert!(decrypt_bytes(&encrypted[..20], "correct horse").is_err());
+        // Each save uses a fresh nonce.
+        assert_ne!(encrypt_bytes(plaintext, "correct horse"), encrypted);
+    }
+
+    #[test]
+    fn prune_drops_old_records_but_keeps_total() {
+        let today = current_day();
+        let mut data = data_with_days(&[today - 400, today - 31, today - 29, today]);
+        data.total_count = 1234;
+
+        assert_eq!(data.prune(30), 2);
+
+        let days: Vec<u64> = data.daily_records.iter().map(|r| r.day()).collect();
+        assert_eq!(days, [today - 29, today]);
+        assert_eq!(data.total_count, 1234);
+    }
+
+    #[test]
+    fn sqlite_storage_round_trips_data() {
+        let dir = temp_dir("sqlite");
+        fs::create_dir_all(&dir).unwrap();
+        let store = SqliteStorage {
+            path: dir.join("data.db"),
+        };
+        let mut data = data_with_days(&[20_000, 20_001]);
+        data.total_count = 2;
+        data.hourly_counts[8] = 2;
+        store.save(&data).unwrap();
+
+        data.daily_records[1].count = 5;
+        data.total_count = 6;
+        store.increment_day(&data, 20_001).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.total_count, 6);
+        assert_eq!(loaded.hourly_counts[8], 2);
+        let counts: Vec<(u64, u64)> = loaded
+            .daily_records
+            .iter()
+            .map(|r| (r.day(), r.count))
+            .collect();
+        assert_eq!(counts, [(20_000, 1), (20_001, 5)]);
+        let conn = store.open().unwrap();
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn sqlite_storage_starts_from_data_json_before_init() {
+        let dir = temp_dir("sqlite-no-init");
+        fs::create_dir_all(&dir).unwrap();
+        let mut data = data_with_days(&[20_000]);
+        data.total_count = 9;
+        JsonStorage {
+            path: dir.join("data.json"),
+        }
+        .save(&data)
+        .unwrap();
+        let store = SqliteStorage {
+            path: dir.join("data.db"),
+        };
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.total_count, 9);
+        store.save(&loaded).unwrap();
+        let reloaded = store.load().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(reloaded.total_count, 9);
+        assert_eq!(reloaded.daily_records.len(), 1);
+    }
+
+    #[test]
+    fn bincode_storage_round_trips_data() {
+        let dir = temp_dir("bincode");
+        fs::create_dir_all(&dir).unwrap();
+        let store = BincodeStorage {
+            path: dir.join("data.bin"),
+        };
+        let mut data = data_with_days(&[20_000, 20_001]);
+        data.total_count = 2;
+        data.hourly_counts[8] = 2;
+        data.weekday_hour_counts[3][8] = 2;
+        store.save(&data).unwrap();
+        let loaded = store.load().unwrap();
+        data.increment_app("Code");
+        store.save(&data).unwrap();
+        let with_apps = store.load().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(loaded.schema_version, SCHEMA_VERSION);
+        assert_eq!(loaded.total_count, 2);
+        assert_eq!(loaded.weekday_hour_counts[3][8], 2);
+        assert_eq!(loaded.daily_records.len(), 2);
+        assert!(loaded.get_app_stats().is_empty());
+        assert_eq!(with_apps.get_app_stats(), [("Code".to_string(), 1)]);
+        assert!(encode_binary(&data).len() < serde_json::to_vec(&data).unwrap().len());
+    }
+
+    #[test]
+    fn binary_data_checks_its_schema_version() {
+        let bytes = encode_binary(&data_with_days(&[20_000]));
+        assert_eq!(decode_binary(&bytes).unwrap().daily_records.len(), 1);
+
+        let mut newer = bytes.clone();
+        newer[BINARY_MAGIC.len()..BINARY_MAGIC.len() + 4]
+            .copy_from_slice(&(SCHEMA_VERSION + 1).to_le_bytes());
+        assert!(decode_binary(&newer).unwrap_err().contains("newer"));
+        let mut older = bytes.clone();
+        older[BINARY_MAGIC.len()..BINARY_MAGIC.len() + 4].copy_from_slice(&1u32.to_le_bytes());
+        assert!(decode_binary(&older).is_err());
+
+        assert!(decode_binary(b"{\"total_count\": 1}").is_err());
+        assert!(decode_binary(&bytes[..bytes.len() - 1]).is_err());
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(decode_binary(&trailing).is_err());
+    }
+
+    #[test]
+    fn previous_periods_do_not_overlap_current_ones() {
+        let today = current_day();
+        let mut data = data_with_days(&[
+            today,
+            today - 6,
+            today - 7,
+            today - 13,
+            today - 14,
+            today - 45,
+        ]);
+        data.daily_records[5].count = 10;
+
+        assert_eq!(data.get_weekly_stats(), 2);
+        assert_eq!(data.get_previous_week_stats(), 2);
+        assert_eq!(data.get_monthly_stats(), 5);
+        assert_eq!(data.get_previous_month_stats(), 10);
+    }
+
+    #[test]
+    fn parse_elapsed_time_formats() {
+        assert_eq!(parse_elapsed_time("00:05"), Some(5));
+        assert_eq!(parse_elapsed_time("01:02:03"), Some(3723));
+        assert_eq!(parse_elapsed_time("2-00:00:01"), Some(172_801));
+        assert_eq!(parse_elapsed_time(""), None);
+        assert_eq!(parse_elapsed_time("12"), None);
+    }
+
+    #[test]
+    fn civil_from_days_handles_century_rules() {
+        // 2100 is not a leap year, so 28 Feb is followed by 1 Mar.
+        assert_eq!(civil_from_days(47_540), (2100, 2, 28));
+        assert_eq!(civil_from_days(47_541), (2100, 3, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn sparkline_scales_to_the_largest_value() {
+        const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+        assert_eq!(sparkline(&[0, 1, 4, 8], &LEVELS), "▁▂▅█");
+        assert_eq!(sparkline(&[0, 0], &LEVELS), "▁▁");
+        assert_eq!(sparkline(&[], &LEVELS), "");
+    }
+
+    #[test]
+    fn daily_counts_end_with_today() {
+        let mut data = data_with_days(&[100, 118, 120]);
+        data.daily_records[1].count = 4;
+        data.daily_records[2].count = 50;
+        assert_eq!(daily_counts(&data, 120, 3), [4, 0, 50]);
+        assert_eq!(daily_counts(&data, 120, 1), [50]);
+        assert!(daily_counts(&data, 120, 0).is_empty());
+        assert_eq!(daily_counts(&data, 1, 5), [0, 0]);
+    }
+
+    #[test]
+    fn moving_average_shrinks_at_the_edges() {
+        let values = [0, 10, 20, 30, 40];
+        assert_eq!(moving_average(&values, 3), [5, 10, 20, 30, 35]);
+        assert_eq!(moving_average(&values, 1), values);
+        assert_eq!(moving_average(&values, 4), [10, 15, 25, 30, 35]);
+        assert_eq!(moving_average(&values, 100), [20; 5]);
+        assert!(moving_average(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn iso_weeks_at_year_boundaries() {
+        let day = |y, m, d| days_from_civil(y, m, d) as u64;
+        assert_eq!(iso_week(day(2024, 3, 3)), (2024, 9));
+        assert_eq!(iso_week(day(2024, 12, 30)), (2025, 1));
+        assert_eq!(iso_week(day(2021, 1, 3)), (2020, 53));
+        assert_eq!(iso_week(day(2021, 1, 4)), (2021, 1));
+        assert_eq!(iso_week(day(2026, 12, 31)), (2026, 53));
+
+        // Tuesday 7 Oct 2025 -> Monday 29 Sep to Sunday 5 Oct, week 40.
+        let (first, last) = last_complete_week(day(2025, 10, 7));
+        assert_eq!((first, last), (day(2025, 9, 29), day(2025, 10, 5)));
+        assert_eq!(iso_week(first), (2025, 40));
+        assert_eq!(last_complete_week(day(2025, 10, 6)).0, day(2025, 9, 29));
+    }
+
+    #[test]
+    fn shares_round_to_a_full_hundred_percent() {
+        assert_eq!(percent_tenths(&[1, 1, 1]), [334, 333, 333]);
+        assert_eq!(percent_tenths(&[4210, 30_000]), [123, 877]);
+        assert_eq!(percent_tenths(&[0, 0]), [0, 0]);
+        assert_eq!(percent_tenths(&[]), Vec::<u64>::new());
+        let uneven = percent_tenths(&[7, 13, 29, 1, 1, 2]);
+        assert_eq!(uneven.iter().sum::<u64>(), 1000);
+    }
+}
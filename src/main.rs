@@ -1,17 +1,34 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use colored::*;
-use rdev::{Event, EventType, listen};
+use keystr::*;
+use rdev::{Event, EventType, Key, listen};
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
 use std::fs;
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Parser)]
 #[command(name = "keystr")]
 #[command(about = "A CLI tool to count keyboard presses (no key data stored)", long_about = None)]
 struct Cli {
+    /// Disable colored output (also honors the NO_COLOR environment variable)
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Draw with plain ASCII instead of Unicode (automatic when TERM=dumb)
+    #[arg(long, global = true)]
+    ascii: bool,
+    /// Use a separate named profile with its own data and daemon
+    #[arg(long, global = true, value_parser = parse_profile_name)]
+    profile: Option<String>,
+    /// Read and write this JSON data file instead, for this command only
+    #[arg(long, global = true, value_name = "PATH")]
+    data_file: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -24,748 +41,5903 @@ enum Commands {
     Start,
     /// Stop the background monitoring process
     Stop,
+    /// Stop any running monitor and start a fresh one
+    Restart,
     /// Check if monitoring is running
-    Status,
+    Status {
+        /// Print machine-readable JSON; exits 0 when running, 1 otherwise
+        #[arg(long)]
+        json: bool,
+        /// Append a sparkline of the last 30 days
+        #[arg(long)]
+        spark: bool,
+    },
+    /// Watch the keystroke count update live
+    Watch {
+        /// Refresh interval in seconds
+        #[arg(short, long, default_value_t = 1)]
+        interval: u64,
+        /// Redraw when the data file changes instead of on a timer
+        #[arg(long)]
+        watch_file: bool,
+    },
+    /// Show the current keystrokes-per-minute rate
+    Kpm,
     /// Show statistics
-    Stats {
-        /// Show daily stats
-        #[arg(short, long)]
-        daily: bool,
-        /// Show weekly stats
-        #[arg(short, long)]
-        weekly: bool,
-        /// Show monthly stats
-        #[arg(short, long)]
-        monthly: bool,
+    Stats(StatsArgs),
+    /// Draw the daily graph on its own, or print the series it plots
+    Graph(GraphArgs),
+    /// Print today's keystroke count as a bare number, e.g. for prompts
+    Today,
+    /// Show the key numbers and a 7-day sparkline on one screen
+    Summary,
+    /// Print keystroke counters in Prometheus text format
+    Metrics,
+    /// Serve stats as JSON over HTTP
+    Serve {
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+        /// Address to bind; keep the default to stay private to this machine
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: std::net::IpAddr,
     },
-    /// Export statistics to a text file
+    /// Export statistics to a file
     Export {
         /// Output file path
         #[arg(short, long, default_value = "keystr_stats.txt")]
         output: String,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Text)]
+        format: ExportFormat,
+        /// First day to include (YYYY-MM-DD)
+        #[arg(long, value_parser = parse_date)]
+        from: Option<u64>,
+        /// Last day to include (YYYY-MM-DD)
+        #[arg(long, value_parser = parse_date)]
+        to: Option<u64>,
+        /// Only include days since this long ago (e.g. 90d, 12w, 6mo, 1y)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with = "from")]
+        since: Option<u64>,
+        /// Only include days typed on since the last incremental export
+        #[arg(long)]
+        incremental: bool,
+        /// Forget the last incremental export so this one includes everything
+        #[arg(long, requires = "incremental")]
+        reset_marker: bool,
+    },
+    /// Write a report for the last complete week into a directory
+    Report {
+        /// Report on the most recent complete Monday-Sunday week
+        #[arg(long, required = true)]
+        weekly: bool,
+        /// Directory to write `report-YYYY-Www.txt` into
+        #[arg(long)]
+        dir: PathBuf,
+    },
+    /// Print a signed token with your headline numbers to share
+    Share,
+    /// Check a token from `keystr share` and show what it claims
+    Verify {
+        /// The token, e.g. ks1.eyJ0b3RhbCI6...
+        token: String,
+    },
+    /// Manage the daily keystroke goal
+    Goal {
+        #[command(subcommand)]
+        action: GoalCommand,
+    },
+    /// Manage named profiles
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommand,
+    },
+    /// Print where keystr keeps its files
+    Where,
+    /// Check the setup and explain why counting might not work
+    Doctor,
+    /// Summarize the stored data file
+    Dump {
+        /// Print the stored JSON itself instead of a summary
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Inspect keystr's configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Merge another keystr data file into this one
+    Import {
+        /// Path to the data file to merge in
+        path: PathBuf,
+        /// Report the merge result without saving
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Save a timestamped copy of the statistics
+    Backup {
+        /// Backup file path (defaults to `data-YYYYMMDD-HHMMSS.json` in the config dir)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Replace the statistics with a backup
+    Restore {
+        /// Path to the backup to restore
+        path: PathBuf,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Remove daily records older than the retention window
+    Prune {
+        /// Days to keep (defaults to `retention_days` from the config)
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        days: Option<u64>,
+    },
+    /// Fold `events.jsonl` back into `data.json` and empty the log
+    Compact,
+    /// Move your history to another storage format and switch to it
+    Convert {
+        /// Format to convert to
+        #[arg(long, value_enum)]
+        to: ConvertFormat,
     },
     /// Reset all statistics
-    Reset,
-    /// Enable auto-start on boot
+    Reset {
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+        /// Only remove this day (YYYY-MM-DD)
+        #[arg(long, value_parser = parse_date, conflicts_with_all = ["from", "to"])]
+        day: Option<u64>,
+        /// Only remove days from this date (YYYY-MM-DD)
+        #[arg(long, value_parser = parse_date)]
+        from: Option<u64>,
+        /// Only remove days up to this date (YYYY-MM-DD)
+        #[arg(long, value_parser = parse_date)]
+        to: Option<u64>,
+    },
+    /// Stop the monitor and delete all keystr data, config and logs
+    Uninstall {
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Start counting automatically when you log in
+    #[command(visible_alias = "enable-autostart")]
     Enable,
-    /// Disable auto-start on boot
+    /// Stop starting automatically at login
+    #[command(visible_alias = "disable-autostart")]
     Disable,
+    /// Run the monitor in this terminal instead of detaching
+    Run {
+        /// Print the running count once per second
+        #[arg(long)]
+        foreground: bool,
+    },
+    /// Print a shell completion script
+    ///
+    /// Redirect the output to wherever your shell loads completions from:
+    ///
+    ///     keystr completions bash > ~/.local/share/bash-completion/completions/keystr
+    ///     keystr completions zsh > ~/.zfunc/_keystr
+    ///     keystr completions fish > ~/.config/fish/completions/keystr.fish
+    ///     keystr completions powershell >> $PROFILE
+    #[command(hide = true, verbatim_doc_comment)]
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
     /// Internal command - do not use directly
     #[command(hide = true)]
     Daemon,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct KeystrokeData {
-    total_count: u64,
-    daily_records: Vec<DailyRecord>,
+#[derive(Subcommand)]
+enum GoalCommand {
+    /// Set the daily keystroke goal
+    Set {
+        /// Keystrokes to aim for each day
+        #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+        target: u64,
+    },
+    /// Remove the daily goal
+    Clear,
+    /// Show the goal and today's progress
+    Show,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct DailyRecord {
-    date: String,
-    count: u64,
-    timestamp: u64,
+#[derive(Subcommand)]
+enum ProfileCommand {
+    /// List existing profiles
+    List,
 }
 
-impl KeystrokeData {
-    fn new() -> Self {
-        KeystrokeData {
-            total_count: 0,
-            daily_records: Vec::new(),
-        }
-    }
-
-    fn increment(&mut self) {
-        self.total_count += 1;
-        let today = format_date_storage();
-        let timestamp = current_timestamp();
-
-        if let Some(record) = self.daily_records.iter_mut().find(|r| r.date == today) {
-            record.count += 1;
-        } else {
-            self.daily_records.push(DailyRecord {
-                date: today,
-                count: 1,
-                timestamp,
-            });
-        }
-    }
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the resolved config file location
+    Path,
+}
 
-    fn get_daily_stats(&self, days: usize) -> Vec<DailyRecord> {
-        let mut records = self.daily_records.clone();
-        records.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
-        records.into_iter().take(days).collect()
-    }
+#[derive(Args, Default)]
+struct StatsArgs {
+    /// Show daily stats
+    #[arg(short, long)]
+    daily: bool,
+    /// Show weekly stats
+    #[arg(short, long)]
+    weekly: bool,
+    /// Show monthly stats
+    #[arg(short, long)]
+    monthly: bool,
+    /// Show the annual total and a 12-month graph
+    #[arg(short, long)]
+    yearly: bool,
+    /// Show keystrokes by hour of day
+    #[arg(long)]
+    hourly: bool,
+    /// Show how much typing falls within these hours, e.g. 9-17
+    #[arg(long, value_name = "START-END", value_parser = parse_hour_range)]
+    work_hours: Option<HourRange>,
+    /// Show keystrokes by day of week
+    #[arg(long)]
+    weekday: bool,
+    /// First day to include (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date)]
+    from: Option<u64>,
+    /// Last day to include (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date)]
+    to: Option<u64>,
+    /// Only include days since this long ago (e.g. 90d, 12w, 6mo, 1y)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with = "from")]
+    since: Option<u64>,
+    /// Graph a centered moving average over this many days instead of raw counts
+    #[arg(long, value_name = "WINDOW", value_parser = clap::value_parser!(u64).range(1..))]
+    smooth: Option<u64>,
+    /// Graph a running total of the shown days instead of each day's count
+    #[arg(long)]
+    cumulative: bool,
+    /// Rows in the daily graph (3-40, default 10)
+    #[arg(long, value_name = "ROWS")]
+    height: Option<usize>,
+    /// Days in the default daily view (default 7)
+    #[arg(long, value_name = "N")]
+    days: Option<usize>,
+    /// Compare this week and month against the previous ones
+    #[arg(long)]
+    compare: bool,
+    /// List the N days with the most keystrokes
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+    /// Show how keystrokes split across key categories
+    #[arg(long)]
+    categories: bool,
+    /// Show how many days fall into each daily keystroke range
+    #[arg(long)]
+    distribution: bool,
+    /// Show a weekday × hour activity heatmap
+    #[arg(long)]
+    heatmap: bool,
+    /// List the lifetime totals you've reached
+    #[arg(long)]
+    achievements: bool,
+    /// Output format for --heatmap
+    #[arg(long, value_enum, requires = "heatmap", default_value_t = HeatmapFormat::Grid)]
+    format: HeatmapFormat,
+    /// Print the computed statistics as JSON instead of graphs
+    #[arg(long)]
+    json: bool,
+    /// Print a plain DATE/COUNT table for piping, with no graph or color
+    #[arg(long, conflicts_with = "json")]
+    plain: bool,
+}
 
-    fn get_weekly_stats(&self) -> u64 {
-        let seven_days_ago = current_timestamp() - (7 * 24 * 60 * 60);
-        self.daily_records
-            .iter()
-            .filter(|r| r.timestamp >= seven_days_ago)
-            .map(|r| r.count)
-            .sum()
-    }
+#[derive(Args)]
+struct GraphArgs {
+    /// Days to graph, ending today (default 7)
+    #[arg(long, value_name = "N")]
+    days: Option<usize>,
+    /// First day to include (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date)]
+    from: Option<u64>,
+    /// Last day to include (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date)]
+    to: Option<u64>,
+    /// Only include days since this long ago (e.g. 90d, 12w, 6mo, 1y)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with = "from")]
+    since: Option<u64>,
+    /// Graph a centered moving average over this many days instead of raw counts
+    #[arg(long, value_name = "WINDOW", value_parser = clap::value_parser!(u64).range(1..))]
+    smooth: Option<u64>,
+    /// Graph a running total of the shown days instead of each day's count
+    #[arg(long)]
+    cumulative: bool,
+    /// Rows in the graph (3-40, default 10)
+    #[arg(long, value_name = "ROWS")]
+    height: Option<usize>,
+    /// Print the plotted series as JSON, oldest first, instead of drawing it
+    #[arg(long)]
+    json: bool,
+}
 
-    fn get_monthly_stats(&self) -> u64 {
-        let thirty_days_ago = current_timestamp() - (30 * 24 * 60 * 60);
-        self.daily_records
-            .iter()
-            .filter(|r| r.timestamp >= thirty_days_ago)
-            .map(|r| r.count)
-            .sum()
+impl GraphArgs {
+    /// The `stats` options that pick and shape the same daily graph.
+    fn stats_args(&self) -> StatsArgs {
+        StatsArgs {
+            days: self.days,
+            from: self.from,
+            to: self.to,
+            smooth: self.smooth,
+            cumulative: self.cumulative,
+            height: self.height,
+            ..StatsArgs::default()
+        }
     }
 }
 
-fn get_config_dir() -> PathBuf {
-    let mut path = dirs::config_dir().expect("Could not find config directory");
-    path.push("keystroke");
-    path
+/// How a command renders its results on stdout.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// Colored, human-friendly output
+    Pretty,
+    /// A single JSON document with no ANSI codes
+    Json,
 }
 
-fn get_data_file() -> PathBuf {
-    let mut path = get_config_dir();
-    path.push("data.json");
-    path
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+enum HeatmapFormat {
+    /// Shaded grid in the terminal
+    #[default]
+    Grid,
+    /// Comma-separated counts, one row per weekday
+    Csv,
 }
 
-fn get_pid_file() -> PathBuf {
-    let mut path = get_config_dir();
-    path.push("daemon.pid");
-    path
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ConvertFormat {
+    /// A single `data.json`
+    Json,
+    /// A compact binary `data.bin`
+    Bincode,
 }
 
-fn load_data() -> KeystrokeData {
-    let file_path = get_data_file();
-    if file_path.exists() {
-        let content = fs::read_to_string(&file_path).unwrap_or_else(|_| "{}".to_string());
-        serde_json::from_str(&content).unwrap_or_else(|_| KeystrokeData::new())
-    } else {
-        KeystrokeData::new()
+impl ConvertFormat {
+    fn storage_kind(self) -> StorageKind {
+        match self {
+            ConvertFormat::Json => StorageKind::Json,
+            ConvertFormat::Bincode => StorageKind::Bincode,
+        }
     }
 }
 
-fn save_data(data: &KeystrokeData) {
-    let file_path = get_data_file();
-    let json = serde_json::to_string_pretty(data).expect("Failed to serialize data");
-    fs::write(file_path, json).expect("Failed to write data file");
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ExportFormat {
+    /// Decorated plain-text report
+    Text,
+    /// Comma-separated daily records
+    Csv,
+    /// Raw keystroke data as JSON
+    Json,
+    /// GitHub-flavored Markdown report
+    Markdown,
+    /// Self-contained HTML page with a bar chart of daily counts
+    Html,
 }
 
-fn format_date_display(time: &SystemTime) -> String {
-    let duration = time
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards");
-    let secs = duration.as_secs();
-    let days_since_epoch = (secs / 86400) as i64;
-
-    let mut year = 1970;
-    let mut days_remaining = days_since_epoch;
+/// How one role in the output is drawn.
+struct Style {
+    color: Option<Color>,
+    bold: bool,
+    dimmed: bool,
+}
 
-    let years_passed = days_remaining / 365;
-    year += years_passed as i32;
-    days_remaining -= years_passed * 365;
+impl Style {
+    const PLAIN: Style = Style::new(None, false, false);
+    const BOLD: Style = Style::new(None, true, false);
+    const DIMMED: Style = Style::new(None, false, true);
 
-    let leap_days = years_passed / 4;
-    days_remaining -= leap_days;
+    const fn new(color: Option<Color>, bold: bool, dimmed: bool) -> Self {
+        Style {
+            color,
+            bold,
+            dimmed,
+        }
+    }
 
-    if days_remaining < 0 {
-        year -= 1;
-        days_remaining += 365;
+    const fn color(color: Color) -> Self {
+        Style::new(Some(color), false, false)
     }
 
-    let month_days = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
-    let mut month = 0;
-    let mut day = days_remaining as i32;
+    const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Style::color(Color::TrueColor { r, g, b })
+    }
 
-    for (i, &month_len) in month_days.iter().enumerate() {
-        if day <= month_len {
-            month = i + 1;
-            break;
+    fn paint<T: Colorize>(&self, text: T) -> ColoredString {
+        let mut painted = text.normal();
+        if let Some(color) = self.color {
+            painted = painted.color(color);
+        }
+        if self.bold {
+            painted = painted.bold();
         }
-        day -= month_len;
+        if self.dimmed {
+            painted = painted.dimmed();
+        }
+        painted
     }
+}
 
-    if month == 0 {
-        month = 12;
-        day = month_days[11];
-    }
+/// The styles behind each `Themed` role.
+struct Theme {
+    /// Headline numbers.
+    accent: Style,
+    /// Bars and secondary counts.
+    good: Style,
+    /// Check marks for completed steps.
+    success: Style,
+    heading: Style,
+    /// Boxes, rules and axes.
+    frame: Style,
+    /// Dates and axis labels.
+    muted: Style,
+    /// Empty space behind bars.
+    faint: Style,
+    /// Reference lines and below-average notes.
+    warn: Style,
+    /// Commands to run and the top-ranked day.
+    highlight: Style,
+}
 
-    let month_names = [
-        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
-    ];
+const DEFAULT_THEME: Theme = Theme {
+    accent: Style::color(Color::BrightCyan),
+    good: Style::color(Color::BrightGreen),
+    success: Style::color(Color::Green),
+    heading: Style::color(Color::BrightWhite),
+    frame: Style::color(Color::BrightBlack),
+    muted: Style::rgb(120, 120, 120),
+    faint: Style::rgb(40, 40, 40),
+    warn: Style::color(Color::Yellow),
+    highlight: Style::color(Color::BrightYellow),
+};
 
-    format!("{:02} {} {}", day, month_names[month - 1], year)
-}
+const MONOCHROME_THEME: Theme = Theme {
+    accent: Style::BOLD,
+    good: Style::PLAIN,
+    success: Style::BOLD,
+    heading: Style::BOLD,
+    frame: Style::DIMMED,
+    muted: Style::DIMMED,
+    faint: Style::DIMMED,
+    warn: Style::PLAIN,
+    highlight: Style::BOLD,
+};
 
-fn format_date_storage() -> String {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards");
-    let days = now.as_secs() / 86400;
-    format!("{}", days)
-}
+const SOLARIZED_THEME: Theme = Theme {
+    accent: Style::rgb(42, 161, 152),
+    good: Style::rgb(133, 153, 0),
+    success: Style::rgb(133, 153, 0),
+    heading: Style::rgb(38, 139, 210),
+    frame: Style::rgb(101, 123, 131),
+    muted: Style::rgb(131, 148, 150),
+    faint: Style::rgb(88, 110, 117),
+    warn: Style::rgb(181, 137, 0),
+    highlight: Style::rgb(203, 75, 22),
+};
 
-fn current_timestamp() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards")
-        .as_secs()
+/// The styles for `kind`.
+fn theme_for(kind: ThemeKind) -> &'static Theme {
+    match kind {
+        ThemeKind::Default => &DEFAULT_THEME,
+        ThemeKind::Monochrome => &MONOCHROME_THEME,
+        ThemeKind::Solarized => &SOLARIZED_THEME,
+    }
 }
 
-fn is_running() -> Option<u32> {
-    let pid_file = get_pid_file();
-    if !pid_file.exists() {
-        return None;
+/// Colors text by its role, using the configured `theme`. `--no-color`
+/// and `NO_COLOR` still switch every style off.
+trait Themed: Colorize + Sized {
+    fn accent(self) -> ColoredString {
+        theme_for(config().theme).accent.paint(self)
+    }
+    fn good(self) -> ColoredString {
+        theme_for(config().theme).good.paint(self)
+    }
+    fn success(self) -> ColoredString {
+        theme_for(config().theme).success.paint(self)
+    }
+    fn heading(self) -> ColoredString {
+        theme_for(config().theme).heading.paint(self)
+    }
+    fn frame(self) -> ColoredString {
+        theme_for(config().theme).frame.paint(self)
     }
+    fn muted(self) -> ColoredString {
+        theme_for(config().theme).muted.paint(self)
+    }
+    fn faint(self) -> ColoredString {
+        theme_for(config().theme).faint.paint(self)
+    }
+    fn warn(self) -> ColoredString {
+        theme_for(config().theme).warn.paint(self)
+    }
+    fn highlight(self) -> ColoredString {
+        theme_for(config().theme).highlight.paint(self)
+    }
+}
 
-    let pid_str = fs::read_to_string(&pid_file).ok()?;
-    let pid: u32 = pid_str.trim().parse().ok()?;
+impl Themed for &str {}
+impl Themed for ColoredString {}
 
-    // we need to check if process is actually running
-    #[cfg(unix)]
-    {
-        use std::process::Command;
-        let output = Command::new("kill")
-            .arg("-0")
-            .arg(pid.to_string())
-            .output()
-            .ok()?;
+/// Every glyph keystr draws with, so `--ascii` (or `TERM=dumb`) swaps them
+/// all at once for terminals that can't show Unicode.
+struct Charset {
+    /// Rules, box edges and the x axis.
+    horizontal: &'static str,
+    vertical: &'static str,
+    /// Box corners: top left, top right, bottom left, bottom right.
+    corners: [&'static str; 4],
+    /// Bars and graph columns.
+    filled: &'static str,
+    /// Empty space behind bars.
+    empty: &'static str,
+    /// The average line across a graph.
+    dashed: &'static str,
+    /// The unfilled part of the goal progress bar.
+    track: &'static str,
+    /// Heatmap cells, from no keystrokes up to the busiest.
+    shades: [char; 5],
+    /// Sparkline columns, lowest first.
+    levels: [char; 8],
+    check: &'static str,
+    cross: &'static str,
+    info: &'static str,
+    warning: &'static str,
+    arrow: &'static str,
+    /// Daemon running or not.
+    active: &'static str,
+    inactive: &'static str,
+    up: &'static str,
+    down: &'static str,
+    /// Marks a goal met.
+    star: &'static str,
+    trophy: &'static str,
+    keyboard: &'static str,
+    /// Between the ends of a range, e.g. `500–1000`.
+    range: &'static str,
+    /// Sets off an aside, as in "1200 / 1000 — 120%".
+    dash: &'static str,
+    /// In "weekday × hour".
+    times: &'static str,
+}
 
-        if output.status.success() {
-            Some(pid)
-        } else {
-            let _ = fs::remove_file(&pid_file);
-            None
-        }
-    }
+impl Charset {
+    const UNICODE: Charset = Charset {
+        horizontal: "─",
+        vertical: "│",
+        corners: ["╭", "╮", "╰", "╯"],
+        filled: "█",
+        empty: "·",
+        dashed: "╌",
+        track: "░",
+        shades: ['·', '░', '▒', '▓', '█'],
+        levels: ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'],
+        check: "✓",
+        cross: "✗",
+        info: "ℹ",
+        warning: "⚠",
+        arrow: "→",
+        active: "●",
+        inactive: "○",
+        up: "▲",
+        down: "▼",
+        star: "★",
+        trophy: "🏆",
+        keyboard: "⌨",
+        range: "–",
+        dash: "—",
+        times: "×",
+    };
 
-    #[cfg(not(unix))]
-    {
-        Some(pid)
+    const ASCII: Charset = Charset {
+        horizontal: "-",
+        vertical: "|",
+        corners: ["+", "+", "+", "+"],
+        filled: "#",
+        empty: ".",
+        dashed: "-",
+        track: ".",
+        shades: ['.', ':', '+', '*', '#'],
+        levels: ['_', '.', ':', '-', '=', '+', '*', '#'],
+        check: "+",
+        cross: "x",
+        info: "i",
+        warning: "!",
+        arrow: ">",
+        active: "*",
+        inactive: "o",
+        up: "^",
+        down: "v",
+        star: "*",
+        trophy: "*",
+        keyboard: "#",
+        range: "-",
+        dash: "-",
+        times: "x",
+    };
+
+    /// `title` framed in a box, as its three lines.
+    fn boxed(&self, title: &str) -> [String; 3] {
+        let rule = self.horizontal.repeat(title.chars().count());
+        let [top_left, top_right, bottom_left, bottom_right] = self.corners;
+        [
+            format!("{}{}{}", top_left, rule, top_right),
+            format!("{}{}{}", self.vertical, title, self.vertical),
+            format!("{}{}{}", bottom_left, rule, bottom_right),
+        ]
     }
 }
 
-#[cfg(target_os = "linux")]
-fn is_autostart_enabled() -> bool {
-    let service_name = "keystr";
-    let output = Command::new("systemctl")
-        .arg("--user")
-        .arg("is-enabled")
-        .arg(service_name)
-        .output()
-        .expect("Failed to check service status");
+thread_local! {
+    static CHARSET: std::cell::Cell<&'static Charset> =
+        const { std::cell::Cell::new(&Charset::UNICODE) };
+}
+
+/// The glyphs to draw with on this thread.
+fn charset() -> &'static Charset {
+    CHARSET.with(std::cell::Cell::get)
+}
+
+/// Switches the charset for the calling thread only, so a thread that draws
+/// needs its own call (or a copy of `charset()` taken before it's spawned).
+fn set_charset(charset: &'static Charset) {
+    CHARSET.with(|current| current.set(charset));
+}
+
+/// Prints `message` as an error and exits with a non-zero status, so
+/// scripts can tell a failed command from a successful one.
+fn fail(message: impl fmt::Display) -> ! {
+    eprintln!("\n  {} {}\n", charset().cross.red().bold(), message);
+    std::process::exit(1);
+}
 
-    String::from_utf8_lossy(&output.stdout).trim() == "enabled"
+/// Like `expect`, but reports the error with `fail` instead of panicking.
+trait OrExit<T> {
+    fn or_exit(self, context: &str) -> T;
 }
 
-#[cfg(not(target_os = "linux"))]
-fn is_autostart_enabled() -> bool {
-    false
+impl<T, E: fmt::Display> OrExit<T> for Result<T, E> {
+    fn or_exit(self, context: &str) -> T {
+        self.unwrap_or_else(|error| fail(format_args!("{}: {}", context, error)))
+    }
 }
 
 fn cmd_init() {
-    println!(
-        "\n{}",
-        "╭─────────────────────────────────────╮".bright_black()
-    );
-    println!(
-        "{}",
-        "│  Keystroke Counter Initialization  │"
-            .bright_cyan()
-            .bold()
-    );
-    println!(
-        "{}\n",
-        "╰─────────────────────────────────────╯".bright_black()
-    );
+    let [top, title, bottom] = charset().boxed("  Keystroke Counter Initialization   ");
+    println!("\n{}", top.frame());
+    println!("{}", title.accent().bold());
+    println!("{}\n", bottom.frame());
 
     let config_dir = get_config_dir();
     if !config_dir.exists() {
-        fs::create_dir_all(&config_dir).expect("Failed to create config directory");
+        fs::create_dir_all(&config_dir).or_exit("Failed to create config directory");
         println!(
             "  {} {}",
-            "✓".green().bold(),
+            charset().check.success().bold(),
             "Config directory created".dimmed()
         );
     } else {
         println!(
             "  {} {}",
-            "✓".green().bold(),
+            charset().check.success().bold(),
             "Config directory ready".dimmed()
         );
     }
 
-    let data_file = get_data_file();
-    if !data_file.exists() {
-        let initial_data = KeystrokeData::new();
-        save_data(&initial_data);
+    let store = storage();
+    // Turning `encrypt` on later is picked up here too: an existing plaintext
+    // file is re-saved under the new passphrase.
+    let encrypting = config().encrypt
+        && matches!(config().storage, StorageKind::Json | StorageKind::Bincode)
+        && !fs::read(store.path()).is_ok_and(|bytes| is_encrypted(&bytes));
+    if encrypting {
+        prompt_new_passphrase().or_exit("Could not set a passphrase");
+    }
+
+    if !store.path().exists() {
+        // Opting into SQLite carries over any existing JSON history.
+        let legacy = JsonStorage {
+            path: get_data_file(),
+        };
+        let migrating = config().storage != StorageKind::Json && legacy.path.exists();
+        let initial_data = if migrating {
+            legacy.load().or_exit("Could not load statistics")
+        } else {
+            KeystrokeData::new()
+        };
+        store
+            .save(&initial_data)
+            .or_exit("Could not save statistics");
+        println!(
+            "  {} {}",
+            charset().check.success().bold(),
+            if migrating {
+                "Database created from existing data.json".dimmed()
+            } else {
+                "Data file created with sample history".dimmed()
+            }
+        );
+    } else {
+        println!(
+            "  {} {}",
+            charset().check.success().bold(),
+            "Data file ready".dimmed()
+        );
+    }
+
+    if encrypting {
+        let data = store.load().or_exit("Could not load statistics");
+        store.save(&data).or_exit("Could not save statistics");
+        println!(
+            "  {} {}",
+            charset().check.success().bold(),
+            "Data file encrypted".dimmed()
+        );
+    }
+
+    if ensure_share_key().or_exit("Could not create the share key") {
+        println!(
+            "  {} {}",
+            charset().check.success().bold(),
+            "Share key created".dimmed()
+        );
+    }
+
+    if !get_config_file().exists() {
+        // Carry over settings from a legacy config.json if there is one.
+        save_config(&read_config().or_exit("Invalid config"))
+            .or_exit("Failed to write config file");
         println!(
             "  {} {}",
-            "✓".green().bold(),
-            "Data file created with sample history".dimmed()
+            charset().check.success().bold(),
+            "Config file created with defaults".dimmed()
         );
     } else {
-        println!("  {} {}", "✓".green().bold(), "Data file ready".dimmed());
+        println!(
+            "  {} {}",
+            charset().check.success().bold(),
+            "Config file ready".dimmed()
+        );
     }
 
-    println!("\n  {} {}", "→".bright_cyan(), "Ready to start monitoring!");
+    println!(
+        "\n  {} Ready to start monitoring!",
+        charset().arrow.accent()
+    );
     println!(
         "  {} Run {} to begin\n",
-        "→".bright_cyan(),
-        "keystr start".bright_yellow().bold()
+        charset().arrow.accent(),
+        "keystr start".highlight().bold()
     );
 }
 
+/// Arguments for spawning the daemon for the active profile.
+fn daemon_args() -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(name) = active_profile() {
+        args.push("--profile".to_string());
+        args.push(name.to_string());
+    }
+    args.push("daemon".to_string());
+    args
+}
+
+/// Stdin for the daemon. With encryption on, the passphrase is checked here,
+/// where it can be prompted for, and piped over since the daemon can't ask.
+fn daemon_stdin() -> Stdio {
+    if !config().encrypt {
+        return Stdio::null();
+    }
+    // Exits with an error if the passphrase doesn't decrypt the data.
+    load_data().or_exit("Could not load statistics");
+    Stdio::piped()
+}
+
+/// Writes the passphrase to the daemon's stdin, if `daemon_stdin` piped it.
+fn hand_over_passphrase(daemon: &mut std::process::Child) {
+    use std::io::Write;
+
+    if let Some(mut stdin) = daemon.stdin.take() {
+        stdin
+            .write_all(passphrase().or_exit("Could not read passphrase").as_bytes())
+            .and_then(|()| stdin.write_all(b"\n"))
+            .or_exit("Failed to hand the passphrase to the daemon");
+    }
+}
+
+/// Removes a stale PID file, telling the user about it.
+fn report_stale_pid_file() {
+    if let Some(pid) = clear_stale_pid_file() {
+        println!(
+            "  {} Removed stale PID file (PID {} is not a running daemon)",
+            charset().warning.yellow(),
+            pid.to_string().bright_cyan()
+        );
+    }
+}
+
 fn cmd_start() {
+    report_stale_pid_file();
+
     if let Some(pid) = is_running() {
         println!(
             "\n  {} Monitoring is already active (PID: {})\n",
-            "●".green().bold(),
+            charset().active.green().bold(),
             pid.to_string().bright_cyan()
         );
         return;
     }
 
-    println!("\n  {} Starting keystroke monitor...", "→".bright_cyan());
+    if !has_input_permission() {
+        println!(
+            "\n  {} keystr needs permission to see keyboard events",
+            charset().cross.red().bold()
+        );
+        println!(
+            "  {} To fix it, {}",
+            charset().arrow.bright_cyan(),
+            MACOS_PERMISSION_HINT
+        );
+        println!(
+            "  {} Then run {} again\n",
+            charset().arrow.bright_cyan(),
+            "keystr start".bright_yellow()
+        );
+        std::process::exit(1);
+    }
+
+    println!(
+        "\n  {} Starting keystroke monitor...",
+        charset().arrow.bright_cyan()
+    );
 
-    let exe = std::env::current_exe().expect("Failed to get current executable path");
+    let exe = std::env::current_exe().or_exit("Failed to get current executable path");
+    let _ = fs::remove_file(get_status_file());
 
     #[cfg(unix)]
     {
-        Command::new(exe)
-            .arg("daemon")
-            .stdin(Stdio::null())
+        // The daemon outlives us, so the child handle is intentionally dropped.
+        #[allow(clippy::zombie_processes)]
+        let mut daemon = Command::new(exe)
+            .args(daemon_args())
+            .env_remove("KEYSTR_PASSPHRASE")
+            .stdin(daemon_stdin())
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .spawn()
-            .expect("Failed to start daemon");
+            .or_exit("Failed to start daemon");
+        hand_over_passphrase(&mut daemon);
     }
 
     #[cfg(not(unix))]
     {
-        Command::new(exe)
-            .arg("daemon")
+        let mut daemon = Command::new(exe)
+            .args(daemon_args())
+            .env_remove("KEYSTR_PASSPHRASE")
+            .stdin(daemon_stdin())
             .creation_flags(0x08000000)
             .spawn()
-            .expect("Failed to start daemon");
+            .or_exit("Failed to start daemon");
+        hand_over_passphrase(&mut daemon);
     }
 
-    std::thread::sleep(std::time::Duration::from_millis(500));
+    let started = wait_for_daemon_status();
 
+    if let Err(reason) = &started {
+        eprintln!(
+            "  {} Monitor failed to start: {}",
+            charset().cross.red().bold(),
+            reason
+        );
+        eprintln!(
+            "  {} Details are in {}\n",
+            charset().arrow.bright_cyan(),
+            get_log_file().display()
+        );
+        std::process::exit(1);
+    }
     if let Some(pid) = is_running() {
         println!(
             "  {} Monitor active (PID: {})",
-            "✓".green().bold(),
+            charset().check.green().bold(),
             pid.to_string().bright_cyan()
         );
         println!(
             "  {} Only counting keystrokes - no data captured",
-            "ℹ".blue()
+            charset().info.blue()
         );
         println!(
             "  {} Use {} to stop\n",
-            "→".bright_cyan(),
+            charset().arrow.bright_cyan(),
             "keystr stop".bright_yellow()
         );
     } else {
-        println!("  {} Failed to start monitor\n", "✗".red().bold());
+        eprintln!(
+            "  {} Failed to start monitor\n",
+            charset().cross.red().bold()
+        );
+        std::process::exit(1);
     }
 }
 
-fn cmd_stop() {
+/// Asks the daemon to exit, returning whether the signal was delivered.
+fn signal_stop(pid: u32) -> bool {
+    #[cfg(unix)]
+    let output = Command::new("kill").arg(pid.to_string()).output();
+
+    #[cfg(not(unix))]
+    let output = Command::new("taskkill")
+        .args(&["/PID", &pid.to_string(), "/F"])
+        .output();
+
+    output.is_ok_and(|output| output.status.success())
+}
+
+/// Last resort for a daemon that ignores SIGTERM.
+#[cfg(unix)]
+fn force_kill(pid: u32) -> bool {
+    Command::new("kill")
+        .args(["-KILL", &pid.to_string()])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Stops the monitor if it's running and waits until its process is gone.
+/// Returns `false` when there was nothing to stop or it's still alive.
+fn cmd_stop() -> bool {
     if let Some(pid) = is_running() {
         println!(
             "\n  {} Stopping monitor (PID: {})...",
-            "→".bright_yellow(),
+            charset().arrow.bright_yellow(),
             pid.to_string().bright_cyan()
         );
 
-        #[cfg(unix)]
-        {
-            Command::new("kill")
-                .arg(pid.to_string())
-                .output()
-                .expect("Failed to stop daemon");
+        if !signal_stop(pid) {
+            eprintln!(
+                "  {} Failed to stop monitor\n",
+                charset().cross.red().bold()
+            );
+            return false;
         }
 
-        #[cfg(not(unix))]
-        {
-            Command::new("taskkill")
-                .args(&["/PID", &pid.to_string(), "/F"])
-                .output()
-                .expect("Failed to stop daemon");
+        if !wait_for_exit(pid) {
+            #[cfg(unix)]
+            let killed = {
+                println!(
+                    "  {} Monitor ignored SIGTERM; sending SIGKILL (unsaved counts are lost)",
+                    charset().warning.yellow()
+                );
+                force_kill(pid) && wait_for_exit(pid)
+            };
+            #[cfg(not(unix))]
+            let killed = false;
+
+            if !killed {
+                eprintln!(
+                    "  {} Monitor (PID: {}) is still running\n",
+                    charset().cross.red().bold(),
+                    pid
+                );
+                return false;
+            }
         }
 
+        // Only now is it certain nothing will write the PID file again.
         let _ = fs::remove_file(get_pid_file());
-        println!("  {} Monitor stopped\n", "✓".green().bold());
+        println!("  {} Monitor stopped\n", charset().check.green().bold());
     } else {
-        println!("\n  {} Monitor is not running\n", "ℹ".blue());
+        println!("\n  {} Monitor is not running\n", charset().info.blue());
+        return false;
     }
+    true
 }
 
-fn cmd_status() {
-    println!();
-    if let Some(pid) = is_running() {
-        println!(
-            "  {} {} │ PID: {}",
-            "●".green().bold(),
+/// Waits for a signalled daemon's process to exit. Returns `false` if it's
+/// still alive after a few seconds.
+fn wait_for_exit(pid: u32) -> bool {
+    const STOP_TIMEOUT: Duration = Duration::from_secs(3);
+
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while process_alive(pid) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    true
+}
+
+fn cmd_restart() {
+    // `stop` waits for the old daemon to exit, so the new one can't race it
+    // for the data file.
+    if is_running().is_some() && !cmd_stop() {
+        eprintln!(
+            "  {} Not starting a new monitor\n",
+            charset().cross.red().bold()
+        );
+        std::process::exit(1);
+    }
+
+    cmd_start();
+}
+
+/// The running/inactive line of `keystr status`. The sparkline goes last so
+/// the PID stays where scripts and eyes expect it.
+fn status_line(pid: Option<u32>, stale: Option<u64>, paused: bool, spark: Option<&str>) -> String {
+    let mut line = match pid {
+        Some(pid) if let Some(age) = stale => format!(
+            "  {} {} {} PID: {}",
+            charset().warning.yellow().bold(),
+            format!("Active but stale (last heartbeat {} ago)", format_age(age)).yellow(),
+            charset().vertical,
+            pid.to_string().bright_cyan()
+        ),
+        Some(pid) if paused => format!(
+            "  {} {} {} PID: {}",
+            charset().active.yellow().bold(),
+            "Active, paused by schedule".yellow(),
+            charset().vertical,
+            pid.to_string().bright_cyan()
+        ),
+        Some(pid) => format!(
+            "  {} {} {} PID: {}",
+            charset().active.green().bold(),
             "Active".bright_green().bold(),
+            charset().vertical,
             pid.to_string().bright_cyan()
+        ),
+        None => format!("  {} {}", charset().inactive.dimmed(), "Inactive".dimmed()),
+    };
+    if let Some(spark) = spark {
+        let _ = write!(
+            line,
+            " {} {} {}",
+            charset().vertical,
+            spark.bright_green(),
+            "30d".dimmed()
         );
+    }
+    line
+}
+
+/// How old the heartbeat of the daemon at `pid` is, if that's old enough to
+/// suggest it's wedged.
+fn stale_heartbeat(pid: Option<u32>, now: u64) -> Option<u64> {
+    pid?;
+    let stale_after = config().heartbeat_interval_secs.max(1) * 3;
+    heartbeat_age(now).filter(|&age| age > stale_after)
+}
+
+/// Whether the running daemon at `pid` is outside every `schedule` window
+/// right now, and so not counting.
+fn paused_by_schedule(pid: Option<u32>, now: u64) -> bool {
+    pid.is_some() && !schedule_allows(&config().schedule, local_minute(now))
+}
+
+/// `42s` under a minute, otherwise like `format_duration`.
+fn format_age(seconds: u64) -> String {
+    if seconds < 60 {
+        format!("{}s", seconds)
     } else {
-        println!("  {} {}", "○".dimmed(), "Inactive".dimmed());
+        format_duration(seconds)
+    }
+}
+
+fn cmd_status(format: OutputFormat, spark: bool) {
+    if format == OutputFormat::Json {
+        #[derive(Serialize)]
+        struct StatusReport {
+            running: bool,
+            pid: Option<u32>,
+            /// Set when the daemon's heartbeat has gone quiet.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            stale_seconds: Option<u64>,
+            /// Running, but outside every `schedule` window.
+            paused: bool,
+        }
+
+        let pid = is_running();
+        let now = current_timestamp();
+        let report = StatusReport {
+            running: pid.is_some(),
+            pid,
+            stale_seconds: stale_heartbeat(pid, now),
+            paused: paused_by_schedule(pid, now),
+        };
+        println!("{}", serde_json::to_string(&report).unwrap());
+        std::process::exit(if pid.is_some() { 0 } else { 1 });
+    }
+
+    println!();
+    report_stale_pid_file();
+
+    let spark = spark
+        .then(|| load_data().ok())
+        .flatten()
+        .map(|data| sparkline(&daily_counts(&data, current_day(), 30), &charset().levels));
+    let pid = is_running();
+    let now = current_timestamp();
+    let stale = stale_heartbeat(pid, now);
+    let paused = paused_by_schedule(pid, now);
+    println!("{}", status_line(pid, stale, paused, spark.as_deref()));
+    if stale.is_some() {
+        println!(
+            "  {} The process is there but has stopped checking in; try {}",
+            charset().arrow.accent(),
+            "keystr restart".highlight()
+        );
+    }
+
+    if paused {
+        let windows: Vec<String> = config().schedule.iter().map(ToString::to_string).collect();
+        println!(
+            "  {} Counting resumes inside {}",
+            charset().arrow.accent(),
+            windows.join(", ").highlight()
+        );
     }
 
     if is_autostart_enabled() {
         println!(
             "  {} {}",
-            "✓".green().bold(),
+            charset().check.green().bold(),
             "Auto-start is Enabled".dimmed()
         );
     } else {
         println!(
             "  {} {}",
-            "✗".red().bold(),
+            charset().cross.red().bold(),
             "Auto-start is Disabled".dimmed()
         );
     }
     println!();
 }
 
-fn cmd_daemon() {
-    let pid = std::process::id();
-    let pid_file = get_pid_file();
-    fs::write(&pid_file, pid.to_string()).expect("Failed to write PID file");
-
-    let data = Arc::new(Mutex::new(load_data()));
-    let data_clone = Arc::clone(&data);
+/// Something worth telling the `on_command` hook about.
+#[derive(Debug, PartialEq)]
+enum Milestone {
+    /// Today's count passed this multiple of `on_daily_milestone`.
+    Daily(u64),
+    /// Today's count beat every earlier day.
+    Record(u64),
+    /// The lifetime total reached a new achievement.
+    Lifetime(u64),
+}
 
-    let callback = move |event: Event| {
-        if let EventType::KeyPress(_) = event.event_type {
-            let mut data = data_clone.lock().unwrap();
-            data.increment();
+impl Milestone {
+    fn kind(&self) -> &'static str {
+        match self {
+            Milestone::Daily(_) => "daily",
+            Milestone::Record(_) => "record",
+            Milestone::Lifetime(_) => "lifetime",
+        }
+    }
 
-            if data.total_count % 10 == 0 {
-                save_data(&*data);
+    fn value(&self) -> u64 {
+        match self {
+            Milestone::Daily(value) | Milestone::Record(value) | Milestone::Lifetime(value) => {
+                *value
             }
         }
-    };
-
-    ctrlc::set_handler(move || {
-        let data = data.lock().unwrap();
-        save_data(&*data);
-        let _ = fs::remove_file(get_pid_file());
-        std::process::exit(0);
-    })
-    .expect("Error setting Ctrl-C handler");
-
-    if let Err(error) = listen(callback) {
-        eprintln!("Error: {:?}", error);
-        let _ = fs::remove_file(&pid_file);
     }
 }
 
-fn draw_line_graph(records: &[DailyRecord], max_height: usize) {
-    if records.is_empty() {
-        return;
+/// Follows today's count and reports the milestones it crosses.
+struct MilestoneWatcher {
+    step: Option<u64>,
+    day: u64,
+    /// Today's count at the last check.
+    count: u64,
+    /// Best count of any day before `day`.
+    best_before: u64,
+}
+
+impl MilestoneWatcher {
+    fn new(step: Option<u64>, data: &KeystrokeData, today: u64) -> Self {
+        let count_on = |keep: &dyn Fn(u64) -> bool| {
+            data.daily_records
+                .iter()
+                .filter(|r| keep(r.day()))
+                .map(|r| r.count)
+                .max()
+                .unwrap_or(0)
+        };
+        MilestoneWatcher {
+            step: step.filter(|&step| step > 0),
+            day: today,
+            count: count_on(&|day| day == today),
+            best_before: count_on(&|day| day < today),
+        }
     }
 
-    let max_count = records.iter().map(|r| r.count).max().unwrap_or(1);
-    let scale = max_count as f64 / max_height as f64;
+    /// Notes that `day`'s count is now `count`.
+    fn check(&mut self, day: u64, count: u64) -> Vec<Milestone> {
+        if day != self.day {
+            self.best_before = self.best_before.max(self.count);
+            self.day = day;
+            self.count = 0;
+        }
+        let before = std::mem::replace(&mut self.count, count);
 
-    // Draw Y-axis label
-    println!("     {}", format!("{}", max_count).bright_black());
+        let mut crossed = Vec::new();
+        if let Some(step) = self.step
+            && count / step > before / step
+        {
+            crossed.push(Milestone::Daily(count / step * step));
+        }
+        // The very first day has nothing to beat.
+        if self.best_before > 0 && before <= self.best_before && count > self.best_before {
+            crossed.push(Milestone::Record(count));
+        }
+        crossed
+    }
+}
 
-    for row in (0..max_height).rev() {
-        let threshold = (row as f64 * scale) as u64;
-        print!("     ");
+/// Saves everything counted so far, however little, as the daemon stops.
+fn flush_on_signal(data: &KeystrokeData, store: &dyn Storage, log: &DaemonLog) {
+    if let Err(error) = store.save(data) {
+        log.log(&format!("error: could not save: {}", error));
+    }
+    log.log(&format!("stopped by signal, total {}", data.total_count));
+}
 
-        for (i, record) in records.iter().enumerate() {
-            if record.count > threshold {
-                let bar = if record.count == max_count && row == max_height - 1 {
-                    "█".bright_cyan().bold()
-                } else {
-                    "█".bright_green()
-                };
-                print!("{}", bar);
-            } else {
-                print!("{}", "·".truecolor(40, 40, 40));
-            }
-            if i < records.len() - 1 {
-                print!(" ");
-            }
+/// Adds one event to the total, recording any achievements and telling the
+/// hook about milestones it passes.
+fn count_toward_total(
+    data: &mut KeystrokeData,
+    lifetime_milestones: &[u64],
+    hook: &mut Option<(String, MilestoneWatcher)>,
+    log: &DaemonLog,
+) {
+    let before = data.total_count;
+    data.increment();
+    for total in data.check_achievements(before, lifetime_milestones) {
+        log.log(&format!("reached {} lifetime keystrokes", total));
+        if let Some((command, _)) = hook {
+            run_milestone_hook(command, &Milestone::Lifetime(total), log);
         }
-        println!();
     }
-
-    // Draw X-axis
-    print!("     ");
-    for i in 0..records.len() {
-        print!("{}", "─".bright_black());
-        if i < records.len() - 1 {
-            print!(" ");
+    if let Some((command, watcher)) = hook {
+        let today = data.today_record();
+        for milestone in watcher.check(today.day(), today.count) {
+            run_milestone_hook(command, &milestone, log);
         }
     }
-    println!();
+}
+
+/// Starts `command` for `milestone` without waiting for it. A failure to
+/// start, or a non-zero exit, only goes to the log.
+fn run_milestone_hook(command: &str, milestone: &Milestone, log: &DaemonLog) {
+    #[cfg(windows)]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    };
+    #[cfg(not(windows))]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let spawned = shell
+        .arg(command)
+        .env("KEYSTR_MILESTONE", milestone.value().to_string())
+        .env("KEYSTR_MILESTONE_KIND", milestone.kind())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
 
-    // Draw dates
-    print!("     ");
-    for record in records {
-        let timestamp = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(record.timestamp);
-        let formatted = format_date_display(&timestamp);
-        let parts: Vec<&str> = formatted.split_whitespace().collect();
-        if parts.len() >= 2 {
-            print!("{} ", format!("{}", parts[0]).truecolor(100, 100, 100));
+    match spawned {
+        Ok(mut child) => {
+            let log = log.clone();
+            std::thread::spawn(move || match child.wait() {
+                Ok(status) if !status.success() => {
+                    log.log(&format!("error: on_command exited with {}", status))
+                }
+                Ok(_) => {}
+                Err(error) => log.log(&format!("error: on_command failed: {}", error)),
+            });
         }
+        Err(error) => log.log(&format!("error: could not run on_command: {}", error)),
     }
-    println!("\n");
 }
 
-fn cmd_stats(daily: bool, weekly: bool, monthly: bool) {
-    let data = load_data();
+/// Spots OS auto-repeat: the same key pressed again, with no release in
+/// between, soon after the previous press. Only the last key is remembered,
+/// and only in memory.
+struct RepeatDetector {
+    last: Option<(Key, Instant)>,
+}
 
-    println!(
-        "\n{}",
-        "╭────────────────────────────────────╮".bright_black()
-    );
-    println!(
-        "{}",
-        "│      Keystroke Statistics          │"
-            .bright_cyan()
-            .bold()
-    );
-    println!(
-        "{}",
-        "╰────────────────────────────────────╯".bright_black()
-    );
+impl RepeatDetector {
+    /// Longer than common initial auto-repeat delays.
+    const INTERVAL: Duration = Duration::from_secs(1);
 
-    println!(
-        "\n     {} {}",
-        "Total:".dimmed(),
-        data.total_count.to_string().bright_cyan().bold()
-    );
+    fn new() -> Self {
+        RepeatDetector { last: None }
+    }
 
-    if daily || (!weekly && !monthly) {
-        println!(
-            "\n     {}",
-            "Daily Activity (Last 7 Days)".bright_white().bold()
-        );
-        println!("     {}\n", "─".repeat(28).bright_black());
+    /// Notes a press of `key`, returning whether it's an auto-repeat.
+    fn press(&mut self, key: Key, now: Instant) -> bool {
+        let repeat = self
+            .last
+            .is_some_and(|(last, at)| last == key && now.duration_since(at) <= Self::INTERVAL);
+        self.last = Some((key, now));
+        repeat
+    }
 
-        let daily_stats = data.get_daily_stats(7);
+    fn release(&mut self, key: Key) {
+        if self.last.is_some_and(|(last, _)| last == key) {
+            self.last = None;
+        }
+    }
+}
 
-        // Reverse for chronological order in graph
-        let mut graph_data = daily_stats.clone();
-        graph_data.reverse();
+/// Rolling window of recent keypresses used to compute keystrokes per minute.
+///
+/// Only press times are kept, never which key, and the buffer is bounded so a
+/// stuck key can't grow it without limit.
+struct KpmMeter {
+    presses: VecDeque<Instant>,
+}
 
-        if !graph_data.is_empty() {
-            draw_line_graph(&graph_data, 10);
+impl KpmMeter {
+    const WINDOW: Duration = Duration::from_secs(60);
+    const CAPACITY: usize = 3000;
+
+    fn new() -> Self {
+        KpmMeter {
+            presses: VecDeque::with_capacity(Self::CAPACITY),
         }
+    }
 
-        for record in &daily_stats {
-            let timestamp =
-                SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(record.timestamp);
-            let formatted_date = format_date_display(&timestamp);
-            println!(
-                "     {} │ {}",
-                formatted_date.truecolor(120, 120, 120),
-                record.count.to_string().bright_green()
-            );
+    fn record(&mut self, now: Instant) {
+        if self.presses.len() == Self::CAPACITY {
+            self.presses.pop_front();
         }
+        self.presses.push_back(now);
     }
 
-    if weekly {
-        let weekly_count = data.get_weekly_stats();
-        println!("\n     {}", "Weekly Summary (7 days)".bright_white().bold());
-        println!("     {}", "─".repeat(28).bright_black());
-        println!(
-            "     {} keystrokes\n",
-            weekly_count.to_string().bright_cyan().bold()
-        );
+    /// Keypresses within the last minute, evicting anything older.
+    fn kpm(&mut self, now: Instant) -> u64 {
+        while let Some(&oldest) = self.presses.front() {
+            if now.duration_since(oldest) <= Self::WINDOW {
+                break;
+            }
+            self.presses.pop_front();
+        }
+        self.presses.len() as u64
     }
 
-    if monthly {
-        let monthly_count = data.get_monthly_stats();
-        println!(
-            "\n     {}",
-            "Monthly Summary (30 days)".bright_white().bold()
-        );
-        println!("     {}", "─".repeat(28).bright_black());
-        println!(
-            "     {} keystrokes\n",
-            monthly_count.to_string().bright_cyan().bold()
-        );
+    fn clear(&mut self) {
+        self.presses.clear();
     }
+}
 
-    println!();
+/// Accumulates active typing time from keypress timestamps.
+///
+/// The gap since the previous keypress counts as active unless it exceeds
+/// the idle threshold, in which case a new active window starts. A
+/// [`clamped`](Self::clamped) tracker counts such a gap as the threshold
+/// instead, estimating time spent at the keyboard including short pauses.
+struct ActivityTracker {
+    idle_threshold: Duration,
+    clamp: bool,
+    last_press: Option<Instant>,
+    /// Sub-second remainder not yet reported.
+    carry: Duration,
 }
 
-fn cmd_export(output: &str) {
-    let data = load_data();
+impl ActivityTracker {
+    fn new(idle_threshold: Duration) -> Self {
+        ActivityTracker {
+            idle_threshold,
+            clamp: false,
+            last_press: None,
+            carry: Duration::ZERO,
+        }
+    }
+
+    fn clamped(idle_threshold: Duration) -> Self {
+        ActivityTracker {
+            clamp: true,
+            ..ActivityTracker::new(idle_threshold)
+        }
+    }
 
-    let mut content = String::new();
-    content.push_str("╭────────────────────────────────────╮\n");
-    content.push_str("│   Keystr Counter Statistics     │\n");
-    content.push_str("╰────────────────────────────────────╯\n\n");
-    content.push_str(&format!("Total Keystrokes: {}\n\n", data.total_count));
+    /// Time since the previous keypress, if there was one in this window.
+    fn gap(&self, now: Instant) -> Option<Duration> {
+        self.last_press
+            .map(|last| now.saturating_duration_since(last))
+    }
 
-    content.push_str("Daily Records:\n");
-    content.push_str("────────────────────────────────────\n");
-    for record in data.daily_records.iter().rev() {
-        let timestamp = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(record.timestamp);
-        let formatted_date = format_date_display(&timestamp);
-        content.push_str(&format!(
-            "{}: {} keystrokes\n",
-            formatted_date, record.count
-        ));
+    /// Records a keypress and returns the whole seconds of active time it adds.
+    fn record(&mut self, now: Instant) -> u64 {
+        if let Some(last) = self.last_press.replace(now) {
+            let gap = now.saturating_duration_since(last);
+            if gap <= self.idle_threshold {
+                self.carry += gap;
+            } else if self.clamp {
+                self.carry += self.idle_threshold;
+            }
+        }
+        let seconds = self.carry.as_secs();
+        self.carry -= Duration::from_secs(seconds);
+        seconds
     }
 
-    content.push_str("\nWeekly Summary (7 days):  ");
-    content.push_str(&format!("{} keystrokes\n", data.get_weekly_stats()));
+    /// Starts a new active window at the next keypress.
+    fn reset(&mut self) {
+        self.last_press = None;
+    }
+}
 
-    content.push_str("Monthly Summary (30 days): ");
-    content.push_str(&format!("{} keystrokes\n", data.get_monthly_stats()));
+/// Class and title of the focused window, for `filter_app`. `None` where
+/// that can't be found out: under Wayland, or without `xprop`.
+#[cfg(target_os = "linux")]
+fn focused_app() -> Option<String> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() || std::env::var_os("DISPLAY").is_none() {
+        return None;
+    }
+    let xprop = |args: &[&str]| {
+        let output = Command::new("xprop")
+            .args(args)
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let root = xprop(&["-root", "_NET_ACTIVE_WINDOW"])?;
+    let window = xprop(&[
+        "-id",
+        parse_active_window(&root)?,
+        "WM_CLASS",
+        "_NET_WM_NAME",
+    ])?;
+    Some(xprop_values(&window))
+}
 
-    fs::write(output, content).expect("Failed to write export file");
-    println!(
-        "\n  {} Exported to {}\n",
-        "✓".green().bold(),
-        output.bright_cyan()
-    );
+/// Name of the frontmost app, via System Events.
+#[cfg(target_os = "macos")]
+fn focused_app() -> Option<String> {
+    let output = Command::new("osascript")
+        .args([
+            "-e",
+            "tell application \"System Events\" to get name of first application process whose frontmost is true",
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !name.is_empty()).then_some(name)
 }
 
-fn cmd_reset() {
-    println!();
-    print!("  {} ", "Reset all statistics? (y/N):".bright_yellow());
-    use std::io::{self, Write};
-    io::stdout().flush().unwrap();
+/// Title of the foreground window.
+#[cfg(windows)]
+fn focused_app() -> Option<String> {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowTextW};
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
+    let mut title = [0u16; 512];
+    // SAFETY: GetWindowTextW writes at most `title.len()` units into the buffer.
+    let len = unsafe {
+        let window = GetForegroundWindow();
+        if window.is_null() {
+            return None;
+        }
+        GetWindowTextW(window, title.as_mut_ptr(), title.len() as i32)
+    };
+    (len > 0).then(|| String::from_utf16_lossy(&title[..len as usize]))
+}
 
-    if input.trim().to_lowercase() == "y" {
-        let new_data = KeystrokeData::new();
-        save_data(&new_data);
-        println!("  {} All statistics cleared\n", "✓".green().bold());
-    } else {
-        println!("  {} Reset cancelled\n", "ℹ".blue());
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn focused_app() -> Option<String> {
+    None
+}
+
+/// The window id in `xprop -root _NET_ACTIVE_WINDOW` output, e.g.
+/// `_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007`. `0x0` means nothing
+/// has focus.
+#[cfg(any(target_os = "linux", test))]
+fn parse_active_window(output: &str) -> Option<&str> {
+    let (_, id) = output.split_once("# ")?;
+    let id = id.split(',').next()?.trim();
+    (id.starts_with("0x") && id != "0x0").then_some(id)
+}
+
+/// The quoted values in `xprop` output joined by spaces, so
+/// `WM_CLASS(STRING) = "code", "Code"` gives `code Code`.
+#[cfg(any(target_os = "linux", test))]
+fn xprop_values(output: &str) -> String {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(" = ").map(|(_, values)| values))
+        .flat_map(|values| values.split(", "))
+        .map(|value| value.trim().trim_matches('"'))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn app_matches(filter: &str, focused: &str) -> bool {
+    focused.to_lowercase().contains(&filter.to_lowercase())
+}
+
+/// Notices the machine sleeping. `Instant` stands still while suspended but
+/// the wall clock keeps going, so the wall clock pulling ahead means the
+/// machine was asleep in between.
+struct SleepDetector {
+    wall: SystemTime,
+    mono: Instant,
+}
+
+impl SleepDetector {
+    /// Ignore smaller drift, e.g. from NTP corrections.
+    const THRESHOLD: Duration = Duration::from_secs(30);
+
+    fn new(wall: SystemTime, mono: Instant) -> Self {
+        SleepDetector { wall, mono }
+    }
+
+    /// Returns roughly how long the machine slept since the last check.
+    fn check(&mut self, wall: SystemTime, mono: Instant) -> Option<Duration> {
+        let wall_elapsed = wall.duration_since(self.wall).unwrap_or_default();
+        let mono_elapsed = mono.saturating_duration_since(self.mono);
+        self.wall = wall;
+        self.mono = mono;
+        wall_elapsed
+            .checked_sub(mono_elapsed)
+            .filter(|&slept| slept > Self::THRESHOLD)
     }
 }
 
-#[cfg(target_os = "linux")]
-fn cmd_enable() {
-    let exe_path = std::env::current_exe().expect("Failed to get executable path");
-    let service_name = "keystr";
-    let service_unit = format!(
-        r#"[Unit]
-Description=Keystr Daemon
+/// Timestamped log of daemon lifecycle events, kept to a bounded size.
+///
+/// The daemon runs detached with its output discarded, so this is the only
+/// record of why it stopped.
+#[derive(Clone)]
+struct DaemonLog {
+    path: PathBuf,
+    max_bytes: u64,
+    /// Also print each line to stderr, for `run --foreground`.
+    echo: bool,
+}
 
-[Service]
-ExecStart={} daemon
-Restart=always
+impl DaemonLog {
+    fn log(&self, message: &str) {
+        use std::io::Write;
 
-[Install]
-WantedBy=default.target
-"#,
-        exe_path.to_str().unwrap()
-    );
+        // Keep one previous generation so the lead-up to a crash survives.
+        if fs::metadata(&self.path).is_ok_and(|meta| meta.len() >= self.max_bytes) {
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(".1");
+            let _ = fs::rename(&self.path, rotated);
+        }
 
-    let service_path = dirs::config_dir()
-        .unwrap()
-        .join("systemd/user")
-        .join(format!("{}.service", service_name));
+        let now = current_timestamp();
+        let line = format!(
+            "[{}] {}\n",
+            format_log_time(now, utc_offset_at(now)),
+            message
+        );
+        if self.echo {
+            // Pad over the live count line in case it's showing.
+            eprintln!("\r{:<60}", line.trim_end());
+        }
+        if let Ok(mut file) = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+        {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+}
 
-    fs::create_dir_all(service_path.parent().unwrap()).expect("Failed to create systemd user dir");
-    fs::write(&service_path, service_unit).expect("Failed to write service file");
+/// Decides when the daemon writes: once `save_every` events are pending,
+/// but no sooner than `min_interval` after the previous write, so a fast
+/// typist doesn't rewrite the data file every second. Events that arrive in
+/// between just stay pending.
+struct SaveCoalescer {
+    save_every: u64,
+    min_interval: Duration,
+    last_save: Option<Instant>,
+}
 
-    Command::new("systemctl")
-        .arg("--user")
-        .arg("daemon-reload")
-        .output()
-        .expect("Failed to reload daemon");
+impl SaveCoalescer {
+    fn new(save_every: u64, min_interval: Duration) -> Self {
+        SaveCoalescer {
+            save_every: save_every.max(1),
+            min_interval,
+            last_save: None,
+        }
+    }
 
-    Command::new("systemctl")
-        .arg("--user")
-        .arg("enable")
-        .arg(service_name)
-        .output()
-        .expect("Failed to enable service");
+    /// Whether `min_interval` has passed since the last write.
+    fn ready(&self, now: Instant) -> bool {
+        self.last_save
+            .is_none_or(|last| now.saturating_duration_since(last) >= self.min_interval)
+    }
 
-    Command::new("systemctl")
-        .arg("--user")
-        .arg("start")
-        .arg(service_name)
-        .output()
-        .expect("Failed to start service");
+    /// Whether `unsaved` pending events should be written now.
+    fn due(&self, unsaved: u64, now: Instant) -> bool {
+        unsaved >= self.save_every && self.ready(now)
+    }
 
-    println!("\n  {} Auto-start enabled.", "✓".green().bold());
-    println!("  {} Keystr will now run on boot.", "ℹ".blue());
-    println!(
-        "  {} Use `keystr disable` to turn off.\n",
-        "→".bright_cyan()
-    );
+    /// Records a write, successful or not, so a failing disk is retried at
+    /// the same pace rather than on every keystroke.
+    fn attempted(&mut self, now: Instant) {
+        self.last_save = Some(now);
+    }
 }
 
-#[cfg(target_os = "linux")]
-fn cmd_disable() {
-    let service_name = "keystr";
-    let service_path = dirs::config_dir()
-        .unwrap()
-        .join("systemd/user")
-        .join(format!("{}.service", service_name));
+/// Saves `data` if anything was counted since the last save, returning how
+/// many events were flushed. Callers hold the data lock. On failure the
+/// events stay pending for the next attempt.
+fn flush_unsaved(
+    data: &KeystrokeData,
+    store: &dyn Storage,
+    unsaved: &AtomicU64,
+) -> Result<u64, String> {
+    let pending = unsaved.swap(0, Ordering::SeqCst);
+    if pending > 0
+        && let Err(error) = store.save(data)
+    {
+        unsaved.fetch_add(pending, Ordering::SeqCst);
+        return Err(error);
+    }
+    Ok(pending)
+}
 
-    Command::new("systemctl")
-        .arg("--user")
-        .arg("stop")
-        .arg(service_name)
-        .output()
-        .expect("Failed to stop service");
+/// Runs the monitor in the current process. `foreground` keeps a live count
+/// on the terminal, which is handy for checking the input hook works.
+fn cmd_daemon(foreground: bool) {
+    use std::io::IsTerminal;
 
-    Command::new("systemctl")
-        .arg("--user")
-        .arg("disable")
-        .arg(service_name)
-        .output()
-        .expect("Failed to disable service");
+    let config = config();
+    let log = DaemonLog {
+        path: get_log_file(),
+        max_bytes: config.log_max_bytes,
+        echo: foreground,
+    };
+
+    // Two `keystr start`s can both see no daemon and spawn one each; the lock
+    // makes sure only the first survives. It's released when the process exits.
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(get_daemon_lock_file())
+        .or_exit("Failed to open daemon lock file");
+    if lock.try_lock().is_err() {
+        log.log("another daemon is already running; exiting");
+        std::process::exit(1);
+    }
+
+    let pid_file = get_pid_file();
+    fs::write(&pid_file, pid_file_contents()).or_exit("Failed to write PID file");
+    log.log(&format!("started (PID {})", std::process::id()));
+
+    // Started by `keystr start`, which pipes the passphrase; in a terminal
+    // it's prompted for as usual.
+    if config.encrypt
+        && !std::io::stdin().is_terminal()
+        && let Err(error) = receive_passphrase(std::io::stdin().lock())
+    {
+        log.log(&format!("error: {}", error));
+        write_daemon_status(&format!("error: {}", error));
+        let _ = fs::remove_file(&pid_file);
+        std::process::exit(1);
+    }
+
+    let coalescer = Arc::new(Mutex::new(SaveCoalescer::new(
+        config.save_every,
+        Duration::from_secs(config.min_save_interval_secs),
+    )));
+    let mut initial = match load_data() {
+        Ok(data) => data,
+        Err(error) => {
+            log.log(&format!("error: could not load statistics: {}", error));
+            write_daemon_status(&format!("error: could not load statistics: {}", error));
+            let _ = fs::remove_file(&pid_file);
+            std::process::exit(1);
+        }
+    };
+    if let Some(retention_days) = config.retention_days {
+        let removed = initial.prune(retention_days);
+        if removed > 0 {
+            if let Err(error) = save_data(&initial) {
+                log.log(&format!("error: could not save statistics: {}", error));
+            }
+            log.log(&format!(
+                "pruned {} record(s) older than {} days",
+                removed, retention_days
+            ));
+        }
+    }
+    let mut hook = config.on_command.clone().map(|command| {
+        let watcher = MilestoneWatcher::new(config.on_daily_milestone, &initial, current_day());
+        (command, watcher)
+    });
+    let data = Arc::new(Mutex::new(initial));
+
+    // Taking the data lock shows the counting side isn't stuck holding it,
+    // not just that the process is still there.
+    let heartbeat_data = Arc::clone(&data);
+    let heartbeat = Duration::from_secs(config.heartbeat_interval_secs.max(1));
+    std::thread::spawn(move || {
+        loop {
+            drop(heartbeat_data.lock().unwrap());
+            write_heartbeat(current_timestamp());
+            std::thread::sleep(heartbeat);
+        }
+    });
+    // Events counted since the last save, guarded by the data lock.
+    let unsaved = Arc::new(AtomicU64::new(0));
+    let kpm_meter = Arc::new(Mutex::new(KpmMeter::new()));
+    let idle_threshold = config.idle_threshold_secs;
+    let lifetime_milestones = config.lifetime_milestones.clone();
+    let mut activity = ActivityTracker::new(Duration::from_secs(idle_threshold));
+    let mut typing = ActivityTracker::clamped(Duration::from_secs(idle_threshold));
+    let mut repeats = RepeatDetector::new();
+    let count_repeats = config.count_repeats;
+    let count_keys = config.count.contains(&CountedEvent::Keys);
+    let count_mouse = config.count.contains(&CountedEvent::Mouse);
+    let count_scroll = config.count.contains(&CountedEvent::Scroll);
+    if config.count.is_empty() {
+        log.log("count is empty, so nothing adds to the total");
+    } else if config.count != [CountedEvent::Keys] {
+        let kinds: Vec<&str> = [
+            (count_keys, "keys"),
+            (count_mouse, "mouse"),
+            (count_scroll, "scroll"),
+        ]
+        .into_iter()
+        .filter_map(|(counted, kind)| counted.then_some(kind))
+        .collect();
+        log.log(&format!("counting {} toward the total", kinds.join(", ")));
+    }
+
+    // Without this the first keypress after waking would look like it came
+    // straight after the last one before sleep, counting the gap as active.
+    let resumed = Arc::new(AtomicBool::new(false));
+    let resumed_flag = Arc::clone(&resumed);
+    let kpm_clone = Arc::clone(&kpm_meter);
+    let sleep_log = log.clone();
+    std::thread::spawn(move || {
+        let mut detector = SleepDetector::new(SystemTime::now(), Instant::now());
+        loop {
+            std::thread::sleep(Duration::from_secs(5));
+            if take_clock_warning() {
+                sleep_log.log(CLOCK_WARNING);
+            }
+            if let Some(slept) = detector.check(SystemTime::now(), Instant::now()) {
+                kpm_clone.lock().unwrap().clear();
+                resumed_flag.store(true, Ordering::SeqCst);
+                sleep_log.log(&format!(
+                    "resumed after about {} asleep",
+                    format_duration(slept.as_secs())
+                ));
+            }
+        }
+    });
+
+    // Polled rather than asked per keystroke, since finding the focused
+    // window can mean running a helper. Where it can't be found at all,
+    // everything counts rather than nothing.
+    let app_filter = config
+        .filter_app
+        .clone()
+        .filter(|filter| !filter.is_empty());
+    let in_app = Arc::new(AtomicBool::new(true));
+    if let Some(filter) = app_filter.clone() {
+        log.log(&format!("counting only while \"{}\" is focused", filter));
+        let in_app = Arc::clone(&in_app);
+        let focus_log = log.clone();
+        std::thread::spawn(move || {
+            let mut detected = true;
+            loop {
+                let focused = focused_app();
+                if focused.is_some() != detected {
+                    detected = focused.is_some();
+                    focus_log.log(if detected {
+                        "found the focused window again; filter_app applies"
+                    } else {
+                        "can't tell which window is focused; counting every app until it can"
+                    });
+                }
+                let matches = focused.is_none_or(|app| app_matches(&filter, &app));
+                in_app.store(matches, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(500));
+            }
+        });
+    }
+
+    // Checked on a timer so the callback never has to work out the time of
+    // day; a few seconds' lag at the edges of a window doesn't matter.
+    let in_schedule = Arc::new(AtomicBool::new(true));
+    if !config.schedule.is_empty() {
+        let schedule = config.schedule.clone();
+        let windows: Vec<String> = schedule.iter().map(ToString::to_string).collect();
+        log.log(&format!("counting only during {}", windows.join(", ")));
+        let in_schedule = Arc::clone(&in_schedule);
+        let schedule_log = log.clone();
+        std::thread::spawn(move || {
+            let mut allowed = true;
+            loop {
+                let now = schedule_allows(&schedule, local_minute(current_timestamp()));
+                if now != allowed {
+                    allowed = now;
+                    schedule_log.log(if allowed {
+                        "resumed by schedule"
+                    } else {
+                        "paused by schedule"
+                    });
+                }
+                in_schedule.store(allowed, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_secs(5));
+            }
+        });
+    }
+    let counting = move || in_app.load(Ordering::SeqCst) && in_schedule.load(Ordering::SeqCst);
+
+    let data_clone = Arc::clone(&data);
+    let unsaved_clone = Arc::clone(&unsaved);
+    let coalescer_clone = Arc::clone(&coalescer);
+    let kpm_clone = Arc::clone(&kpm_meter);
+    let store = storage();
+    let save_log = log.clone();
+    let callback = move |event: Event| {
+        let mut data = data_clone.lock().unwrap();
+        match event.event_type {
+            EventType::KeyPress(key) => {
+                let now = Instant::now();
+                if resumed.swap(false, Ordering::SeqCst) {
+                    activity.reset();
+                    typing.reset();
+                }
+                let repeat = repeats.press(key, now);
+                if !counting() || !count_keys {
+                    return;
+                }
+                data.record_press(repeat);
+                if repeat && !count_repeats {
+                    return;
+                }
+                count_toward_total(&mut data, &lifetime_milestones, &mut hook, &save_log);
+                if let Some(filter) = &app_filter {
+                    data.increment_app(filter);
+                }
+                // Only the category is counted, never the key itself.
+                data.increment_category(KeyCategory::of(&key));
+                let gap = activity.gap(now).map(|gap| gap.as_secs());
+                data.record_gap(gap, idle_threshold);
+                data.add_active_seconds(activity.record(now));
+                data.add_typing_seconds(typing.record(now));
+                let mut meter = kpm_clone.lock().unwrap();
+                meter.record(now);
+                data.record_kpm(meter.kpm(now), current_timestamp());
+            }
+            EventType::KeyRelease(key) => {
+                repeats.release(key);
+                return;
+            }
+            EventType::ButtonPress(_) if counting() => {
+                data.increment_click();
+                if count_mouse {
+                    count_toward_total(&mut data, &lifetime_milestones, &mut hook, &save_log);
+                }
+            }
+            EventType::Wheel { .. } if counting() && count_scroll => {
+                count_toward_total(&mut data, &lifetime_milestones, &mut hook, &save_log);
+            }
+            _ => return,
+        }
+
+        let pending = unsaved_clone.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut coalescer = coalescer_clone.lock().unwrap();
+        let now = Instant::now();
+        if coalescer.due(pending, now) {
+            // Only today's record changes while counting, so skip the rest.
+            // A failed save leaves the count pending, so it's retried.
+            coalescer.attempted(now);
+            match store.increment_day(&data, current_day()) {
+                Ok(()) => unsaved_clone.store(0, Ordering::SeqCst),
+                Err(error) => save_log.log(&format!("error: could not save: {}", error)),
+            }
+        }
+    };
+
+    // Flush on a timer too, so a few keystrokes before an idle gap aren't
+    // lost. The signal handler saves and exits while holding the data lock,
+    // so this thread can't write again once shutdown has started.
+    let data_clone = Arc::clone(&data);
+    let unsaved_clone = Arc::clone(&unsaved);
+    let interval = Duration::from_secs(config.save_interval_secs.max(1));
+    let timer_log = log.clone();
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(interval);
+            let data = data_clone.lock().unwrap();
+            let mut coalescer = coalescer.lock().unwrap();
+            let now = Instant::now();
+            if !coalescer.ready(now) {
+                continue;
+            }
+            match flush_unsaved(&data, storage().as_ref(), &unsaved_clone) {
+                Ok(0) => {}
+                Ok(flushed) => {
+                    coalescer.attempted(now);
+                    timer_log.log(&format!(
+                        "saved {} pending event(s), total {}",
+                        flushed, data.total_count
+                    ))
+                }
+                Err(error) => {
+                    coalescer.attempted(now);
+                    timer_log.log(&format!("error: could not save: {}", error))
+                }
+            }
+        }
+    });
+
+    // Publish the live typing speed separately from the persisted history.
+    let data_clone = Arc::clone(&data);
+    let glyphs = charset();
+    std::thread::spawn(move || {
+        use std::io::Write;
+
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let live = LiveStats {
+                kpm: kpm_meter.lock().unwrap().kpm(Instant::now()),
+                updated: current_timestamp(),
+            };
+            if let Ok(json) = serde_json::to_string(&live) {
+                let _ = fs::write(get_live_file(), json);
+            }
+
+            if foreground {
+                let (total, today) = {
+                    let data = data_clone.lock().unwrap();
+                    (data.total_count, data.get_today_stats())
+                };
+                print!(
+                    "\r  {} Total {}  Today {}  {} keystrokes/min   ",
+                    glyphs.keyboard.bright_cyan(),
+                    total.to_string().bright_cyan().bold(),
+                    today.to_string().bright_green(),
+                    live.kpm.to_string().bright_yellow()
+                );
+                let _ = std::io::stdout().flush();
+            }
+        }
+    });
+
+    // With the `termination` feature this also fires on SIGTERM, which is
+    // what `keystr stop` sends, so the last unsaved counts are flushed.
+    let signal_log = log.clone();
+    ctrlc::set_handler(move || {
+        let data = data.lock().unwrap();
+        flush_on_signal(&data, storage().as_ref(), &signal_log);
+        let _ = fs::remove_file(get_live_file());
+        let _ = fs::remove_file(get_heartbeat_file());
+        let _ = fs::remove_file(get_pid_file());
+        std::process::exit(0);
+    })
+    .or_exit("Error setting signal handler");
+
+    // `listen` blocks for good once it's running, so it only gets a moment
+    // to fail before the daemon counts as started.
+    let failed = Arc::new(AtomicBool::new(false));
+    let failed_flag = Arc::clone(&failed);
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(750));
+        if !failed_flag.load(Ordering::SeqCst) {
+            write_daemon_status("listening");
+        }
+    });
 
-    fs::remove_file(service_path).ok();
+    if let Err(error) = listen(callback) {
+        failed.store(true, Ordering::SeqCst);
+        let message = describe_listen_error(&error, on_wayland());
+        write_daemon_status(&format!("error: {}", message));
+        log.log(&format!("error: {}", message));
+        let _ = fs::remove_file(&pid_file);
+        std::process::exit(1);
+    }
+}
 
-    println!("\n  {} Auto-start disabled.", "✓".green().bold());
+fn cmd_kpm() {
+    let live: Option<LiveStats> = fs::read_to_string(get_live_file())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+
+    // A snapshot older than a few seconds means the daemon isn't updating it.
+    match live.filter(|live| current_timestamp().saturating_sub(live.updated) <= 5) {
+        Some(live) => println!(
+            "\n  {} {} keystrokes/min\n",
+            charset().keyboard.bright_cyan(),
+            live.kpm.to_string().bright_cyan().bold()
+        ),
+        None => {
+            println!(
+                "\n  {} No live data - is the monitor running? Try {}\n",
+                charset().info.blue(),
+                "keystr start".bright_yellow()
+            );
+            std::process::exit(1);
+        }
+    }
 }
 
-#[cfg(not(target_os = "linux"))]
-fn cmd_enable() {
-    println!(
-        "\n  {} Auto-start is only supported on Linux with systemd.\n",
-        "ℹ".blue()
-    );
+/// Sends `()` whenever the storage file, or an SQLite sidecar of it,
+/// changes. Watches the directory since saves replace the file.
+fn watch_data_file(
+    path: &std::path::Path,
+) -> notify::Result<(notify::RecommendedWatcher, Receiver<()>)> {
+    use notify::{EventKind, RecursiveMode, Watcher};
+
+    let name = path.file_name().unwrap_or_default().to_os_string();
+    let dir = path
+        .parent()
+        .unwrap_or(std::path::Path::new("."))
+        .to_path_buf();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        let ours = event.paths.iter().any(|changed| {
+            changed.file_name().is_some_and(|changed| {
+                changed == name
+                    || changed
+                        .to_string_lossy()
+                        .starts_with(&format!("{}-", name.to_string_lossy()))
+            })
+        });
+        if ours {
+            let _ = sender.send(());
+        }
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    Ok((watcher, receiver))
 }
 
-#[cfg(not(target_os = "linux"))]
-fn cmd_disable() {
+/// Blocks until the next change, then swallows any that follow within
+/// `quiet`, so a burst of writes redraws once. Returns `false` once the
+/// watcher is gone.
+fn wait_for_change(changes: &Receiver<()>, quiet: Duration) -> bool {
+    if changes.recv().is_err() {
+        return false;
+    }
+    while changes.recv_timeout(quiet).is_ok() {}
+    true
+}
+
+fn cmd_watch(interval: u64, watch_file: bool) {
+    use std::io::{self, Write};
+
+    ctrlc::set_handler(|| {
+        println!();
+        std::process::exit(0);
+    })
+    .or_exit("Error setting Ctrl-C handler");
+
     println!(
-        "\n  {} Auto-start is only supported on Linux with systemd.\n",
-        "ℹ".blue()
+        "\n  {} Watching keystrokes (Ctrl-C to exit)\n",
+        charset().arrow.bright_cyan()
     );
-}
 
-fn main() {
-    let cli = Cli::parse();
+    let interval = Duration::from_secs(interval.max(1));
+    let watcher = if watch_file {
+        let path = storage().watch_path();
+        match watch_data_file(&path) {
+            Ok(watcher) => Some(watcher),
+            Err(error) => {
+                println!(
+                    "  {} Can't watch {} ({}); refreshing every {}s instead\n",
+                    charset().info.blue(),
+                    path.display(),
+                    error,
+                    interval.as_secs()
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut first_frame = true;
+    loop {
+        // Only ever read the data file so the daemon's writes are untouched.
+        let data = load_data().or_exit("Could not load statistics");
+        let status = if is_running().is_some() {
+            format!(
+                "{} {}",
+                charset().active.green().bold(),
+                "Active".bright_green()
+            )
+        } else {
+            format!("{} {}", charset().inactive.dimmed(), "Inactive".dimmed())
+        };
+
+        // Cursor movement is an escape code too, so without color each
+        // frame is simply printed below the last.
+        let redraw = colored::control::SHOULD_COLORIZE.should_colorize();
+        let clear = if redraw { "\r\x1b[2K" } else { "" };
+        if !first_frame {
+            if redraw {
+                // Move the cursor back up over the previous frame.
+                print!("\x1b[3A");
+            } else {
+                println!();
+            }
+        }
+        first_frame = false;
+
+        println!("{}     {}", clear, status);
+        println!(
+            "{}     {} {}",
+            clear,
+            "Total:".dimmed(),
+            data.total_count.to_string().bright_cyan().bold()
+        );
+        println!(
+            "{}     {} {}",
+            clear,
+            "Today:".dimmed(),
+            data.get_today_stats().to_string().bright_green()
+        );
+        let _ = io::stdout().flush();
+
+        match &watcher {
+            Some((_, changes)) => {
+                if !wait_for_change(changes, Duration::from_millis(200)) {
+                    fail("Lost the file watcher");
+                }
+            }
+            None => std::thread::sleep(interval),
+        }
+    }
+}
+
+/// Draws daily counts with a dashed reference line at `average`, smoothed
+/// with a `smooth`-day moving average when given.
+fn draw_line_graph(
+    out: &mut String,
+    records: &[DailyRecord],
+    max_height: usize,
+    average: f64,
+    smooth: Option<u64>,
+) -> fmt::Result {
+    let values = plotted_values(records, smooth);
+    let labels: Vec<String> = records
+        .iter()
+        .map(|record| {
+            let (_, _, day) = civil_from_days(record.day() as i64);
+            format!("{:02}", day)
+        })
+        .collect();
+
+    let average = Some(average.round() as u64).filter(|&average| average > 0);
+    draw_bar_graph(out, &values, &labels, max_height, average)
+}
+
+/// The heights `draw_line_graph` gives each of `records`.
+fn plotted_values(records: &[DailyRecord], smooth: Option<u64>) -> Vec<u64> {
+    let values: Vec<u64> = records.iter().map(|r| r.count).collect();
+    match smooth {
+        Some(window) => moving_average(&values, window as usize),
+        None => values,
+    }
+}
+
+/// The daily graph for `records`, as a running total with `--cumulative`.
+fn draw_daily_graph(
+    out: &mut String,
+    records: &[DailyRecord],
+    data: &KeystrokeData,
+    args: &StatsArgs,
+) -> fmt::Result {
+    let height = args.height.unwrap_or(10).clamp(3, 40);
+    if args.cumulative {
+        // The daily average means nothing against a running total.
+        draw_line_graph(out, &cumulative_records(records), height, 0.0, args.smooth)
+    } else {
+        draw_line_graph(out, records, height, data.average_daily(), args.smooth)
+    }
+}
+
+/// The points `draw_daily_graph` plots for `records`, oldest first.
+fn graph_series(records: &[DailyRecord], args: &StatsArgs) -> Vec<DailyCount> {
+    let cumulative;
+    let records = if args.cumulative {
+        cumulative = cumulative_records(records);
+        &cumulative
+    } else {
+        records
+    };
+    records
+        .iter()
+        .zip(plotted_values(records, args.smooth))
+        .map(|(record, count)| DailyCount {
+            date: format_iso_day(record.day()),
+            count,
+        })
+        .collect()
+}
+
+fn cmd_graph(args: &GraphArgs) {
+    if let (Some(from), Some(to)) = (args.from, args.to)
+        && from > to
+    {
+        fail("Invalid range: --from must not be after --to");
+    }
+
+    let data = load_data().or_exit("Could not load statistics");
+    let stats_args = args.stats_args();
+    let mut records = listed_days(&data, &stats_args);
+    records.reverse();
+    if args.json {
+        let series = graph_series(&records, &stats_args);
+        println!("{}", serde_json::to_string_pretty(&series).unwrap());
+        return;
+    }
+
+    let mut out = String::from("\n");
+    if records.is_empty() {
+        out.push_str("     No days to graph yet.\n");
+    } else {
+        draw_daily_graph(&mut out, &records, &data, &stats_args)
+            .expect("Writing to a String cannot fail");
+    }
+    println!("{}", out);
+}
+
+/// How many days the default daily view shows: `--days`, limited to the
+/// days actually recorded.
+fn shown_days(data: &KeystrokeData, args: &StatsArgs) -> usize {
+    args.days
+        .unwrap_or(7)
+        .clamp(1, data.daily_records.len().max(1))
+}
+
+fn draw_hourly_graph(
+    out: &mut String,
+    hourly_counts: &[u64; 24],
+    max_height: usize,
+) -> fmt::Result {
+    let labels: Vec<String> = (0..24).map(|hour| format!("{:02}", hour)).collect();
+    draw_bar_graph(out, hourly_counts, &labels, max_height, None)
+}
+
+fn draw_weekday_graph(
+    out: &mut String,
+    weekday_counts: &[u64; 7],
+    max_height: usize,
+) -> fmt::Result {
+    let labels: Vec<String> = WEEKDAY_NAMES.iter().map(|day| day.to_string()).collect();
+    draw_bar_graph(out, weekday_counts, &labels, max_height, None)
+}
+
+/// Shade for one heatmap cell, from `·` for none up to `█` for the busiest.
+fn heat_shade(count: u64, max_count: u64) -> char {
+    let shades = charset().shades;
+    if count == 0 {
+        return shades[0];
+    }
+    shades[bar_height(count, max_count, shades.len() - 1)]
+}
+
+/// A weekday × hour grid, two columns per hour.
+fn draw_heatmap(out: &mut String, counts: &[[u64; 24]; 7]) -> fmt::Result {
+    let max_count = counts.iter().flatten().copied().max().unwrap_or(0);
+
+    write!(out, "         ")?;
+    for hour in (0..24).step_by(3) {
+        write!(out, "{}", format!("{:<6}", hour).muted())?;
+    }
+    writeln!(out)?;
+    for (name, hours) in WEEKDAY_NAMES.iter().zip(counts) {
+        write!(out, "     {} ", name.muted())?;
+        for &count in hours {
+            let cell = heat_shade(count, max_count).to_string().repeat(2);
+            if count == 0 {
+                write!(out, "{}", cell.faint())?;
+            } else {
+                write!(out, "{}", cell.good())?;
+            }
+        }
+        writeln!(out)?;
+    }
+    writeln!(
+        out,
+        "\n         {} {} {}\n",
+        "less".dimmed(),
+        charset().shades[1..].iter().collect::<String>().good(),
+        format!("more (busiest hour: {})", max_count).dimmed()
+    )
+}
+
+/// The heatmap as CSV: one row per weekday, one column per hour.
+fn render_heatmap_csv(counts: &[[u64; 24]; 7]) -> String {
+    let mut csv = String::from("weekday");
+    for hour in 0..24 {
+        let _ = write!(csv, ",{}", hour);
+    }
+    csv.push('\n');
+    for (name, hours) in WEEKDAY_NAMES.iter().zip(counts) {
+        csv.push_str(name);
+        for count in hours {
+            let _ = write!(csv, ",{}", count);
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Draws one labeled horizontal bar per row, scaled to the largest.
+fn draw_horizontal_bars(out: &mut String, rows: &[(String, u64)], max_width: usize) -> fmt::Result {
+    let counts: Vec<u64> = rows.iter().map(|(_, count)| *count).collect();
+    let max_count = counts.iter().copied().max().unwrap_or(0);
+    let label_width = rows.iter().map(|(label, _)| label.chars().count()).max();
+
+    for ((label, count), tenths) in rows.iter().zip(percent_tenths(&counts)) {
+        let count = *count;
+        let width = bar_height(count, max_count, max_width);
+        writeln!(
+            out,
+            "     {} {}{} {} ({}.{}%)",
+            format!("{:<width$}", label, width = label_width.unwrap_or(0)).dimmed(),
+            charset().filled.repeat(width).good(),
+            charset().empty.repeat(max_width - width).faint(),
+            group_thousands(count).accent(),
+            tenths / 10,
+            tenths % 10
+        )?;
+    }
+    Ok(())
+}
+
+/// `4210` as `4,210`.
+fn group_thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Rows filled for `count`, rounded up so any non-zero count shows a bar.
+fn bar_height(count: u64, max_count: u64, max_height: usize) -> usize {
+    if max_count == 0 {
+        return 0;
+    }
+    (count as u128 * max_height as u128).div_ceil(max_count as u128) as usize
+}
+
+/// Draws one vertical bar per value, each column as wide as its label. A
+/// `reference` value within the chart is marked with a dashed line.
+fn draw_bar_graph(
+    out: &mut String,
+    values: &[u64],
+    labels: &[String],
+    max_height: usize,
+    reference: Option<u64>,
+) -> fmt::Result {
+    if values.is_empty() {
+        return Ok(());
+    }
+
+    let width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(1);
+    let max_count = values.iter().copied().max().unwrap_or(0);
+    // Only a unique peak is highlighted; flat data has no standout bar.
+    let peak = values
+        .iter()
+        .position(|&count| count == max_count)
+        .filter(|_| max_count > 0 && values.iter().filter(|&&c| c == max_count).count() == 1);
+
+    // Draw Y-axis label
+    writeln!(out, "     {}", max_count.to_string().frame())?;
+
+    let reference_row = reference
+        .filter(|&value| value > 0 && value <= max_count)
+        .map(|value| bar_height(value, max_count, max_height) - 1);
+
+    // With nothing to plot, the axis alone is the baseline.
+    let rows = if max_count == 0 { 0 } else { max_height };
+    for row in (0..rows).rev() {
+        write!(out, "     ")?;
+        let on_reference = reference_row == Some(row);
+
+        for (i, &count) in values.iter().enumerate() {
+            if bar_height(count, max_count, max_height) > row {
+                let bar = if peak == Some(i) {
+                    charset().filled.repeat(width).accent().bold()
+                } else {
+                    charset().filled.repeat(width).good()
+                };
+                write!(out, "{}", bar)?;
+            } else if on_reference {
+                write!(out, "{}", charset().dashed.repeat(width).warn())?;
+            } else {
+                write!(out, "{}", charset().empty.repeat(width).faint())?;
+            }
+            if i < values.len() - 1 {
+                let gap = if on_reference {
+                    charset().dashed.warn()
+                } else {
+                    " ".normal()
+                };
+                write!(out, "{}", gap)?;
+            }
+        }
+        if on_reference && let Some(value) = reference {
+            write!(out, " {}", format!("avg {}", value).warn())?;
+        }
+        writeln!(out)?;
+    }
+
+    // Draw X-axis
+    write!(out, "     ")?;
+    for i in 0..values.len() {
+        write!(out, "{}", charset().horizontal.repeat(width).frame())?;
+        if i < values.len() - 1 {
+            write!(out, " ")?;
+        }
+    }
+    writeln!(out)?;
+
+    // Draw labels
+    write!(out, "     ")?;
+    for label in labels {
+        write!(
+            out,
+            "{} ",
+            format!("{:>width$}", label, width = width).muted()
+        )?;
+    }
+    writeln!(out, "\n")
+}
+
+fn cmd_stats(args: &StatsArgs) {
+    if let (Some(from), Some(to)) = (args.from, args.to)
+        && from > to
+    {
+        fail("Invalid range: --from must not be after --to");
+    }
+
+    let data = load_data().or_exit("Could not load statistics");
+    if args.json {
+        println!("{}", render_stats_json(&data, args));
+        return;
+    }
+    if args.plain {
+        print!("{}", render_stats_plain(&data, args));
+        return;
+    }
+    if args.heatmap && args.format == HeatmapFormat::Csv {
+        print!("{}", render_heatmap_csv(&data.weekday_hour_counts));
+        return;
+    }
+
+    let mut out = String::new();
+    render_stats(&mut out, &data, args).expect("Writing to a String cannot fail");
+    print!("{}", out);
+}
+
+/// Statistics emitted by `stats --json`.
+#[derive(Serialize, Deserialize)]
+struct StatsReport {
+    total: u64,
+    today: u64,
+    weekly: u64,
+    monthly: u64,
+    streak: u32,
+    /// `"all"` when auto-repeats are counted, `"distinct"` otherwise.
+    counting: String,
+    raw_total: u64,
+    distinct_total: u64,
+    daily: Vec<DailyCount>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DailyCount {
+    date: String,
+    count: u64,
+}
+
+/// The days `--json` and `--plain` list, newest first: the `--from`/`--to`
+/// range if one was given, otherwise the last `--days`.
+fn listed_days(data: &KeystrokeData, args: &StatsArgs) -> Vec<DailyRecord> {
+    if args.from.is_some() || args.to.is_some() {
+        let mut records = data.get_range_stats(args.from.unwrap_or(0), args.to.unwrap_or(u64::MAX));
+        records.reverse();
+        records
+    } else {
+        data.get_daily_stats(shown_days(data, args))
+    }
+}
+
+fn render_stats_json(data: &KeystrokeData, args: &StatsArgs) -> String {
+    let daily = listed_days(data, args);
+
+    let report = StatsReport {
+        total: data.total_count,
+        today: data.get_today_stats(),
+        weekly: data.get_weekly_stats(),
+        monthly: data.get_monthly_stats(),
+        streak: data.current_streak(),
+        counting: counting_mode().to_string(),
+        raw_total: data.raw_total,
+        distinct_total: data.distinct_total,
+        daily: daily
+            .iter()
+            .map(|record| DailyCount {
+                date: format_iso_day(record.day()),
+                count: record.count,
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&report).expect("Failed to serialize stats")
+}
+
+/// `stats --plain`: ISO dates and right-aligned counts, nothing else.
+fn render_stats_plain(data: &KeystrokeData, args: &StatsArgs) -> String {
+    let records = listed_days(data, args);
+    let width = records
+        .iter()
+        .map(|r| r.count.to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("COUNT".len());
+    let mut out = format!("{:<10}  {:>width$}\n", "DATE", "COUNT");
+    for record in &records {
+        let _ = writeln!(
+            out,
+            "{}  {:>width$}",
+            format_iso_day(record.day()),
+            record.count
+        );
+    }
+    out
+}
+
+/// Which presses the totals include, per `count_repeats`.
+fn counting_mode() -> &'static str {
+    if config().count_repeats {
+        "all"
+    } else {
+        "distinct"
+    }
+}
+
+/// Describes a `--from`/`--to` range for headings.
+fn range_label(from: Option<u64>, to: Option<u64>) -> String {
+    match (from, to) {
+        (Some(from), Some(to)) => format!(
+            "{} {} {}",
+            format_day(from),
+            charset().range,
+            format_day(to)
+        ),
+        (Some(from), None) => format!("Since {}", format_day(from)),
+        (None, Some(to)) => format!("Until {}", format_day(to)),
+        (None, None) => "All time".to_string(),
+    }
+}
+
+fn render_stats(out: &mut String, data: &KeystrokeData, args: &StatsArgs) -> fmt::Result {
+    let from = args.from.unwrap_or(0);
+    let to = args.to.unwrap_or(u64::MAX);
+
+    let [top, title, bottom] = charset().boxed("      Keystroke Statistics          ");
+    writeln!(out, "\n{}", top.frame())?;
+    writeln!(out, "{}", title.accent().bold())?;
+    writeln!(out, "{}", bottom.frame())?;
+
+    writeln!(
+        out,
+        "\n     {} {}",
+        "Total:".dimmed(),
+        data.total_count.to_string().accent().bold()
+    )?;
+    let mode = if config().count_repeats {
+        "all presses, including auto-repeat"
+    } else {
+        "distinct presses, auto-repeat excluded"
+    };
+    writeln!(
+        out,
+        "     {} {} {}",
+        "Counting:".dimmed(),
+        mode,
+        format!("(raw {}, distinct {})", data.raw_total, data.distinct_total).muted()
+    )?;
+    let today_record = data.get_today_record();
+    let (active_today, typing_today) =
+        today_record.map_or((0, 0), |r| (r.active_seconds, r.typing_seconds));
+    writeln!(
+        out,
+        "     {} {}",
+        "Active typing time:".dimmed(),
+        format_duration(active_today).good()
+    )?;
+    writeln!(
+        out,
+        "     {} {}",
+        "Est. typing time:".dimmed(),
+        format_duration(typing_today).good()
+    )?;
+    if let Some(record) = today_record.filter(|r| r.sessions > 0) {
+        writeln!(out, "     {}", describe_sessions(record))?;
+    }
+    let average = data.average_daily();
+    if average > 0.0 {
+        let today = data.get_today_stats();
+        let relative = if today as f64 >= average {
+            "above average".good()
+        } else {
+            "below average".warn()
+        };
+        writeln!(
+            out,
+            "     {} {}  (today {}, {})",
+            "Daily average:".dimmed(),
+            format!("{:.0}", average).accent(),
+            today,
+            relative
+        )?;
+    }
+    if data.peak_kpm > 0 {
+        writeln!(
+            out,
+            "     {} {} keystrokes/min on {}",
+            "Peak:".dimmed(),
+            data.peak_kpm.to_string().accent().bold(),
+            format_day(local_day(data.peak_timestamp))
+        )?;
+    }
+
+    let ranged = args.from.is_some() || args.to.is_some();
+    if ranged {
+        let range_stats = data.get_range_stats(from, to);
+        let label = range_label(args.from, args.to);
+        writeln!(out, "\n     {}", label.heading().bold())?;
+        writeln!(out, "     {}\n", charset().horizontal.repeat(28).frame())?;
+        writeln!(
+            out,
+            "     {} {}\n",
+            "Keystrokes:".dimmed(),
+            range_stats
+                .iter()
+                .map(|r| r.count)
+                .sum::<u64>()
+                .to_string()
+                .accent()
+                .bold()
+        )?;
+
+        if !range_stats.is_empty() {
+            draw_daily_graph(out, &range_stats, data, args)?;
+        }
+
+        for record in range_stats.iter().rev() {
+            writeln!(
+                out,
+                "     {} {} {}",
+                format_day(record.day()).muted(),
+                charset().vertical,
+                record.count.to_string().good()
+            )?;
+        }
+    }
+
+    if args.daily
+        || !(args.weekly
+            || args.monthly
+            || args.yearly
+            || args.hourly
+            || args.work_hours.is_some()
+            || args.weekday
+            || args.compare
+            || args.categories
+            || args.distribution
+            || args.heatmap
+            || args.achievements
+            || args.top.is_some()
+            || ranged)
+    {
+        let days = shown_days(data, args);
+        writeln!(
+            out,
+            "\n     {}",
+            format!("Daily Activity (Last {} Days)", days)
+                .heading()
+                .bold()
+        )?;
+        writeln!(out, "     {}\n", charset().horizontal.repeat(28).frame())?;
+
+        let daily_stats = data.get_daily_stats(days);
+
+        // Reverse for chronological order in graph
+        let mut graph_data = daily_stats.clone();
+        graph_data.reverse();
+
+        if !graph_data.is_empty() {
+            draw_daily_graph(out, &graph_data, data, args)?;
+        }
+
+        for record in &daily_stats {
+            let formatted_date = format_day(record.day());
+            let (first, last) = record.seen_range();
+            writeln!(
+                out,
+                "     {} {} {} {}",
+                formatted_date.muted(),
+                charset().vertical,
+                format!("{:<8}", record.count).good(),
+                format!(
+                    "{} {} {}",
+                    format_clock(first),
+                    charset().range,
+                    format_clock(last)
+                )
+                .dimmed()
+            )?;
+        }
+    }
+
+    if args.hourly {
+        writeln!(
+            out,
+            "\n     {}",
+            "Hourly Activity (Hour of Day)".heading().bold()
+        )?;
+        writeln!(out, "     {}\n", charset().horizontal.repeat(28).frame())?;
+        draw_hourly_graph(out, &data.hourly_counts, 10)?;
+    }
+
+    if let Some(range) = args.work_hours {
+        writeln!(
+            out,
+            "\n     {}",
+            format!("Work Hours ({})", range.label(charset().range))
+                .heading()
+                .bold()
+        )?;
+        writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+        let (within, tracked) = work_hour_totals(&data.hourly_counts, range);
+        if tracked == 0 {
+            writeln!(
+                out,
+                "     {}\n",
+                "No hourly data yet; records from before hourly tracking don't have any".dimmed()
+            )?;
+        } else {
+            writeln!(
+                out,
+                "     {} {} ({:.1}% of all activity)",
+                "Keystrokes:".dimmed(),
+                within.to_string().accent().bold(),
+                within as f64 * 100.0 / tracked as f64
+            )?;
+            if tracked < data.total_count {
+                writeln!(
+                    out,
+                    "     {}",
+                    format!(
+                        "Covers the {} keystrokes with an hour recorded; older records have none",
+                        tracked
+                    )
+                    .dimmed()
+                )?;
+            }
+            writeln!(out)?;
+        }
+    }
+
+    if args.weekday {
+        writeln!(out, "\n     {}", "Activity by Weekday".heading().bold())?;
+        writeln!(out, "     {}\n", charset().horizontal.repeat(28).frame())?;
+        draw_weekday_graph(out, &data.get_weekday_stats(), 10)?;
+    }
+
+    if args.heatmap {
+        writeln!(
+            out,
+            "\n     {}",
+            format!("Activity Heatmap (Weekday {} Hour)", charset().times)
+                .heading()
+                .bold()
+        )?;
+        writeln!(out, "     {}\n", charset().horizontal.repeat(28).frame())?;
+        draw_heatmap(out, &data.weekday_hour_counts)?;
+    }
+
+    if args.weekly {
+        let weekly_count = data.get_weekly_stats();
+        writeln!(out, "\n     {}", "Weekly Summary (7 days)".heading().bold())?;
+        writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+        writeln!(
+            out,
+            "     {} keystrokes\n",
+            weekly_count.to_string().accent().bold()
+        )?;
+    }
+
+    if args.monthly {
+        let monthly_count = data.get_monthly_stats();
+        writeln!(
+            out,
+            "\n     {}",
+            "Monthly Summary (30 days)".heading().bold()
+        )?;
+        writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+        writeln!(
+            out,
+            "     {} keystrokes\n",
+            monthly_count.to_string().accent().bold()
+        )?;
+    }
+
+    if let Some(n) = args.top {
+        let top_days = data.get_top_days(n);
+        writeln!(
+            out,
+            "\n     {}",
+            format!("Top {} Days", top_days.len()).heading().bold()
+        )?;
+        writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+
+        for (rank, record) in top_days.iter().enumerate() {
+            let position = format!("{:>2}.", rank + 1);
+            let date = format_day(record.day());
+            // The single busiest day stands out from the rest.
+            if rank == 0 {
+                writeln!(
+                    out,
+                    "     {} {} {} {}",
+                    position.highlight().bold(),
+                    date.heading().bold(),
+                    charset().vertical,
+                    record.count.to_string().accent().bold()
+                )?;
+            } else {
+                writeln!(
+                    out,
+                    "     {} {} {} {}",
+                    position.dimmed(),
+                    date.muted(),
+                    charset().vertical,
+                    record.count.to_string().good()
+                )?;
+            }
+        }
+    }
+
+    if args.yearly {
+        writeln!(
+            out,
+            "\n     {}",
+            "Yearly Summary (365 days)".heading().bold()
+        )?;
+        writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+        writeln!(
+            out,
+            "     {} keystrokes\n",
+            data.get_yearly_stats().to_string().accent().bold()
+        )?;
+
+        let months: std::collections::HashMap<String, u64> =
+            data.get_months_stats().into_iter().collect();
+        let (values, labels): (Vec<u64>, Vec<String>) = last_twelve_months(current_day())
+            .into_iter()
+            .map(|(year, month)| {
+                let count = months.get(&year_month_key(year, month)).copied();
+                (
+                    count.unwrap_or(0),
+                    MONTH_NAMES[month as usize - 1].to_string(),
+                )
+            })
+            .unzip();
+        draw_bar_graph(out, &values, &labels, 10, None)?;
+    }
+
+    if args.categories {
+        writeln!(out, "\n     {}", "Key Categories".heading().bold())?;
+        writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+        let rows: Vec<(String, u64)> = data
+            .get_category_stats()
+            .into_iter()
+            .map(|(category, count)| (category.name().to_string(), count))
+            .collect();
+        draw_horizontal_bars(out, &rows, 30)?;
+
+        let apps = data.get_app_stats();
+        if !apps.is_empty() {
+            writeln!(out, "\n     {}", "Counted In Apps".heading().bold())?;
+            writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+            draw_horizontal_bars(out, &apps, 30)?;
+        }
+    }
+
+    if args.achievements {
+        writeln!(out, "\n     {}", "Achievements".heading().bold())?;
+        writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+        if data.achievements.is_empty() {
+            writeln!(
+                out,
+                "     {}",
+                "None yet, the first comes at 1000 keystrokes".muted()
+            )?;
+        }
+        for achievement in &data.achievements {
+            writeln!(
+                out,
+                "     {} {:>10} keystrokes  {}",
+                charset().trophy,
+                achievement.total.to_string().accent().bold(),
+                format_day(local_day(achievement.timestamp)).muted()
+            )?;
+        }
+    }
+
+    if args.distribution {
+        writeln!(out, "\n     {}", "Days by Keystroke Count".heading().bold())?;
+        writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+        draw_horizontal_bars(out, &data.get_distribution(charset().range), 30)?;
+    }
+
+    if args.compare {
+        writeln!(out, "\n     {}", "Trend".heading().bold())?;
+        writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+        write_comparison(
+            out,
+            "This week",
+            "last week",
+            data.get_weekly_stats(),
+            data.get_previous_week_stats(),
+        )?;
+        write_comparison(
+            out,
+            "This month",
+            "last month",
+            data.get_monthly_stats(),
+            data.get_previous_month_stats(),
+        )?;
+    }
+
+    if let Some(goal) = config().daily_goal {
+        writeln!(out, "\n     {}", "Daily Goal".heading().bold())?;
+        writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+        write_goal_progress(out, data.get_today_stats(), goal)?;
+    }
+
+    writeln!(out, "\n     {}", "Streaks".heading().bold())?;
+    writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+    writeln!(
+        out,
+        "     {} {} days",
+        "Current:".dimmed(),
+        data.current_streak().to_string().accent().bold()
+    )?;
+    writeln!(
+        out,
+        "     {} {} days",
+        "Longest:".dimmed(),
+        data.longest_streak().to_string().good()
+    )?;
+
+    let today_clicks = data.get_today_record().map_or(0, |r| r.clicks);
+    writeln!(out, "\n     {}", "Mouse Activity".heading().bold())?;
+    writeln!(out, "     {}", charset().horizontal.repeat(28).frame())?;
+    writeln!(
+        out,
+        "     {} {}",
+        "Total clicks:".dimmed(),
+        data.total_clicks.to_string().accent().bold()
+    )?;
+    writeln!(
+        out,
+        "     {} {}",
+        "Today:".dimmed(),
+        today_clicks.to_string().good()
+    )?;
+
+    writeln!(out)?;
+    Ok(())
+}
+
+/// "4 sessions today, longest break 52m".
+fn describe_sessions(record: &DailyRecord) -> String {
+    let plural = if record.sessions == 1 { "" } else { "s" };
+    let mut line = format!(
+        "{} session{} today",
+        record.sessions.to_string().accent(),
+        plural
+    );
+    if record.longest_gap > 0 {
+        let _ = write!(
+            line,
+            ", longest break {}",
+            format_duration(record.longest_gap).accent()
+        );
+    }
+    line
+}
+
+/// Formats seconds as `3h 12m`, or just minutes under an hour.
+fn format_duration(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    if minutes >= 60 {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Provenance written at the top of every export.
+struct ExportHeader {
+    /// Local time the export was made, as `YYYY-MM-DD HH:MM:SS`.
+    generated: String,
+    /// First and last day with records, or "No records".
+    period: String,
+}
+
+impl ExportHeader {
+    fn new(data: &KeystrokeData, timestamp: u64, offset_seconds: i64) -> Self {
+        let days = data.daily_records.iter().map(DailyRecord::day);
+        let period = match (days.clone().min(), days.max()) {
+            (Some(first), Some(last)) => range_label(Some(first), Some(last)),
+            _ => "No records".to_string(),
+        };
+        ExportHeader {
+            generated: format_log_time(timestamp, offset_seconds),
+            period,
+        }
+    }
+}
+
+/// `1 keystroke`, `2 keystrokes`.
+fn keystroke_count(count: u64) -> String {
+    format!("{} keystroke{}", count, if count == 1 { "" } else { "s" })
+}
+
+fn render_export_text(data: &KeystrokeData, header: &ExportHeader, range: Option<&str>) -> String {
+    let mut content = String::new();
+    for line in charset().boxed("   Keystr Counter Statistics        ") {
+        content.push_str(&line);
+        content.push('\n');
+    }
+    content.push('\n');
+    content.push_str(&format!("Generated: {}\n", header.generated));
+    content.push_str(&format!("Period: {}\n\n", header.period));
+    if let Some(range) = range {
+        let in_range: u64 = data.daily_records.iter().map(|r| r.count).sum();
+        content.push_str(&format!(
+            "Range: {} ({})\n",
+            range,
+            keystroke_count(in_range)
+        ));
+    }
+    content.push_str(&format!("Total Keystrokes: {}\n\n", data.total_count));
+
+    let rule = charset().horizontal.repeat(36);
+    content.push_str("Daily Records:\n");
+    content.push_str(&rule);
+    content.push('\n');
+    for record in data.daily_records.iter().rev() {
+        let formatted_date = format_day(record.day());
+        content.push_str(&format!(
+            "{}: {} keystrokes\n",
+            formatted_date, record.count
+        ));
+    }
+
+    for (title, rows) in [
+        ("Weekly Breakdown", weekly_breakdown(data)),
+        ("Monthly Breakdown", data.get_months_stats()),
+    ] {
+        content.push_str(&format!("\n{}:\n{}\n", title, rule));
+        for (period, count) in rows.iter().rev() {
+            content.push_str(&format!("{}: {} keystrokes\n", period, count));
+        }
+    }
+
+    content.push_str("\nWeekly Summary (7 days):  ");
+    content.push_str(&format!("{} keystrokes\n", data.get_weekly_stats()));
+
+    content.push_str("Monthly Summary (30 days): ");
+    content.push_str(&format!("{} keystrokes\n", data.get_monthly_stats()));
+    content
+}
+
+fn render_export_csv(data: &KeystrokeData, header: &ExportHeader, range: Option<&str>) -> String {
+    let mut records = data.daily_records.clone();
+    records.sort_by_key(|r| r.day());
+
+    // Comment lines keep the rows themselves plain CSV.
+    let mut content = format!(
+        "# Generated: {}\n# Period: {}\n",
+        header.generated, header.period
+    );
+    if let Some(range) = range {
+        let in_range: u64 = records.iter().map(|r| r.count).sum();
+        content.push_str(&format!(
+            "# Range: {} ({})\n",
+            range,
+            keystroke_count(in_range)
+        ));
+    }
+    content.push_str("date,timestamp,count\n");
+    for record in &records {
+        content.push_str(&format!(
+            "{},{},{}\n",
+            format_day(record.day()),
+            record.timestamp,
+            record.count
+        ));
+    }
+    for (title, column, rows) in [
+        ("Weekly breakdown", "week", weekly_breakdown(data)),
+        ("Monthly breakdown", "month", data.get_months_stats()),
+    ] {
+        content.push_str(&format!("# {}\n{},count\n", title, column));
+        for (period, count) in rows {
+            content.push_str(&format!("{},{}\n", period, count));
+        }
+    }
+    content
+}
+
+fn render_export_markdown(
+    data: &KeystrokeData,
+    header: &ExportHeader,
+    range: Option<&str>,
+) -> String {
+    let mut records = data.daily_records.clone();
+    records.sort_by_key(|r| r.day());
+
+    let mut content = String::from("# Keystroke Report\n\n");
+    content.push_str(&format!(
+        "**Generated:** {}  \n**Period:** {}\n\n",
+        header.generated, header.period
+    ));
+    if let Some(range) = range {
+        let in_range: u64 = records.iter().map(|r| r.count).sum();
+        content.push_str(&format!(
+            "**Range:** {} ({})\n\n",
+            range,
+            keystroke_count(in_range)
+        ));
+    }
+    content.push_str(&format!("**Total keystrokes:** {}\n\n", data.total_count));
+
+    content.push_str("| Date | Keystrokes |\n");
+    content.push_str("| --- | ---: |\n");
+    for record in &records {
+        content.push_str(&format!(
+            "| {} | {} |\n",
+            format_day(record.day()),
+            record.count
+        ));
+    }
+
+    for (title, column, rows) in [
+        ("Weekly Breakdown", "Week", weekly_breakdown(data)),
+        ("Monthly Breakdown", "Month", data.get_months_stats()),
+    ] {
+        content.push_str(&format!(
+            "\n## {}\n\n| {} | Keystrokes |\n| --- | ---: |\n",
+            title, column
+        ));
+        for (period, count) in rows {
+            content.push_str(&format!("| {} | {} |\n", period, count));
+        }
+    }
+
+    content.push_str(&format!(
+        "\n- **Weekly (7 days):** {} keystrokes\n",
+        data.get_weekly_stats()
+    ));
+    content.push_str(&format!(
+        "- **Monthly (30 days):** {} keystrokes\n",
+        data.get_monthly_stats()
+    ));
+    content
+}
+
+/// An HTML page that opens offline: styles and the chart are inline, with
+/// no scripts or external assets.
+fn render_export_html(data: &KeystrokeData, header: &ExportHeader, range: Option<&str>) -> String {
+    let mut records = data.daily_records.clone();
+    records.sort_by_key(|r| r.day());
+
+    let mut content = String::from(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Keystroke Report</title>
+<style>
+body { font-family: system-ui, sans-serif; max-width: 760px; margin: 2rem auto; padding: 0 1rem; color: #222; }
+p.meta { color: #666; }
+table { border-collapse: collapse; margin: 1rem 0; }
+th { text-align: left; font-weight: normal; color: #666; padding: 0.2rem 1.5rem 0.2rem 0; }
+td { text-align: right; font-variant-numeric: tabular-nums; }
+svg { width: 100%; height: auto; }
+svg text { font-size: 11px; fill: #666; }
+</style>
+</head>
+<body>
+<h1>Keystroke Report</h1>
+"#,
+    );
+    content.push_str(&format!(
+        "<p class=\"meta\">Generated: {}<br>Period: {}</p>\n",
+        header.generated, header.period
+    ));
+
+    let mut rows = Vec::new();
+    if let Some(range) = range {
+        let in_range: u64 = records.iter().map(|r| r.count).sum();
+        rows.push((format!("Range {}", range), in_range));
+    }
+    rows.push(("Total keystrokes".to_string(), data.total_count));
+    rows.push(("Weekly (7 days)".to_string(), data.get_weekly_stats()));
+    rows.push(("Monthly (30 days)".to_string(), data.get_monthly_stats()));
+    rows.push((
+        "Daily average".to_string(),
+        data.average_daily().round() as u64,
+    ));
+    content.push_str("<table>\n");
+    for (label, value) in rows {
+        content.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>\n",
+            label,
+            group_thousands(value)
+        ));
+    }
+    content.push_str("</table>\n");
+
+    content.push_str(&render_svg_chart(&records));
+    content.push_str("</body>\n</html>\n");
+    content
+}
+
+/// Daily counts as bars scaled to the busiest day, oldest on the left, with
+/// the count axis marked at zero, half and the peak.
+fn render_svg_chart(records: &[DailyRecord]) -> String {
+    const WIDTH: f64 = 720.0;
+    const HEIGHT: f64 = 260.0;
+    const LEFT: f64 = 64.0;
+    const TOP: f64 = 16.0;
+    const PLOT_WIDTH: f64 = WIDTH - LEFT - 16.0;
+    const PLOT_HEIGHT: f64 = HEIGHT - TOP - 48.0;
+    const BASELINE: f64 = TOP + PLOT_HEIGHT;
+
+    let mut svg = format!(
+        "<svg viewBox=\"0 0 {WIDTH} {HEIGHT}\" xmlns=\"http://www.w3.org/2000/svg\" role=\"img\" aria-label=\"Keystrokes per day\">\n"
+    );
+    let max = records.iter().map(|r| r.count).max().unwrap_or(0).max(1);
+    let slot = PLOT_WIDTH / records.len().max(1) as f64;
+    for (i, record) in records.iter().enumerate() {
+        let height = record.count as f64 / max as f64 * PLOT_HEIGHT;
+        svg.push_str(&format!(
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#2aa198\"><title>{}: {}</title></rect>\n",
+            LEFT + i as f64 * slot + slot * 0.1,
+            BASELINE - height,
+            slot * 0.8,
+            height,
+            format_day(record.day()),
+            group_thousands(record.count)
+        ));
+    }
+
+    svg.push_str(&format!(
+        "<path d=\"M{LEFT} {TOP}V{BASELINE}H{}\" stroke=\"#999\" fill=\"none\"/>\n",
+        LEFT + PLOT_WIDTH
+    ));
+    for value in [0, max / 2, max] {
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\" dominant-baseline=\"middle\">{}</text>\n",
+            LEFT - 6.0,
+            BASELINE - value as f64 / max as f64 * PLOT_HEIGHT,
+            group_thousands(value)
+        ));
+    }
+    match (records.first(), records.last()) {
+        (Some(first), Some(last)) => {
+            svg.push_str(&format!(
+                "<text x=\"{LEFT}\" y=\"{}\">{}</text>\n",
+                BASELINE + 16.0,
+                format_day(first.day())
+            ));
+            if records.len() > 1 {
+                svg.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>\n",
+                    LEFT + PLOT_WIDTH,
+                    BASELINE + 16.0,
+                    format_day(last.day())
+                ));
+            }
+        }
+        _ => svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">No records</text>\n",
+            LEFT + PLOT_WIDTH / 2.0,
+            TOP + PLOT_HEIGHT / 2.0
+        )),
+    }
+    svg.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">Date</text>\n",
+        LEFT + PLOT_WIDTH / 2.0,
+        HEIGHT - 8.0
+    ));
+    svg.push_str(&format!(
+        "<text transform=\"rotate(-90)\" x=\"{}\" y=\"14\" text-anchor=\"middle\">Keystrokes</text>\n",
+        -(TOP + PLOT_HEIGHT / 2.0)
+    ));
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn render_metrics(data: &KeystrokeData) -> String {
+    let metrics = [
+        (
+            "keystr_total_keystrokes",
+            "Keystrokes recorded since keystr was initialized.",
+            "counter",
+            data.total_count,
+        ),
+        (
+            "keystr_today_keystrokes",
+            "Keystrokes recorded today.",
+            "gauge",
+            data.get_today_stats(),
+        ),
+        (
+            "keystr_weekly_keystrokes",
+            "Keystrokes recorded over the last 7 days.",
+            "gauge",
+            data.get_weekly_stats(),
+        ),
+    ];
+
+    let mut content = String::new();
+    for (name, help, kind, value) in metrics {
+        content.push_str(&format!("# HELP {} {}\n", name, help));
+        content.push_str(&format!("# TYPE {} {}\n", name, kind));
+        content.push_str(&format!("{} {}\n", name, value));
+    }
+    content
+}
+
+fn cmd_today() {
+    println!(
+        "{}",
+        load_data()
+            .or_exit("Could not load statistics")
+            .get_today_stats()
+    );
+}
+
+/// The `summary` panel for `today`, kept within 80 columns.
+fn render_summary(out: &mut String, data: &KeystrokeData, today: u64) -> fmt::Result {
+    let last_week = daily_counts(data, today, 7);
+    let peak = match data.get_top_days(1).first() {
+        Some(record) if record.count > 0 => {
+            format!("{} on {}", record.count, format_day(record.day()))
+        }
+        _ => "none yet".to_string(),
+    };
+    let cell = |label: &str, value: String| format!("{:<12}{:>10}", label, value);
+
+    let [top, title, bottom] = charset().boxed("         Keystroke Summary          ");
+    writeln!(out, "\n{}", top.frame())?;
+    writeln!(out, "{}", title.accent().bold())?;
+    writeln!(out, "{}\n", bottom.frame())?;
+
+    let rows = [
+        (
+            cell("Total", data.total_count.to_string()),
+            cell("Streak", format!("{} days", data.current_streak())),
+        ),
+        (
+            cell("Today", data.get_today_stats().to_string()),
+            cell("Average", format!("{:.0}/day", data.average_daily())),
+        ),
+        (
+            cell("This week", data.get_weekly_stats().to_string()),
+            cell("This month", data.get_monthly_stats().to_string()),
+        ),
+    ];
+    for (left, right) in rows {
+        writeln!(out, "     {}    {}", left.accent(), right.accent())?;
+    }
+    writeln!(out, "     {:<12}{}", "Peak day".dimmed(), peak.highlight())?;
+    if let (Some(min), Some(median), Some(max)) =
+        (data.daily_min(), data.daily_median(), data.daily_max())
+    {
+        writeln!(
+            out,
+            "     {:<12}{} / {:.0} / {} {}",
+            "Daily range".dimmed(),
+            min,
+            median,
+            max,
+            "(min / median / max)".muted()
+        )?;
+    }
+    writeln!(
+        out,
+        "     {:<12}{} {}\n",
+        "Last 7 days".dimmed(),
+        sparkline(&last_week, &charset().levels).good(),
+        "(oldest to today)".muted()
+    )
+}
+
+fn cmd_summary() {
+    let data = load_data().or_exit("Could not load statistics");
+    let mut out = String::new();
+    render_summary(&mut out, &data, current_day()).expect("Writing to a String cannot fail");
+    print!("{}", out);
+}
+
+fn cmd_metrics() {
+    print!(
+        "{}",
+        render_metrics(&load_data().or_exit("Could not load statistics"))
+    );
+}
+
+/// Routes one HTTP request line to `(status, content type, body)`.
+fn route_request(request_line: &str) -> (&'static str, &'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return ("400 Bad Request", "text/plain", "bad request\n".to_string());
+    };
+    if method != "GET" {
+        return (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        );
+    }
+
+    // Each request re-reads the data so the daemon's latest save is served.
+    match target.split('?').next().unwrap_or_default() {
+        "/health" => ("200 OK", "text/plain", "ok\n".to_string()),
+        "/stats" => match load_data() {
+            Ok(data) => (
+                "200 OK",
+                "application/json",
+                render_stats_json(&data, &StatsArgs::default()),
+            ),
+            Err(_) => (
+                "500 Internal Server Error",
+                "text/plain",
+                "could not load statistics\n".to_string(),
+            ),
+        },
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    }
+}
+
+fn handle_connection(stream: std::net::TcpStream) -> std::io::Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers; nothing in them changes the response.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let (status, content_type, body) = route_request(&request_line);
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn cmd_serve(bind: std::net::IpAddr, port: u16) {
+    let listener = match std::net::TcpListener::bind((bind, port)) {
+        Ok(listener) => listener,
+        Err(error) => fail(format_args!(
+            "Could not listen on {}:{}: {}",
+            bind, port, error
+        )),
+    };
+
+    ctrlc::set_handler(|| {
+        println!("\n  {} Server stopped\n", charset().check.green().bold());
+        std::process::exit(0);
+    })
+    .or_exit("Error setting signal handler");
+
+    println!(
+        "\n  {} Serving {} and {} on {} (Ctrl-C to stop)\n",
+        charset().arrow.bright_cyan(),
+        "/stats".bright_yellow(),
+        "/health".bright_yellow(),
+        format!("http://{}:{}", bind, port).bright_cyan()
+    );
+
+    for stream in listener.incoming().flatten() {
+        let _ = handle_connection(stream);
+    }
+}
+
+/// The data file's own layout plus an `export` metadata object, which
+/// `import` ignores, so exports stay importable.
+fn render_export_json(data: &KeystrokeData, header: &ExportHeader, range: Option<&str>) -> String {
+    let mut value = serde_json::to_value(data).expect("Failed to serialize data");
+    value["export"] = serde_json::json!({
+        "generated": header.generated,
+        "period": header.period,
+    });
+    if let Some(range) = range {
+        value["export"]["range"] = range.into();
+    }
+    serde_json::to_string_pretty(&value).expect("Failed to serialize data")
+}
+
+/// `marker` is set for `--incremental`: only days typed on after it are
+/// exported, and it moves forward once the file is written.
+fn cmd_export(
+    output: &str,
+    format: ExportFormat,
+    from: Option<u64>,
+    to: Option<u64>,
+    marker: Option<u64>,
+) {
+    if let (Some(from), Some(to)) = (from, to)
+        && from > to
+    {
+        fail("Invalid range: --from must not be after --to");
+    }
+
+    let mut data = load_data().or_exit("Could not load statistics");
+    let ranged = from.is_some() || to.is_some();
+    let range = ranged.then(|| range_label(from, to));
+    if ranged {
+        data.daily_records = data.get_range_stats(from.unwrap_or(0), to.unwrap_or(u64::MAX));
+        if data.daily_records.is_empty() {
+            fail(format_args!(
+                "No records in range {}",
+                range.as_deref().unwrap_or_default()
+            ));
+        }
+    }
+
+    let now = current_timestamp();
+    if let Some(marker) = marker {
+        data.daily_records = data.get_changed_since(marker);
+        if data.daily_records.is_empty() {
+            println!(
+                "\n  {} Nothing new since the last export, {} left as it was\n",
+                charset().info.blue(),
+                output.bright_cyan()
+            );
+            return;
+        }
+    }
+    let header = ExportHeader::new(&data, now, utc_offset_at(now));
+    let content = match format {
+        ExportFormat::Text => render_export_text(&data, &header, range.as_deref()),
+        ExportFormat::Csv => render_export_csv(&data, &header, range.as_deref()),
+        ExportFormat::Json => render_export_json(&data, &header, range.as_deref()),
+        ExportFormat::Markdown => render_export_markdown(&data, &header, range.as_deref()),
+        ExportFormat::Html => render_export_html(&data, &header, range.as_deref()),
+    };
+
+    fs::write(output, content).or_exit("Failed to write export file");
+    if marker.is_some() {
+        fs::write(get_export_marker_file(), now.to_string())
+            .or_exit("Failed to record the export marker");
+        println!(
+            "\n  {} Exported {} day(s) to {}\n",
+            charset().check.green().bold(),
+            data.daily_records.len(),
+            output.bright_cyan()
+        );
+        return;
+    }
+    println!(
+        "\n  {} Exported to {}\n",
+        charset().check.green().bold(),
+        output.bright_cyan()
+    );
+}
+
+fn cmd_share() {
+    let key = load_share_key().or_exit("Could not load the share key");
+    let data = load_data().or_exit("Could not load statistics");
+    println!(
+        "{}",
+        sign_share(&ShareSummary::of(&data, current_day()), &key)
+    );
+}
+
+fn cmd_verify(token: &str) {
+    let key = load_share_key().or_exit("Could not load the share key");
+    let summary = verify_share(token, &key).or_exit("Token not verified");
+    println!(
+        "\n  {} Signed with this share key on {}, unchanged\n",
+        charset().check.green().bold(),
+        summary.date.bright_cyan()
+    );
+    for (label, value) in [
+        ("Total", summary.total.to_string()),
+        ("This week", summary.weekly.to_string()),
+        ("Streak", format!("{} days", summary.streak)),
+    ] {
+        println!("  {:<12}{}", label.dimmed(), value.bright_cyan().bold());
+    }
+    println!();
+}
+
+fn cmd_report(dir: &std::path::Path) {
+    let (first, last) = last_complete_week(current_day());
+    let (year, week) = iso_week(first);
+    let path = dir.join(format!("report-{:04}-W{:02}.txt", year, week));
+    if path.exists() {
+        println!(
+            "\n  {} {} already exists, skipping\n",
+            charset().info.blue(),
+            path.display().to_string().bright_cyan()
+        );
+        return;
+    }
+
+    let mut data = load_data().or_exit("Could not load statistics");
+    data.daily_records = data.get_range_stats(first, last);
+    let now = current_timestamp();
+    let header = ExportHeader::new(&data, now, utc_offset_at(now));
+    let range = range_label(Some(first), Some(last));
+    let content = render_export_text(&data, &header, Some(&range));
+
+    fs::create_dir_all(dir).or_exit("Failed to create report directory");
+    fs::write(&path, content).or_exit("Failed to write report");
+    println!(
+        "\n  {} Wrote {} ({})\n",
+        charset().check.green().bold(),
+        path.display().to_string().bright_cyan(),
+        range
+    );
+}
+
+fn cmd_goal(action: GoalCommand) {
+    let mut config = read_config().or_exit("Invalid config");
+
+    match action {
+        GoalCommand::Set { target } => {
+            config.daily_goal = Some(target);
+            save_config(&config).or_exit("Failed to write config file");
+            println!(
+                "\n  {} Daily goal set to {} keystrokes\n",
+                charset().check.green().bold(),
+                target.to_string().bright_cyan().bold()
+            );
+        }
+        GoalCommand::Clear => {
+            config.daily_goal = None;
+            save_config(&config).or_exit("Failed to write config file");
+            println!(
+                "\n  {} Daily goal cleared\n",
+                charset().check.green().bold()
+            );
+        }
+        GoalCommand::Show => match config.daily_goal {
+            Some(goal) => {
+                let mut out = String::new();
+                write_goal_progress(
+                    &mut out,
+                    load_data()
+                        .or_exit("Could not load statistics")
+                        .get_today_stats(),
+                    goal,
+                )
+                .expect("Writing to a String cannot fail");
+                println!("\n{}", out);
+            }
+            None => println!(
+                "\n  {} No daily goal set. Use {} to add one\n",
+                charset().info.blue(),
+                "keystr goal set <n>".bright_yellow()
+            ),
+        },
+    }
+}
+
+/// Percentage change from `previous` to `current`, or `None` when there is
+/// nothing to compare against.
+fn percent_change(current: u64, previous: u64) -> Option<f64> {
+    if previous == 0 {
+        return None;
+    }
+    Some((current as f64 - previous as f64) / previous as f64 * 100.0)
+}
+
+fn write_comparison(
+    out: &mut String,
+    label: &str,
+    previous_label: &str,
+    current: u64,
+    previous: u64,
+) -> fmt::Result {
+    let change = match percent_change(current, previous) {
+        Some(change) if change > 0.0 => format!("{} {:.1}%", charset().up, change).bright_green(),
+        Some(change) if change < 0.0 => {
+            format!("{} {:.1}%", charset().down, change.abs()).bright_red()
+        }
+        Some(_) => "= 0.0%".dimmed(),
+        None => "no earlier data".dimmed(),
+    };
+
+    writeln!(
+        out,
+        "     {} {} vs {} {}  {}",
+        format!("{}:", label).dimmed(),
+        current.to_string().bright_cyan().bold(),
+        previous,
+        previous_label.dimmed(),
+        change
+    )
+}
+
+fn write_goal_progress(out: &mut String, today: u64, goal: u64) -> fmt::Result {
+    const WIDTH: usize = 20;
+    let ratio = today as f64 / goal.max(1) as f64;
+    let filled = ((ratio.min(1.0) * WIDTH as f64).round() as usize).min(WIDTH);
+
+    writeln!(
+        out,
+        "     {}{} {} / {} {} {}%",
+        charset().filled.repeat(filled).bright_green(),
+        charset().track.repeat(WIDTH - filled).bright_black(),
+        today.to_string().bright_cyan().bold(),
+        goal,
+        charset().dash,
+        (ratio * 100.0).floor() as u64
+    )?;
+    if today >= goal {
+        writeln!(
+            out,
+            "     {} Goal reached - nice work!",
+            charset().star.bright_yellow().bold()
+        )?;
+    }
+    Ok(())
+}
+
+fn cmd_profile(action: ProfileCommand) {
+    match action {
+        ProfileCommand::List => {
+            let active = active_profile();
+            let marker = |selected: bool| {
+                if selected {
+                    charset().active.green().bold()
+                } else {
+                    charset().inactive.dimmed()
+                }
+            };
+
+            println!("\n  {} default", marker(active.is_none()));
+            for name in list_profiles(&get_profiles_dir()) {
+                let selected = active == Some(name.as_str());
+                println!("  {} {}", marker(selected), name);
+            }
+            println!();
+        }
+    }
+}
+
+fn cmd_config(action: ConfigCommand) {
+    match action {
+        ConfigCommand::Path => println!("{}", get_config_file().display()),
+    }
+}
+
+fn cmd_where() {
+    let paths = [
+        ("Config dir:", get_config_dir()),
+        ("Config file:", get_config_file()),
+        ("Data file:", storage().path()),
+        ("PID file:", get_pid_file()),
+        ("Log file:", get_log_file()),
+    ];
+
+    println!();
+    for (label, path) in paths {
+        let path = std::path::absolute(&path).unwrap_or(path);
+        println!(
+            "  {} {}",
+            format!("{:<12}", label).dimmed(),
+            path.display().to_string().bright_cyan()
+        );
+    }
+    println!();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// One line of the `keystr doctor` checklist.
+struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+    /// What to do about a warning or failure.
+    fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(
+        name: &'static str,
+        status: CheckStatus,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Check {
+            name,
+            status,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+fn check_config_dir(dir: &std::path::Path) -> Check {
+    const NAME: &str = "Config directory";
+    if !dir.exists() {
+        return Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} doesn't exist", dir.display()),
+            "run `keystr init` to create it",
+        );
+    }
+    if !dir.is_dir() {
+        return Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} is not a directory", dir.display()),
+            "move the file aside, or point KEYSTR_DATA_DIR somewhere else",
+        );
+    }
+    let probe = dir.join(".keystr-doctor");
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Check::pass(NAME, dir.display().to_string())
+        }
+        Err(error) => Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} is not writable: {}", dir.display(), error),
+            format!("fix the permissions on {}", dir.display()),
+        ),
+    }
+}
+
+/// `plain_json` files are parsed directly: loading one through its
+/// storage would quietly swap an unreadable file for an empty history.
+fn check_data_file(path: &std::path::Path, store: &dyn Storage, plain_json: bool) -> Check {
+    const NAME: &str = "Data file";
+    if !path.exists() {
+        return Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("no data file at {}", path.display()),
+            "run `keystr init`",
+        );
+    }
+    let loaded = if plain_json {
+        read_data_file(path)
+    } else {
+        store.load()
+    };
+    match loaded {
+        Ok(data) => Check::pass(
+            NAME,
+            format!(
+                "{} record(s), schema v{}",
+                data.daily_records.len(),
+                data.schema_version
+            ),
+        ),
+        Err(error) => Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} can't be read: {}", path.display(), error),
+            "restore a backup with `keystr restore`, or move the file aside and run `keystr init`",
+        ),
+    }
+}
+
+fn check_daemon() -> Check {
+    const NAME: &str = "Monitor";
+    if let Some(pid) = is_running() {
+        return Check::pass(NAME, format!("running (PID {})", pid));
+    }
+    let last_error = fs::read_to_string(get_status_file())
+        .ok()
+        .and_then(|status| status.strip_prefix("error: ").map(str::to_string));
+    match last_error {
+        Some(reason) => Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("not running; last start failed: {}", reason),
+            "fix the problem above, then run `keystr start`",
+        ),
+        None => Check::problem(NAME, CheckStatus::Warn, "not running", "run `keystr start`"),
+    }
+}
+
+fn check_platform(os: &str, wayland: bool, display: bool) -> Check {
+    const NAME: &str = "Platform";
+    match os {
+        "linux" if wayland && display => Check::problem(
+            NAME,
+            CheckStatus::Warn,
+            "Linux (Wayland, through XWayland)",
+            "only keystrokes in X11 apps are seen; log into an X11 session to count everything",
+        ),
+        "linux" if wayland => Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            "Linux (Wayland, no X display)",
+            WAYLAND_HINT,
+        ),
+        "linux" if !display => Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            "Linux, DISPLAY is not set",
+            "run keystr from a graphical X11 session, or set DISPLAY",
+        ),
+        "linux" => Check::pass(NAME, "Linux (X11)"),
+        "macos" => Check::pass(NAME, "macOS"),
+        "windows" => Check::pass(NAME, "Windows"),
+        other => Check::problem(
+            NAME,
+            CheckStatus::Warn,
+            other.to_string(),
+            "keystr is only tested on Linux, macOS and Windows",
+        ),
+    }
+}
+
+/// Only macOS lets us ask up front whether input can be observed.
+fn check_input_permission() -> Option<Check> {
+    const NAME: &str = "Input permission";
+    if !cfg!(target_os = "macos") {
+        return None;
+    }
+    Some(if has_input_permission() {
+        Check::pass(NAME, "granted")
+    } else {
+        Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            "not granted",
+            MACOS_PERMISSION_HINT,
+        )
+    })
+}
+
+/// Prints the setup checklist, returning `false` if anything failed.
+fn cmd_doctor() -> bool {
+    let store = storage();
+    let plain_json = DATA_FILE.get().is_some() || config().storage == StorageKind::Json;
+    let display = std::env::var_os("DISPLAY").is_some_and(|v| !v.is_empty());
+    let mut checks = vec![
+        check_config_dir(&get_config_dir()),
+        check_data_file(&store.path(), store.as_ref(), plain_json),
+        check_daemon(),
+        check_platform(std::env::consts::OS, on_wayland(), display),
+    ];
+    checks.extend(check_input_permission());
+
+    println!();
+    for check in &checks {
+        let mark = match check.status {
+            CheckStatus::Pass => charset().check.green().bold(),
+            CheckStatus::Warn => charset().warning.yellow().bold(),
+            CheckStatus::Fail => charset().cross.red().bold(),
+        };
+        println!(
+            "  {} {} {}",
+            mark,
+            format!("{:<17}", check.name).dimmed(),
+            check.detail
+        );
+        if let Some(fix) = &check.fix {
+            println!("      {} {}", charset().arrow.bright_cyan(), fix);
+        }
+    }
+    println!();
+
+    checks.iter().all(|check| check.status != CheckStatus::Fail)
+}
+
+/// Summary printed by `keystr dump`.
+fn render_dump(out: &mut String, data: &KeystrokeData, path: &std::path::Path) -> fmt::Result {
+    let days = data.daily_records.iter().map(DailyRecord::day);
+    let range = match (days.clone().min(), days.max()) {
+        (Some(first), Some(last)) => format!(
+            "{} {} {}",
+            format_day(first),
+            charset().range,
+            format_day(last)
+        ),
+        _ => "no records".to_string(),
+    };
+    let rows = [
+        ("File:", path.display().to_string()),
+        ("Schema:", format!("v{}", data.schema_version)),
+        ("Records:", data.daily_records.len().to_string()),
+        ("Total:", data.total_count.to_string()),
+        ("Clicks:", data.total_clicks.to_string()),
+        ("Dates:", range),
+    ];
+
+    writeln!(out)?;
+    for (label, value) in rows {
+        writeln!(
+            out,
+            "  {} {}",
+            format!("{:<9}", label).dimmed(),
+            value.bright_cyan()
+        )?;
+    }
+    writeln!(out)
+}
+
+fn cmd_dump(raw: bool) {
+    let store = storage();
+    let path = store.path();
+    if !path.exists() {
+        fail(format_args!(
+            "No data file at {}; keystr is not initialized, run {}",
+            path.display(),
+            "keystr init".bright_yellow()
+        ));
+    }
+
+    let data = store.load().or_exit("Could not load statistics");
+    if raw {
+        // Only a plain `data.json` can be echoed as is; anything else is
+        // shown as the JSON it decodes to.
+        match fs::read_to_string(&path) {
+            Ok(content)
+                if config().storage == StorageKind::Json && !is_encrypted(content.as_bytes()) =>
+            {
+                print!("{}", content)
+            }
+            _ => println!(
+                "{}",
+                serde_json::to_string_pretty(&data).expect("Failed to serialize data")
+            ),
+        }
+        return;
+    }
+
+    let mut out = String::new();
+    render_dump(&mut out, &data, &path).expect("Failed to render dump");
+    print!("{}", out);
+}
+
+/// Exits if the daemon is running. It keeps its own copy of the data in
+/// memory and would write that straight back over whatever we save.
+fn require_stopped() {
+    if is_running().is_some() {
+        println!(
+            "\n  {} Stop the monitor first with {}\n",
+            charset().info.blue(),
+            "keystr stop".bright_yellow()
+        );
+        std::process::exit(1);
+    }
+}
+
+fn cmd_import(path: &std::path::Path, dry_run: bool) {
+    if !dry_run {
+        require_stopped();
+    }
+    let imported = match read_data_file(path) {
+        Ok(imported) => imported,
+        Err(error) => fail(format_args!(
+            "Could not read {}: {}",
+            path.display().to_string().bright_cyan(),
+            error
+        )),
+    };
+
+    let mut data = load_data().or_exit("Could not load statistics");
+    let summary = data.merge(&imported);
+
+    println!(
+        "\n  {} {} day(s) merged, {} day(s) added",
+        charset().check.green().bold(),
+        summary.days_merged.to_string().bright_cyan(),
+        summary.days_added.to_string().bright_cyan()
+    );
+    println!(
+        "  {} {} keystrokes added (new total: {})",
+        charset().check.green().bold(),
+        summary.keystrokes_added.to_string().bright_cyan(),
+        data.total_count.to_string().bright_cyan().bold()
+    );
+
+    if dry_run {
+        println!("  {} Dry run - nothing was saved\n", charset().info.blue());
+    } else {
+        save_data(&data).or_exit("Could not save statistics");
+        println!("  {} Import saved\n", charset().check.green().bold());
+    }
+}
+
+/// Default backup name for `timestamp`, e.g. `data-20241004-093000.json`.
+fn backup_file_name(timestamp: u64, offset_seconds: i64) -> String {
+    let local = timestamp.saturating_add_signed(offset_seconds);
+    let (year, month, day) = civil_from_days((local / 86400) as i64);
+    let seconds = local % 86400;
+    format!(
+        "data-{:04}{:02}{:02}-{:02}{:02}{:02}.json",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+fn cmd_backup(output: Option<PathBuf>) {
+    let output = output.unwrap_or_else(|| {
+        let now = current_timestamp();
+        get_config_dir().join(backup_file_name(now, utc_offset_at(now)))
+    });
+
+    let data = load_data().or_exit("Could not load statistics");
+    // Backups are encrypted like the data file itself when `encrypt` is on.
+    let bytes = encode_data(&data).or_exit("Could not encode statistics");
+    fs::write(&output, bytes).or_exit("Failed to write backup file");
+    println!(
+        "\n  {} Backed up {} keystrokes to {}\n",
+        charset().check.green().bold(),
+        data.total_count.to_string().bright_cyan(),
+        output.display().to_string().bright_cyan()
+    );
+}
+
+fn cmd_restore(path: &std::path::Path, yes: bool) {
+    let restored = match read_data_file(path) {
+        Ok(restored) => restored,
+        Err(error) => fail(format_args!(
+            "{} is not a valid backup: {}",
+            path.display().to_string().bright_cyan(),
+            error
+        )),
+    };
+
+    // A running daemon would overwrite the restored data with its own copy.
+    require_stopped();
+    println!();
+    let question = format!(
+        "Replace current statistics with {} from backup?",
+        keystroke_count(restored.total_count)
+    );
+    if !confirm(&question, "restore", yes) {
+        println!("  {} Restore cancelled\n", charset().info.blue());
+        return;
+    }
+
+    let store = storage();
+    let current = store.path();
+    if current.exists() {
+        let mut pre_restore = current.clone().into_os_string();
+        pre_restore.push(".pre-restore");
+        fs::copy(&current, &pre_restore).or_exit("Failed to save pre-restore copy");
+        println!(
+            "  {} Previous data saved to {}",
+            charset().check.green().bold(),
+            PathBuf::from(pre_restore)
+                .display()
+                .to_string()
+                .bright_cyan()
+        );
+    }
+
+    store.save(&restored).or_exit("Could not save statistics");
+    println!("  {} Statistics restored\n", charset().check.green().bold());
+}
+
+fn cmd_prune(days: Option<u64>) {
+    let Some(retention_days) = days.or(config().retention_days) else {
+        println!(
+            "\n  {} No retention window set. Pass {} or set {} in the config\n",
+            charset().info.blue(),
+            "--days <n>".bright_yellow(),
+            "retention_days".bright_yellow()
+        );
+        return;
+    };
+    require_stopped();
+
+    let mut data = load_data().or_exit("Could not load statistics");
+    let removed = data.prune(retention_days);
+    if removed > 0 {
+        save_data(&data).or_exit("Could not save statistics");
+    }
+
+    println!(
+        "\n  {} Removed {} record(s) older than {} days",
+        charset().check.green().bold(),
+        removed.to_string().bright_cyan(),
+        retention_days
+    );
+    println!(
+        "  {} Lifetime total kept at {}\n",
+        charset().info.blue(),
+        data.total_count.to_string().bright_cyan().bold()
+    );
+}
+
+fn cmd_compact() {
+    let store = JsonlStorage::new();
+    if !store.log.exists() {
+        println!(
+            "\n  {} No event log to compact (used with {})\n",
+            charset().info.blue(),
+            "storage = \"jsonl\"".bright_yellow()
+        );
+        return;
+    }
+
+    let events = store.compact().or_exit("Could not compact event log");
+    println!(
+        "\n  {} Folded {} event(s) into {}\n",
+        charset().check.green().bold(),
+        events.to_string().bright_cyan(),
+        store.snapshot.path.display().to_string().bright_cyan()
+    );
+}
+
+fn cmd_convert(to: ConvertFormat) {
+    let kind = to.storage_kind();
+    let name = toml::Value::try_from(kind).expect("storage kinds serialize as strings");
+    if config().storage == kind {
+        println!(
+            "\n  {} Already using {}\n",
+            charset().info.blue(),
+            format!("storage = {}", name).bright_yellow()
+        );
+        return;
+    }
+    // The daemon would keep writing to the old format.
+    if is_running().is_some() {
+        println!(
+            "\n  {} Stop the monitor first with {}\n",
+            charset().info.blue(),
+            "keystr stop".bright_yellow()
+        );
+        std::process::exit(1);
+    }
+
+    let from = storage();
+    let data = from.load().or_exit("Could not load statistics");
+    let target = storage_for(kind);
+    target.save(&data).or_exit("Could not save statistics");
+    let mut settings = config().clone();
+    settings.storage = kind;
+    save_config(&settings).or_exit("Failed to write config file");
+
+    println!(
+        "\n  {} Converted {} day(s) to {}",
+        charset().check.green().bold(),
+        data.daily_records.len().to_string().bright_cyan(),
+        target.path().display().to_string().bright_cyan()
+    );
+    let left = if from.path().exists() {
+        format!("; {} was left in place", from.path().display())
+    } else {
+        String::new()
+    };
+    println!(
+        "  {} Now using {}{}\n",
+        charset().arrow.accent(),
+        format!("storage = {}", name).bright_yellow(),
+        left
+    );
+}
+
+/// Asks a y/N `question`, or skips it when `yes` was passed. Exits instead
+/// of prompting when stdin isn't a terminal.
+fn confirm(question: &str, command: &str, yes: bool) -> bool {
+    use std::io::{self, IsTerminal, Write};
+
+    if yes {
+        return true;
+    }
+    if !io::stdin().is_terminal() {
+        // Nobody is there to answer, so don't block waiting on the prompt.
+        fail(format_args!(
+            "Not a terminal - pass {} to {} without confirming",
+            "--yes".bright_yellow(),
+            command
+        ));
+    }
+
+    print!("  {} ", format!("{} (y/N):", question).bright_yellow());
+    let _ = io::stdout().flush();
+
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .or_exit("Failed to read input");
+    input.trim().to_lowercase() == "y"
+}
+
+fn cmd_reset(yes: bool) {
+    require_stopped();
+    println!();
+    if confirm("Reset all statistics?", "reset", yes) {
+        let new_data = KeystrokeData::new();
+        save_data(&new_data).or_exit("Could not save statistics");
+        println!(
+            "  {} All statistics cleared\n",
+            charset().check.green().bold()
+        );
+    } else {
+        println!("  {} Reset cancelled\n", charset().info.blue());
+    }
+}
+
+/// Removes the records between `from` and `to`, either end open.
+fn cmd_reset_days(from: Option<u64>, to: Option<u64>, yes: bool) {
+    let (first, last) = (from.unwrap_or(0), to.unwrap_or(u64::MAX));
+    if first > last {
+        fail("Invalid range: --from must not be after --to");
+    }
+    let label = if from.is_some() && from == to {
+        format_day(first)
+    } else {
+        range_label(from, to)
+    };
+    require_stopped();
+
+    let mut data = load_data().or_exit("Could not load statistics");
+    let matching = data.get_range_stats(first, last);
+    if matching.is_empty() {
+        println!("\n  {} No records for {}\n", charset().info.blue(), label);
+        return;
+    }
+    let keystrokes: u64 = matching.iter().map(|r| r.count).sum();
+
+    println!();
+    let question = format!(
+        "Remove {} day(s) with {} keystrokes ({})?",
+        matching.len(),
+        keystrokes,
+        label
+    );
+    if !confirm(&question, "reset", yes) {
+        println!("  {} Reset cancelled\n", charset().info.blue());
+        return;
+    }
+    let removed = data.remove_days(first, last);
+    save_data(&data).or_exit("Could not save statistics");
+    println!(
+        "  {} Removed {} day(s) and {} keystrokes",
+        charset().check.green().bold(),
+        removed.len().to_string().bright_cyan(),
+        keystrokes.to_string().bright_cyan()
+    );
+    println!(
+        "  {} Lifetime total is now {}\n",
+        charset().info.blue(),
+        data.total_count.to_string().bright_cyan().bold()
+    );
+}
+
+/// Every file under `dir`, sorted.
+fn list_files(dir: &std::path::Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(list_files(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+fn cmd_uninstall(yes: bool) {
+    let dir = get_config_dir();
+    if !dir.exists() {
+        println!(
+            "\n  {} Nothing to remove at {}\n",
+            charset().info.blue(),
+            dir.display().to_string().bright_cyan()
+        );
+        return;
+    }
+
+    println!();
+    let question = format!("Delete {} and everything in it?", dir.display());
+    if !confirm(&question, "uninstall", yes) {
+        println!("  {} Uninstall cancelled\n", charset().info.blue());
+        return;
+    }
+
+    // A running daemon would write its data straight back.
+    if is_running().is_some() && !cmd_stop() {
+        fail("Could not stop the monitor; nothing was removed");
+    }
+
+    let files = list_files(&dir);
+    fs::remove_dir_all(&dir).or_exit("Failed to remove keystr data");
+
+    println!(
+        "  {} Removed {} file(s) from {}",
+        charset().check.green().bold(),
+        files.len().to_string().bright_cyan(),
+        dir.display().to_string().bright_cyan()
+    );
+    for file in &files {
+        let name = file.strip_prefix(&dir).unwrap_or(file);
+        println!("    {} {}", "-".bright_black(), name.display());
+    }
+    if is_autostart_enabled() {
+        println!(
+            "  {} Auto-start is still enabled; run {} to remove it",
+            charset().info.blue(),
+            "keystr disable".bright_yellow()
+        );
+    }
+    println!();
+}
+
+/// The systemd user unit `keystr enable` installs on Linux.
+#[cfg(any(target_os = "linux", test))]
+fn systemd_unit(exe: &std::path::Path) -> String {
+    format!(
+        r#"[Unit]
+Description=Keystr Daemon
+
+[Service]
+ExecStart={} daemon
+Restart=always
+
+[Install]
+WantedBy=default.target
+"#,
+        exe.display()
+    )
+}
+
+/// The LaunchAgent `keystr enable` installs on macOS.
+#[cfg(any(target_os = "macos", test))]
+fn launch_agent_plist(exe: &std::path::Path) -> String {
+    let exe = exe
+        .display()
+        .to_string()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LAUNCH_AGENT_LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{exe}</string>
+        <string>daemon</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+</dict>
+</plist>
+"#
+    )
+}
+
+#[cfg(any(target_os = "macos", test))]
+const LAUNCH_AGENT_LABEL: &str = "com.keystr.daemon";
+
+#[cfg(target_os = "linux")]
+fn autostart_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| fail("Could not find config directory"))
+        .join("systemd/user/keystr.service")
+}
+
+#[cfg(target_os = "macos")]
+fn autostart_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| fail("Could not find home directory"))
+        .join("Library/LaunchAgents")
+        .join(format!("{}.plist", LAUNCH_AGENT_LABEL))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn print_autostart_enabled(entry: &str) {
+    println!("\n  {} Auto-start enabled.", charset().check.green().bold());
+    println!(
+        "  {} Installed {}",
+        charset().info.blue(),
+        entry.bright_cyan()
+    );
+    println!(
+        "  {} Use `keystr disable` to turn off.\n",
+        charset().arrow.bright_cyan()
+    );
+}
+
+#[cfg(target_os = "linux")]
+fn cmd_enable() {
+    let exe_path = std::env::current_exe().or_exit("Failed to get executable path");
+    let service_path = autostart_path();
+
+    fs::create_dir_all(service_path.parent().unwrap()).or_exit("Failed to create systemd user dir");
+    fs::write(&service_path, systemd_unit(&exe_path)).or_exit("Failed to write service file");
+
+    systemctl(&["daemon-reload"]);
+    systemctl(&["enable", "keystr"]);
+    systemctl(&["start", "keystr"]);
+
+    print_autostart_enabled(&service_path.display().to_string());
+}
+
+/// Runs `systemctl --user` with `args`, exiting with its complaint if it
+/// fails, not just if it can't be started.
+#[cfg(target_os = "linux")]
+fn systemctl(args: &[&str]) {
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .or_exit("Failed to run systemctl");
+    if !output.status.success() {
+        fail(format_args!(
+            "systemctl --user {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn cmd_disable() {
+    let service_path = autostart_path();
+
+    // Without the unit there's nothing for systemd to stop or disable.
+    if service_path.exists() {
+        systemctl(&["stop", "keystr"]);
+        systemctl(&["disable", "keystr"]);
+    }
+
+    fs::remove_file(&service_path).ok();
+
+    println!(
+        "\n  {} Auto-start disabled.",
+        charset().check.green().bold()
+    );
+    println!(
+        "  {} Removed {}\n",
+        charset().info.blue(),
+        service_path.display()
+    );
+}
+
+#[cfg(target_os = "macos")]
+fn cmd_enable() {
+    let exe_path = std::env::current_exe().or_exit("Failed to get executable path");
+    let plist_path = autostart_path();
+
+    fs::create_dir_all(plist_path.parent().unwrap()).or_exit("Failed to create LaunchAgents dir");
+    // Unload first so enabling twice picks up a moved binary instead of
+    // failing because the agent is already loaded.
+    let _ = Command::new("launchctl")
+        .arg("unload")
+        .arg(&plist_path)
+        .output();
+    fs::write(&plist_path, launch_agent_plist(&exe_path)).or_exit("Failed to write LaunchAgent");
+    let output = Command::new("launchctl")
+        .args(["load", "-w"])
+        .arg(&plist_path)
+        .output()
+        .or_exit("Failed to run launchctl");
+    if !output.status.success() {
+        fail(format_args!(
+            "launchctl could not load the LaunchAgent: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    print_autostart_enabled(&plist_path.display().to_string());
+}
+
+#[cfg(target_os = "macos")]
+fn cmd_disable() {
+    let plist_path = autostart_path();
+    let _ = Command::new("launchctl")
+        .args(["unload", "-w"])
+        .arg(&plist_path)
+        .output();
+    fs::remove_file(&plist_path).ok();
+
+    println!(
+        "\n  {} Auto-start disabled.",
+        charset().check.green().bold()
+    );
+    println!(
+        "  {} Removed {}\n",
+        charset().info.blue(),
+        plist_path.display()
+    );
+}
+
+/// A logon task rather than a service: services run in session 0 and never
+/// see the user's keyboard.
+#[cfg(windows)]
+fn cmd_enable() {
+    let exe_path = std::env::current_exe().or_exit("Failed to get executable path");
+    let output = Command::new("schtasks")
+        .args(["/Create", "/F", "/SC", "ONLOGON", "/TN", "keystr", "/TR"])
+        .arg(format!("\"{}\" start", exe_path.display()))
+        .output()
+        .or_exit("Failed to run schtasks");
+    if !output.status.success() {
+        fail(format_args!(
+            "schtasks could not create the task: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    print_autostart_enabled("scheduled task \"keystr\" (runs at logon)");
+}
+
+#[cfg(windows)]
+fn cmd_disable() {
+    let _ = Command::new("schtasks")
+        .args(["/Delete", "/F", "/TN", "keystr"])
+        .output();
+
+    println!(
+        "\n  {} Auto-start disabled.",
+        charset().check.green().bold()
+    );
+    println!(
+        "  {} Removed scheduled task \"keystr\"\n",
+        charset().info.blue()
+    );
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn cmd_enable() {
+    println!(
+        "\n  {} Auto-start is only supported on Linux, macOS and Windows.\n",
+        charset().info.blue()
+    );
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn cmd_disable() {
+    println!(
+        "\n  {} Auto-start is only supported on Linux, macOS and Windows.\n",
+        charset().info.blue()
+    );
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    if cli.no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        colored::control::set_override(false);
+    }
+    if cli.ascii || std::env::var_os("TERM").is_some_and(|term| term == "dumb") {
+        set_charset(&Charset::ASCII);
+    }
+
+    if let Some(name) = cli.profile {
+        PROFILE.set(name).expect("profile is only set once");
+    }
+    if let Some(path) = cli.data_file {
+        DATA_FILE.set(path).expect("data file is only set once");
+    }
+    init_config().or_exit("Invalid config");
 
     match cli.command {
         Commands::Init => cmd_init(),
         Commands::Start => cmd_start(),
-        Commands::Stop => cmd_stop(),
-        Commands::Status => cmd_status(),
-        Commands::Stats {
-            daily,
-            weekly,
-            monthly,
-        } => cmd_stats(daily, weekly, monthly),
-        Commands::Export { output } => cmd_export(&output),
-        Commands::Reset => cmd_reset(),
-        Commands::Enable => cmd_enable(),
+        Commands::Stop => {
+            if !cmd_stop() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Restart => cmd_restart(),
+        Commands::Status { json, spark } => cmd_status(
+            if json {
+                OutputFormat::Json
+            } else {
+                OutputFormat::Pretty
+            },
+            spark,
+        ),
+        Commands::Kpm => cmd_kpm(),
+        Commands::Stats(mut args) => {
+            if let Some(since) = args.since {
+                args.from = Some(since_day(since, current_timestamp()));
+            }
+            cmd_stats(&args)
+        }
+        Commands::Graph(mut args) => {
+            if let Some(since) = args.since {
+                args.from = Some(since_day(since, current_timestamp()));
+            }
+            cmd_graph(&args)
+        }
+        Commands::Today => cmd_today(),
+        Commands::Summary => cmd_summary(),
+        Commands::Metrics => cmd_metrics(),
+        Commands::Serve { port, bind } => cmd_serve(bind, port),
+        Commands::Watch {
+            interval,
+            watch_file,
+        } => cmd_watch(interval, watch_file),
+        Commands::Export {
+            output,
+            format,
+            from,
+            to,
+            since,
+            incremental,
+            reset_marker,
+        } => {
+            let from = since.map_or(from, |since| Some(since_day(since, current_timestamp())));
+            let marker = match (incremental, reset_marker) {
+                (false, _) => None,
+                (true, true) => Some(0),
+                (true, false) => Some(read_export_marker().unwrap_or(0)),
+            };
+            cmd_export(&output, format, from, to, marker)
+        }
+        Commands::Report { weekly: _, dir } => cmd_report(&dir),
+        Commands::Share => cmd_share(),
+        Commands::Verify { token } => cmd_verify(&token),
+        Commands::Goal { action } => cmd_goal(action),
+        Commands::Profile { action } => cmd_profile(action),
+        Commands::Where => cmd_where(),
+        Commands::Dump { raw } => cmd_dump(raw),
+        Commands::Doctor => {
+            if !cmd_doctor() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Config { action } => cmd_config(action),
+        Commands::Import { path, dry_run } => cmd_import(&path, dry_run),
+        Commands::Backup { output } => cmd_backup(output),
+        Commands::Restore { path, yes } => cmd_restore(&path, yes),
+        Commands::Prune { days } => cmd_prune(days),
+        Commands::Compact => cmd_compact(),
+        Commands::Convert { to } => cmd_convert(to),
+        Commands::Reset { yes, day, from, to } => match (day, from, to) {
+            (Some(day), _, _) => cmd_reset_days(Some(day), Some(day), yes),
+            (None, None, None) => cmd_reset(yes),
+            (None, from, to) => cmd_reset_days(from, to, yes),
+        },
+        Commands::Uninstall { yes } => cmd_uninstall(yes),
+        Commands::Enable => {
+            // The service starts at login with nobody there to type the passphrase.
+            if config().encrypt {
+                fail("Auto-start can't supply a passphrase; use keystr start while encrypt is on");
+            }
+            cmd_enable()
+        }
         Commands::Disable => cmd_disable(),
-        Commands::Daemon => cmd_daemon(),
+        Commands::Run { foreground } => cmd_daemon(foreground),
+        Commands::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "keystr", &mut std::io::stdout())
+        }
+        Commands::Daemon => cmd_daemon(false),
+    }
+
+    if take_clock_warning() {
+        eprintln!("  {} {}", charset().warning.yellow(), CLOCK_WARNING);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    /// Color stays off until this is dropped. The override is process-wide
+    /// and tests run in parallel, so holders take turns.
+    struct WithoutColor {
+        _lock: std::sync::MutexGuard<'static, ()>,
+    }
+
+    impl Drop for WithoutColor {
+        fn drop(&mut self) {
+            colored::control::unset_override();
+        }
+    }
+
+    fn without_color() -> WithoutColor {
+        static COLOR_LOCK: Mutex<()> = Mutex::new(());
+        let guard = COLOR_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+        colored::control::set_override(false);
+        WithoutColor { _lock: guard }
+    }
+
+    #[test]
+    fn stats_without_color_has_no_escape_codes() {
+        let no_color = without_color();
+        let mut data = KeystrokeData::new();
+        for _ in 0..5 {
+            data.increment();
+        }
+        let args = StatsArgs {
+            weekly: true,
+            hourly: true,
+            weekday: true,
+            compare: true,
+            ..StatsArgs::default()
+        };
+        let mut out = String::new();
+        render_stats(&mut out, &data, &args).unwrap();
+        drop(no_color);
+        assert!(out.contains("Total"));
+        assert!(!out.contains('\x1b'));
+    }
+
+    #[test]
+    fn ascii_charset_draws_only_ascii() {
+        let no_color = without_color();
+        set_charset(&Charset::ASCII);
+        let mut data = data_with_days(&[20_000, 20_001, 20_003]);
+        data.daily_records[1].count = 40;
+        data.total_count = 42;
+        data.weekday_hour_counts[2][9] = 7;
+        data.check_achievements(0, &[1]);
+        let args = StatsArgs {
+            daily: true,
+            weekly: true,
+            monthly: true,
+            hourly: true,
+            weekday: true,
+            compare: true,
+            top: Some(3),
+            categories: true,
+            distribution: true,
+            heatmap: true,
+            achievements: true,
+            ..StatsArgs::default()
+        };
+        let mut out = String::new();
+        render_stats(&mut out, &data, &args).unwrap();
+        draw_line_graph(&mut out, &data.daily_records, 5, 14.0, None).unwrap();
+        render_summary(&mut out, &data, 20_003).unwrap();
+        out.push_str(&status_line(
+            Some(42),
+            Some(300),
+            false,
+            Some(&sparkline(&[0, 3, 9], &charset().levels)),
+        ));
+        drop(no_color);
+        set_charset(&Charset::UNICODE);
+
+        assert!(out.contains("+--------"));
+        assert!(out.contains('#'));
+        assert!(
+            out.is_ascii(),
+            "{}",
+            out.lines().find(|l| !l.is_ascii()).unwrap()
+        );
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("keystr-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn plain_stats_are_an_aligned_table() {
+        let mut data = data_with_days(&[19_783, 19_784]);
+        data.daily_records[1].count = 123_456;
+        let args = StatsArgs {
+            from: Some(19_783),
+            ..StatsArgs::default()
+        };
+        assert_eq!(
+            render_stats_plain(&data, &args),
+            "DATE         COUNT\n2024-03-02  123456\n2024-03-01       1\n"
+        );
+    }
+
+    #[test]
+    fn stats_json_matches_the_data() {
+        let mut data = data_with_days(&[current_day() - 1]);
+        data.total_count = 1;
+        data.increment();
+
+        let json = render_stats_json(&data, &StatsArgs::default());
+        let report: StatsReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(report.total, data.total_count);
+        assert_eq!(report.today, 1);
+        assert_eq!(report.weekly, 2);
+        assert_eq!(report.streak, 2);
+        assert_eq!(report.daily.len(), 2);
+        assert_eq!(report.daily[0].date, format_iso_day(current_day()));
+        assert!(!json.contains('\x1b'));
+    }
+
+    #[test]
+    fn cumulative_graph_plots_the_running_total() {
+        let mut records = data_with_days(&[20_000, 20_001, 20_002]).daily_records;
+        for (record, count) in records.iter_mut().zip([3, 0, 4]) {
+            record.count = count;
+        }
+
+        let cumulative = cumulative_records(&records);
+        let counts: Vec<u64> = cumulative.iter().map(|r| r.count).collect();
+        assert_eq!(counts, [3, 3, 7]);
+        assert_eq!(cumulative[2].date, records[2].date);
+
+        let no_color = without_color();
+        let args = StatsArgs {
+            cumulative: true,
+            ..StatsArgs::default()
+        };
+        let mut out = String::new();
+        draw_daily_graph(&mut out, &records, &KeystrokeData::new(), &args).unwrap();
+        drop(no_color);
+        assert_eq!(out.lines().next(), Some("     7"));
+        assert!(!out.contains("avg"));
+    }
+
+    #[test]
+    fn graph_json_matches_what_is_plotted() {
+        let mut data = data_with_days(&[19_783, 19_784, 19_785]);
+        for (record, count) in data.daily_records.iter_mut().zip([3, 0, 9]) {
+            record.count = count;
+        }
+        let args = StatsArgs {
+            days: Some(30),
+            ..StatsArgs::default()
+        };
+        let mut records = listed_days(&data, &args);
+        records.reverse();
+
+        let series = graph_series(&records, &args);
+        let json = serde_json::to_string(&series).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"date":"2024-03-01","count":3},{"date":"2024-03-02","count":0},{"date":"2024-03-03","count":9}]"#
+        );
+
+        let smoothed = StatsArgs {
+            smooth: Some(3),
+            ..StatsArgs::default()
+        };
+        let counts: Vec<u64> = graph_series(&records, &smoothed)
+            .iter()
+            .map(|p| p.count)
+            .collect();
+        assert_eq!(counts, plotted_values(&records, Some(3)));
+        let cumulative = StatsArgs {
+            cumulative: true,
+            ..StatsArgs::default()
+        };
+        let counts: Vec<u64> = graph_series(&records, &cumulative)
+            .iter()
+            .map(|p| p.count)
+            .collect();
+        assert_eq!(counts, [3, 3, 12]);
+    }
+
+    #[test]
+    fn graph_height_and_days_are_clamped() {
+        let data = data_with_days(&[20_000, 20_001, 20_002]);
+        let args = |height, days| StatsArgs {
+            height,
+            days,
+            ..StatsArgs::default()
+        };
+
+        assert_eq!(shown_days(&data, &args(None, None)), 3);
+        assert_eq!(shown_days(&data, &args(None, Some(2))), 2);
+        assert_eq!(shown_days(&data, &args(None, Some(0))), 1);
+        assert_eq!(shown_days(&KeystrokeData::new(), &args(None, Some(30))), 1);
+
+        let no_color = without_color();
+        let rows = |height| {
+            let mut out = String::new();
+            draw_daily_graph(&mut out, &data.daily_records, &data, &args(height, None)).unwrap();
+            out.lines().count()
+        };
+        assert_eq!(rows(Some(20)) - rows(None), 10);
+        assert_eq!(rows(Some(1)), rows(Some(3)));
+        assert_eq!(rows(Some(100)), rows(Some(40)));
+        drop(no_color);
+    }
+
+    #[test]
+    fn doctor_checks_the_config_dir_and_data_file() {
+        let dir = temp_dir("doctor");
+        assert_eq!(check_config_dir(&dir).status, CheckStatus::Fail);
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(check_config_dir(&dir).status, CheckStatus::Pass);
+        let file = dir.join("file");
+        fs::write(&file, "").unwrap();
+        let not_dir = check_config_dir(&file);
+        assert_eq!(not_dir.status, CheckStatus::Fail);
+        assert!(not_dir.detail.contains("not a directory"));
+
+        let path = dir.join("data.json");
+        let store = JsonStorage { path: path.clone() };
+        let missing = check_data_file(&path, &store, true);
+        assert_eq!(missing.status, CheckStatus::Fail);
+        assert!(missing.fix.unwrap().contains("keystr init"));
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(
+            check_data_file(&path, &store, true).status,
+            CheckStatus::Fail
+        );
+        fs::write(&path, r#"{"total_count": 1, "daily_records": []}"#).unwrap();
+        let parsed = check_data_file(&path, &store, true);
+        assert_eq!(parsed.status, CheckStatus::Pass);
+        assert_eq!(parsed.detail, "0 record(s), schema v2");
+    }
+
+    #[test]
+    fn doctor_explains_display_problems() {
+        assert_eq!(
+            check_platform("linux", false, true).status,
+            CheckStatus::Pass
+        );
+        assert_eq!(
+            check_platform("linux", true, true).status,
+            CheckStatus::Warn
+        );
+        let wayland = check_platform("linux", true, false);
+        assert_eq!(wayland.status, CheckStatus::Fail);
+        assert!(wayland.fix.unwrap().contains("X11 session"));
+        assert!(
+            check_platform("linux", false, false)
+                .detail
+                .contains("DISPLAY")
+        );
+        assert_eq!(
+            check_platform("macos", false, false).status,
+            CheckStatus::Pass
+        );
+        assert_eq!(
+            check_platform("freebsd", false, true).status,
+            CheckStatus::Warn
+        );
+    }
+
+    #[test]
+    fn dump_summarizes_the_data() {
+        let mut data = data_with_days(&[20_003, 20_001]);
+        data.total_count = 42;
+        let no_color = without_color();
+        let mut out = String::new();
+        render_dump(&mut out, &data, std::path::Path::new("data.json")).unwrap();
+        let mut empty = String::new();
+        render_dump(
+            &mut empty,
+            &KeystrokeData::new(),
+            std::path::Path::new("data.json"),
+        )
+        .unwrap();
+        drop(no_color);
+
+        assert!(out.contains("Schema:   v2"));
+        assert!(out.contains("Records:  2"));
+        assert!(out.contains("Total:    42"));
+        assert!(out.contains(&format!("{} – {}", format_day(20_001), format_day(20_003))));
+        assert!(empty.contains("no records"));
+    }
+
+    #[test]
+    fn heatmap_counts_by_weekday_and_hour() {
+        let mut data = KeystrokeData::new();
+        data.increment_n(3);
+        let today = current_day();
+        let now = current_timestamp();
+        let hour = hour_with_offset(now, utc_offset_at(now));
+        assert_eq!(data.weekday_hour_counts[weekday_from_day(today)][hour], 3);
+
+        let csv = render_heatmap_csv(&data.weekday_hour_counts);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert!(lines[0].starts_with("weekday,0,1,"));
+        let row: Vec<&str> = lines[weekday_from_day(today) + 1].split(',').collect();
+        assert_eq!(row.len(), 25);
+        assert_eq!(row[hour + 1], "3");
+
+        // Files from before the heatmap load with it empty.
+        let old: KeystrokeData =
+            serde_json::from_str(r#"{"total_count": 5, "daily_records": []}"#).unwrap();
+        assert_eq!(old.weekday_hour_counts, [[0; 24]; 7]);
+    }
+
+    #[test]
+    fn heat_shades_scale_to_the_busiest_cell() {
+        assert_eq!(heat_shade(0, 100), '·');
+        assert_eq!(heat_shade(1, 100), '░');
+        assert_eq!(heat_shade(50, 100), '▒');
+        assert_eq!(heat_shade(51, 100), '▓');
+        assert_eq!(heat_shade(100, 100), '█');
+    }
+
+    #[test]
+    fn status_sparkline_follows_the_pid() {
+        let no_color = without_color();
+        let mut data = data_with_days(&[100, 120]);
+        data.daily_records[1].count = 50;
+        let counts = daily_counts(&data, 120, 30);
+        assert_eq!(counts.len(), 30);
+        assert_eq!(counts[29], 50);
+        let spark = sparkline(&counts, &Charset::UNICODE.levels);
+        let active = status_line(Some(42), None, false, Some(&spark));
+        let inactive = status_line(
+            None,
+            None,
+            false,
+            Some(&sparkline(&[0; 30], &Charset::UNICODE.levels)),
+        );
+        let stale = status_line(Some(42), Some(240), false, None);
+        let paused = status_line(Some(42), None, true, None);
+        drop(no_color);
+        assert!(active.starts_with("  ● Active │ PID: 42 │ "));
+        assert_eq!(
+            stale,
+            "  ⚠ Active but stale (last heartbeat 4m ago) │ PID: 42"
+        );
+        assert_eq!(paused, "  ● Active, paused by schedule │ PID: 42");
+        assert!(active.ends_with("█ 30d"));
+        assert!(inactive.contains(&"▁".repeat(30)));
+    }
+
+    #[test]
+    fn summary_fits_in_80_columns() {
+        let no_color = without_color();
+        let mut data = data_with_days(&[20_000, 20_003, 20_006]);
+        data.daily_records[1].count = 123_456;
+        data.total_count = 9_876_543_210;
+        let mut out = String::new();
+        render_summary(&mut out, &data, 20_006).unwrap();
+        drop(no_color);
+
+        assert!(out.contains("123456 on"));
+        assert!(out.contains("1 / 1 / 123456"));
+        assert!(out.contains("▂▁▁█▁▁▂"));
+        assert!(out.lines().all(|line| line.chars().count() <= 80));
+    }
+
+    #[test]
+    fn list_files_recurses_into_directories() {
+        let dir = temp_dir("list-files");
+        fs::create_dir_all(dir.join("profiles/work")).unwrap();
+        fs::write(dir.join("data.json"), "").unwrap();
+        fs::write(dir.join("profiles/work/data.json"), "").unwrap();
+
+        assert_eq!(
+            list_files(&dir),
+            [dir.join("data.json"), dir.join("profiles/work/data.json")]
+        );
+        assert!(list_files(&dir.join("missing")).is_empty());
+    }
+
+    #[test]
+    fn idle_flush_saves_counts_below_the_save_threshold() {
+        let dir = temp_dir("idle-flush");
+        fs::create_dir_all(&dir).unwrap();
+        let store = JsonStorage {
+            path: dir.join("data.json"),
+        };
+
+        let mut data = KeystrokeData::new();
+        let unsaved = AtomicU64::new(0);
+        for _ in 0..3 {
+            data.increment();
+            unsaved.fetch_add(1, Ordering::SeqCst);
+        }
+
+        assert_eq!(flush_unsaved(&data, &store, &unsaved), Ok(3));
+        assert_eq!(store.load().unwrap().total_count, 3);
+        // Nothing new since the last flush, so the file is left alone.
+        fs::remove_file(dir.join("data.json")).unwrap();
+        assert_eq!(flush_unsaved(&data, &store, &unsaved), Ok(0));
+        assert!(!dir.join("data.json").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn focused_window_is_read_from_xprop() {
+        let root = "_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007\n";
+        assert_eq!(parse_active_window(root), Some("0x3a00007"));
+        assert_eq!(
+            parse_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x0\n"),
+            None
+        );
+        assert_eq!(
+            parse_active_window("_NET_ACTIVE_WINDOW:  not found.\n"),
+            None
+        );
+
+        let window = "WM_CLASS(STRING) = \"code\", \"Code\"\n\
+                      _NET_WM_NAME(UTF8_STRING) = \"main.rs - keystr - Visual Studio Code\"\n";
+        let focused = xprop_values(window);
+        assert_eq!(focused, "code Code main.rs - keystr - Visual Studio Code");
+        assert!(app_matches("code", &focused));
+        assert!(app_matches("Visual Studio", &focused));
+        assert!(!app_matches("Firefox", &focused));
+    }
+
+    #[test]
+    fn coalescing_cuts_writes_under_fast_typing() {
+        // A minute at 600 keystrokes a minute: one every 100ms.
+        let writes = |min_interval: Duration| {
+            let mut coalescer = SaveCoalescer::new(10, min_interval);
+            let start = Instant::now();
+            let (mut pending, mut writes) = (0, 0);
+            for i in 1..=600 {
+                let now = start + Duration::from_millis(i * 100);
+                pending += 1;
+                if coalescer.due(pending, now) {
+                    coalescer.attempted(now);
+                    pending = 0;
+                    writes += 1;
+                }
+            }
+            writes
+        };
+
+        let every_tenth = writes(Duration::ZERO);
+        let coalesced = writes(Duration::from_secs(5));
+        assert_eq!(every_tenth, 60);
+        assert_eq!(coalesced, 12);
+    }
+
+    #[test]
+    fn failed_writes_are_retried_at_the_coalesced_pace() {
+        let mut coalescer = SaveCoalescer::new(10, Duration::from_secs(5));
+        let start = Instant::now();
+        let mut attempts = 0;
+        // Every write fails, so the count stays pending the whole minute.
+        for i in 1..=600 {
+            let now = start + Duration::from_millis(i * 100);
+            if coalescer.due(10 + i, now) {
+                coalescer.attempted(now);
+                attempts += 1;
+            }
+        }
+        assert_eq!(attempts, 12);
+    }
+
+    #[test]
+    fn wall_clock_jumps_are_detected_as_sleep() {
+        let wall = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mono = Instant::now();
+        let mut detector = SleepDetector::new(wall, mono);
+
+        let awake = Duration::from_secs(5);
+        assert_eq!(detector.check(wall + awake, mono + awake), None);
+
+        // Five more seconds of monotonic time, but an hour on the wall clock.
+        let slept = detector.check(wall + awake + Duration::from_secs(3600), mono + 2 * awake);
+        assert_eq!(slept, Some(Duration::from_secs(3595)));
+
+        // Small drift and the wall clock going backwards aren't sleep.
+        let later = wall + Duration::from_secs(3620);
+        assert_eq!(detector.check(later, mono + 3 * awake), None);
+        assert_eq!(detector.check(wall, mono + 4 * awake), None);
+    }
+
+    #[test]
+    fn activity_reset_starts_a_new_window() {
+        let mut tracker = ActivityTracker::new(Duration::from_secs(60));
+        let start = Instant::now();
+        tracker.record(start);
+        tracker.reset();
+        assert_eq!(tracker.record(start + Duration::from_secs(30)), 0);
+        assert_eq!(tracker.record(start + Duration::from_secs(40)), 10);
+    }
+
+    #[test]
+    fn bursts_of_changes_are_debounced() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        for _ in 0..5 {
+            sender.send(()).unwrap();
+        }
+        assert!(wait_for_change(&receiver, Duration::from_millis(20)));
+        assert!(receiver.try_recv().is_err());
+
+        drop(sender);
+        assert!(!wait_for_change(&receiver, Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn data_file_watcher_sees_saves() {
+        let dir = temp_dir("watch-file");
+        fs::create_dir_all(&dir).unwrap();
+        let store = JsonStorage {
+            path: dir.join("data.json"),
+        };
+        let Ok((_watcher, changes)) = watch_data_file(&store.path) else {
+            return;
+        };
+
+        fs::write(dir.join("other.txt"), "").unwrap();
+        store.save(&KeystrokeData::new()).unwrap();
+        assert!(changes.recv_timeout(Duration::from_secs(5)).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn milestones_fire_once_per_step_and_per_record() {
+        let mut data = data_with_days(&[10, 11]);
+        data.daily_records[0].count = 1500;
+        data.daily_records[1].count = 400;
+        let mut watcher = MilestoneWatcher::new(Some(1000), &data, 11);
+
+        assert!(watcher.check(11, 999).is_empty());
+        assert_eq!(watcher.check(11, 1000), vec![Milestone::Daily(1000)]);
+        assert!(watcher.check(11, 1500).is_empty());
+        assert_eq!(watcher.check(11, 1501), vec![Milestone::Record(1501)]);
+        assert_eq!(watcher.check(11, 2001), vec![Milestone::Daily(2000)]);
+
+        // The next day has to beat 2001.
+        assert_eq!(watcher.check(12, 1000), vec![Milestone::Daily(1000)]);
+        assert!(watcher.check(12, 1999).is_empty());
+        assert_eq!(
+            watcher.check(12, 3000),
+            vec![Milestone::Daily(3000), Milestone::Record(3000)]
+        );
+    }
+
+    #[test]
+    fn first_day_and_zero_step_fire_nothing() {
+        let mut watcher = MilestoneWatcher::new(Some(0), &KeystrokeData::new(), 5);
+        assert!(watcher.check(5, 1).is_empty());
+        assert!(watcher.check(5, 5000).is_empty());
+    }
+
+    #[test]
+    fn signal_flush_saves_counts_below_save_every() {
+        let dir = temp_dir("signal-flush");
+        fs::create_dir_all(&dir).unwrap();
+        let store = JsonStorage {
+            path: dir.join("data.json"),
+        };
+        let log = DaemonLog {
+            path: dir.join("daemon.log"),
+            max_bytes: 1024,
+            echo: false,
+        };
+        let mut data = KeystrokeData::new();
+        store.save(&data).unwrap();
+        // Fewer than `save_every`, so nothing would have written them yet.
+        for _ in 0..config().save_every - 1 {
+            data.increment();
+        }
+
+        flush_on_signal(&data, &store, &log);
+        let saved = store.load().unwrap();
+        let logged = fs::read_to_string(&log.path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(saved.total_count, config().save_every - 1);
+        assert_eq!(saved.get_today_stats(), config().save_every - 1);
+        assert!(logged.contains("stopped by signal"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn milestone_hook_gets_the_value_in_its_environment() {
+        let dir = temp_dir("milestone-hook");
+        fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out");
+        let log = DaemonLog {
+            path: dir.join("daemon.log"),
+            max_bytes: 1024,
+            echo: false,
+        };
+        let command = format!(
+            "echo \"$KEYSTR_MILESTONE_KIND $KEYSTR_MILESTONE\" > {}",
+            out.display()
+        );
+        run_milestone_hook(&command, &Milestone::Daily(1000), &log);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !out.exists() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(fs::read_to_string(&out).unwrap(), "daily 1000\n");
+
+        run_milestone_hook("exit 3", &Milestone::Record(5), &log);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !log.path.exists() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(
+            fs::read_to_string(&log.path)
+                .unwrap()
+                .contains("on_command exited")
+        );
+    }
+
+    #[test]
+    fn held_keys_are_detected_as_repeats() {
+        let mut detector = RepeatDetector::new();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert!(!detector.press(Key::KeyA, at(0)));
+        assert!(detector.press(Key::KeyA, at(500)));
+        assert!(detector.press(Key::KeyA, at(530)));
+        assert!(!detector.press(Key::KeyB, at(560)));
+        assert!(!detector.press(Key::KeyA, at(590)));
+
+        detector.release(Key::KeyA);
+        assert!(!detector.press(Key::KeyA, at(620)));
+        assert!(!detector.press(Key::KeyA, at(5000)));
+
+        let mut data = KeystrokeData::new();
+        data.record_press(false);
+        data.record_press(true);
+        assert_eq!((data.raw_total, data.distinct_total), (2, 1));
+    }
+
+    #[test]
+    fn export_breakdowns_split_weeks_and_months_at_year_end() {
+        let day = |y, m, d| days_from_civil(y, m, d) as u64;
+        // Sunday 29 Dec 2024 closes 2024-W52; the Monday after opens 2025-W01.
+        let mut data = data_with_days(&[day(2024, 12, 29), day(2024, 12, 30), day(2025, 1, 2)]);
+        for (record, count) in data.daily_records.iter_mut().zip([5, 7, 11]) {
+            record.count = count;
+        }
+
+        assert_eq!(
+            weekly_breakdown(&data),
+            [("2024-W52".to_string(), 5), ("2025-W01".to_string(), 18)]
+        );
+
+        let header = ExportHeader::new(&data, 0, 0);
+        let text = render_export_text(&data, &header, None);
+        assert!(text.contains("Weekly Breakdown:\n────────────────────────────────────\n2025-W01: 18 keystrokes\n2024-W52: 5 keystrokes\n"));
+        assert!(text.contains("2025-01: 11 keystrokes\n2024-12: 12 keystrokes\n"));
+
+        let markdown = render_export_markdown(&data, &header, None);
+        assert!(markdown.contains("## Weekly Breakdown\n\n| Week | Keystrokes |\n| --- | ---: |\n| 2024-W52 | 5 |\n| 2025-W01 | 18 |\n"));
+        assert!(markdown.contains("## Monthly Breakdown\n\n| Month | Keystrokes |\n| --- | ---: |\n| 2024-12 | 12 |\n| 2025-01 | 11 |\n"));
+
+        let csv = render_export_csv(&data, &header, None);
+        assert!(csv.ends_with("# Weekly breakdown\nweek,count\n2024-W52,5\n2025-W01,18\n# Monthly breakdown\nmonth,count\n2024-12,12\n2025-01,11\n"));
+    }
+
+    fn strip_ansi(text: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    fn graph(values: &[u64], max_height: usize) -> String {
+        let labels: Vec<String> = (0..values.len()).map(|i| format!("{:02}", i)).collect();
+        let mut out = String::new();
+        draw_bar_graph(&mut out, values, &labels, max_height, None).unwrap();
+        strip_ansi(&out)
+    }
+
+    #[test]
+    fn bar_graph_single_record() {
+        assert_eq!(
+            graph(&[7], 3),
+            "     7\n     ██\n     ██\n     ██\n     ──\n     00 \n\n"
+        );
+    }
+
+    #[test]
+    fn bar_graph_all_zero_week_draws_only_the_baseline() {
+        let output = graph(&[0; 7], 10);
+        assert_eq!(
+            output,
+            "     0\n     ── ── ── ── ── ── ──\n     00 01 02 03 04 05 06 \n\n"
+        );
+    }
+
+    #[test]
+    fn bar_graph_marks_the_reference_value() {
+        let labels: Vec<String> = (0..3).map(|i| format!("{:02}", i)).collect();
+        let mut out = String::new();
+        draw_bar_graph(&mut out, &[2, 4, 0], &labels, 4, Some(2)).unwrap();
+        let lines: Vec<String> = strip_ansi(&out).lines().map(String::from).collect();
+        assert_eq!(lines[3], "     ██╌██╌╌╌ avg 2");
+        assert_eq!(lines[2], "     ·· ██ ··");
+    }
+
+    #[test]
+    fn bar_graph_never_hides_non_zero_days() {
+        let output = graph(&[1, 1000, 0], 10);
+        let bottom = output.lines().nth(10).unwrap();
+        assert_eq!(bottom, "     ██ ██ ··");
+        assert_eq!(bar_height(1, 1000, 10), 1);
+        assert_eq!(bar_height(1000, 1000, 10), 10);
+        assert_eq!(bar_height(0, 1000, 10), 0);
+    }
+
+    fn data_with_days(days: &[u64]) -> KeystrokeData {
+        let mut data = KeystrokeData::new();
+        for &day in days {
+            data.daily_records.push(DailyRecord {
+                date: day.to_string(),
+                count: 1,
+                timestamp: day * 86400,
+                clicks: 0,
+                active_seconds: 0,
+                typing_seconds: 0,
+                sessions: 1,
+                longest_gap: 0,
+                first_seen: 0,
+                last_seen: 0,
+            });
+        }
+        data
+    }
+
+    #[test]
+    fn kpm_meter_evicts_presses_older_than_a_minute() {
+        let start = Instant::now();
+        let mut meter = KpmMeter::new();
+        for offset in [0, 10, 30, 59] {
+            meter.record(start + Duration::from_secs(offset));
+        }
+
+        assert_eq!(meter.kpm(start + Duration::from_secs(59)), 4);
+        assert_eq!(meter.kpm(start + Duration::from_secs(80)), 2);
+        assert_eq!(meter.kpm(start + Duration::from_secs(200)), 0);
+    }
+
+    #[test]
+    fn activity_tracker_skips_idle_gaps() {
+        let start = Instant::now();
+        let mut tracker = ActivityTracker::new(Duration::from_secs(60));
+        assert_eq!(tracker.record(start), 0);
+        assert_eq!(tracker.record(start + Duration::from_millis(1500)), 1);
+        assert_eq!(tracker.record(start + Duration::from_secs(3)), 2);
+        // A five minute pause starts a new window without counting the gap.
+        assert_eq!(tracker.record(start + Duration::from_secs(303)), 0);
+        assert_eq!(tracker.record(start + Duration::from_secs(333)), 30);
+    }
+
+    #[test]
+    fn clamped_tracker_caps_long_pauses_at_the_threshold() {
+        let start = Instant::now();
+        let mut tracker = ActivityTracker::clamped(Duration::from_secs(60));
+        assert_eq!(tracker.record(start), 0);
+        assert_eq!(tracker.record(start + Duration::from_secs(20)), 20);
+        assert_eq!(tracker.record(start + Duration::from_secs(320)), 60);
+        tracker.reset();
+        assert_eq!(tracker.record(start + Duration::from_secs(9000)), 0);
+        assert_eq!(tracker.record(start + Duration::from_millis(9_000_500)), 0);
+        assert_eq!(tracker.record(start + Duration::from_secs(9001)), 1);
+    }
+
+    #[test]
+    fn sessions_are_described_with_the_longest_break() {
+        let no_color = without_color();
+        let mut record = data_with_days(&[1]).daily_records.remove(0);
+        let single = describe_sessions(&record);
+        record.sessions = 4;
+        record.longest_gap = 52 * 60;
+        let several = describe_sessions(&record);
+        drop(no_color);
+        assert_eq!(single, "1 session today");
+        assert_eq!(several, "4 sessions today, longest break 52m");
+    }
+
+    #[test]
+    fn autostart_entries_run_the_daemon() {
+        let exe = std::path::Path::new("/opt/R&D/keystr");
+        assert!(systemd_unit(exe).contains("ExecStart=/opt/R&D/keystr daemon\n"));
+        let plist = launch_agent_plist(exe);
+        assert!(plist.contains("<string>/opt/R&amp;D/keystr</string>"));
+        assert!(plist.contains("<string>com.keystr.daemon</string>"));
+        assert!(plist.contains("<key>RunAtLoad</key>"));
+    }
+
+    #[test]
+    fn thousands_are_grouped() {
+        assert_eq!(group_thousands(0), "0");
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(4210), "4,210");
+        assert_eq!(group_thousands(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn bars_show_count_and_share() {
+        let no_color = without_color();
+        let rows = [
+            ("navigation".to_string(), 4210),
+            ("alphanumeric".to_string(), 30_000),
+        ];
+        let mut out = String::new();
+        draw_horizontal_bars(&mut out, &rows, 10).unwrap();
+        drop(no_color);
+        assert!(out.contains("4,210 (12.3%)"));
+        assert!(out.contains("30,000 (87.7%)"));
+    }
+
+    #[test]
+    fn format_duration_hours_and_minutes() {
+        assert_eq!(format_duration(59), "0m");
+        assert_eq!(format_duration(45 * 60), "45m");
+        assert_eq!(format_duration(3 * 3600 + 12 * 60 + 5), "3h 12m");
+    }
+
+    #[test]
+    fn kpm_meter_is_bounded() {
+        let now = Instant::now();
+        let mut meter = KpmMeter::new();
+        for _ in 0..KpmMeter::CAPACITY + 100 {
+            meter.record(now);
+        }
+
+        assert_eq!(meter.kpm(now), KpmMeter::CAPACITY as u64);
+    }
+
+    #[test]
+    fn metrics_use_prometheus_text_format() {
+        let mut data = KeystrokeData::new();
+        data.total_count = 40;
+        data.increment();
+        data.increment();
+
+        let metrics = render_metrics(&data);
+        assert!(metrics.contains("# TYPE keystr_total_keystrokes counter\n"));
+        assert!(metrics.contains("\nkeystr_total_keystrokes 42\n"));
+        assert!(metrics.contains("\nkeystr_today_keystrokes 2\n"));
+        assert!(metrics.contains("\nkeystr_weekly_keystrokes 2\n"));
+        assert!(!metrics.contains('\x1b'));
+        for line in metrics.lines().filter(|l| !l.starts_with('#')) {
+            let value = line.split_whitespace().nth(1).unwrap();
+            assert!(value.parse::<u64>().is_ok(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn daemon_log_rotates_past_max_size() {
+        let dir = temp_dir("daemon-log");
+        fs::create_dir_all(&dir).unwrap();
+        let log = DaemonLog {
+            path: dir.join("daemon.log"),
+            max_bytes: 64,
+            echo: false,
+        };
+
+        log.log("started (PID 1)");
+        assert!(!dir.join("daemon.log.1").exists());
+        for _ in 0..4 {
+            log.log("saved 10 pending event(s), total 100");
+        }
+
+        let rotated = fs::read_to_string(dir.join("daemon.log.1")).unwrap();
+        assert!(rotated.starts_with('['));
+        let current = fs::read_to_string(dir.join("daemon.log")).unwrap();
+        assert!(current.len() < 64 * 2);
+        assert!(current.ends_with("total 100\n"));
+    }
+
+    #[test]
+    fn backup_file_name_uses_local_time() {
+        // 2024-10-04 09:30:00 UTC
+        assert_eq!(backup_file_name(1728034200, 0), "data-20241004-093000.json");
+        assert_eq!(
+            backup_file_name(1728034200, -10 * 3600),
+            "data-20241003-233000.json"
+        );
+    }
+
+    #[test]
+    fn read_data_file_validates_backups() {
+        let dir = temp_dir("read-data-file");
+        fs::create_dir_all(&dir).unwrap();
+
+        let valid = dir.join("valid.json");
+        let mut data = KeystrokeData::new();
+        data.increment();
+        let header = ExportHeader::new(&data, 0, 0);
+        fs::write(&valid, render_export_json(&data, &header, None)).unwrap();
+        assert_eq!(read_data_file(&valid).unwrap().total_count, 1);
+
+        let invalid = dir.join("invalid.json");
+        fs::write(&invalid, r#"{"total": "lots"}"#).unwrap();
+        assert!(read_data_file(&invalid).is_err());
+        assert!(read_data_file(&dir.join("missing.json")).is_err());
+    }
+
+    #[test]
+    fn http_routes() {
+        assert_eq!(route_request("GET /health HTTP/1.1\r\n").0, "200 OK");
+        assert_eq!(route_request("GET /nope HTTP/1.1\r\n").0, "404 Not Found");
+        assert_eq!(
+            route_request("POST /stats HTTP/1.1\r\n").0,
+            "405 Method Not Allowed"
+        );
+        assert_eq!(route_request("").0, "400 Bad Request");
+    }
+
+    #[test]
+    fn markdown_export_has_a_table() {
+        let mut data = data_with_days(&[20_368, 20_367]);
+        data.total_count = 2;
+
+        let markdown = render_export_markdown(&data, &ExportHeader::new(&data, 0, 0), None);
+
+        assert!(markdown.starts_with("# Keystroke Report\n"));
+        assert!(markdown.contains("| Date | Keystrokes |\n| --- | ---: |\n"));
+        let first = markdown.find("06 Oct 2025").unwrap();
+        let second = markdown.find("07 Oct 2025").unwrap();
+        assert!(first < second);
+        assert!(!markdown.contains('\x1b'));
+    }
+
+    #[test]
+    fn html_export_embeds_a_chart() {
+        let mut data = data_with_days(&[20_367, 20_368]);
+        data.daily_records[1].count = 4;
+        data.total_count = 12_345;
+
+        let html = render_export_html(&data, &ExportHeader::new(&data, 0, 0), None);
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<svg"));
+        assert!(html.contains("<td>12,345</td>"));
+        assert_eq!(html.matches("<rect").count(), 2);
+        // The busiest day fills the plot's full 196px height.
+        assert!(html.contains("height=\"196.0\""));
+        assert!(html.contains(">06 Oct 2025</text>"));
+        assert!(html.contains(">07 Oct 2025</text>"));
+        assert!(!html.contains("<script"));
+    }
+
+    #[test]
+    fn ranged_exports_note_the_range() {
+        let data = data_with_days(&[20_367]);
+        let range = range_label(Some(20_360), Some(20_368));
+
+        let header = ExportHeader::new(&data, 0, 0);
+        let text = render_export_text(&data, &header, Some(&range));
+        assert!(text.contains("Range: 29 Sep 2025 – 07 Oct 2025 (1 keystroke)\n"));
+        let markdown = render_export_markdown(&data, &header, Some(&range));
+        assert!(markdown.contains("**Range:** 29 Sep 2025 – 07 Oct 2025 (1 keystroke)\n"));
+        let csv = render_export_csv(&data, &header, Some(&range));
+        assert!(csv.contains("\n# Range: 29 Sep 2025 – 07 Oct 2025 (1 keystroke)\ndate,"));
+        let json: serde_json::Value =
+            serde_json::from_str(&render_export_json(&data, &header, Some(&range))).unwrap();
+        assert_eq!(json["export"]["range"], "29 Sep 2025 – 07 Oct 2025");
+        assert!(!render_export_text(&data, &header, None).contains("Range:"));
+        assert!(!render_export_csv(&data, &header, None).contains("Range:"));
+        assert_eq!(keystroke_count(2), "2 keystrokes");
+    }
+
+    #[test]
+    fn exports_note_when_and_what_they_cover() {
+        let data = data_with_days(&[20_367, 20_360]);
+        let header = ExportHeader::new(&data, 1728034200, 3600);
+
+        let text = render_export_text(&data, &header, None);
+        assert!(text.contains("Generated: 2024-10-04 10:30:00\n"));
+        assert!(text.contains("Period: 29 Sep 2025 – 06 Oct 2025\n"));
+
+        let csv = render_export_csv(&data, &header, None);
+        assert!(csv.starts_with("# Generated: 2024-10-04 10:30:00\n# Period: "));
+        assert!(csv.contains("\ndate,timestamp,count\n"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render_export_json(&data, &header, None)).unwrap();
+        assert_eq!(json["export"]["generated"], "2024-10-04 10:30:00");
+
+        let empty = KeystrokeData::new();
+        assert_eq!(ExportHeader::new(&empty, 0, 0).period, "No records");
+    }
+
+    #[test]
+    fn percent_change_handles_empty_prior_period() {
+        assert_eq!(percent_change(150, 100), Some(50.0));
+        assert_eq!(percent_change(50, 100), Some(-50.0));
+        assert_eq!(percent_change(10, 0), None);
+    }
+
+    #[test]
+    fn monochrome_theme_uses_no_colors() {
+        let theme = theme_for(ThemeKind::Monochrome);
+        let styles = [
+            &theme.accent,
+            &theme.good,
+            &theme.success,
+            &theme.heading,
+            &theme.frame,
+            &theme.muted,
+            &theme.faint,
+            &theme.warn,
+            &theme.highlight,
+        ];
+        assert!(
+            styles
+                .iter()
+                .all(|style| style.paint("x").fgcolor.is_none())
+        );
+
+        let config: Config = toml::from_str("theme = \"solarized\"").unwrap();
+        assert_eq!(config.theme, ThemeKind::Solarized);
+        assert_eq!(Config::default().theme, ThemeKind::Default);
     }
 }
@@ -0,0 +1,77 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{spawn_daemon, temp_dir, wait_for};
+use std::process::Command;
+use std::thread::sleep;
+use std::time::Duration;
+
+// The flush itself is covered in-process by the binary's unit tests; this
+// checks the real signal path, which needs a display to listen on.
+#[test]
+#[ignore = "needs a display to listen for input events"]
+fn sigterm_flushes_data_and_removes_pid_file() {
+    let dir = temp_dir("sigterm");
+    let pid_file = dir.join("daemon.pid");
+    std::fs::write(
+        dir.join("data.json"),
+        r#"{"schema_version": 2, "total_count": 7, "daily_records": []}"#,
+    )
+    .unwrap();
+    let mut daemon = spawn_daemon(&dir);
+
+    let started = wait_for(Duration::from_secs(5), || {
+        pid_file.exists() || daemon.try_wait().unwrap().is_some()
+    });
+    assert!(started, "daemon never wrote its PID file");
+    // Give the listener time to come up; without a display it exits on its own.
+    sleep(Duration::from_millis(500));
+    assert!(
+        daemon.try_wait().unwrap().is_none(),
+        "daemon could not start listening for input events"
+    );
+
+    let status = Command::new("kill")
+        .args(["-TERM", &daemon.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let exit = daemon.wait().unwrap();
+    assert!(exit.success(), "daemon exited with {}", exit);
+    let saved: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.join("data.json")).unwrap()).unwrap();
+    assert!(saved["total_count"].as_u64().unwrap() >= 7, "{}", saved);
+    assert!(!pid_file.exists());
+}
+
+#[test]
+fn stop_kills_a_daemon_that_ignores_sigterm() {
+    let dir = temp_dir("ignores-sigterm");
+    let pid_file = dir.join("daemon.pid");
+    let mut stubborn = Command::new("sh")
+        .args(["-c", "trap '' TERM; exec sleep 30"])
+        .spawn()
+        .unwrap();
+    let pid = stubborn.id();
+    let started = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    std::fs::write(&pid_file, format!("{}\n{}\n", pid, started)).unwrap();
+    // Reap it as soon as it dies, so it doesn't linger as a zombie.
+    let reaper = std::thread::spawn(move || stubborn.wait().unwrap());
+    sleep(Duration::from_millis(200));
+
+    let stop = Command::new(env!("CARGO_BIN_EXE_keystr"))
+        .arg("stop")
+        .env("KEYSTR_DATA_DIR", &dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(stop.status.success(), "{:?}", stop);
+    assert!(String::from_utf8_lossy(&stop.stdout).contains("SIGKILL"));
+    assert!(!reaper.join().unwrap().success());
+    assert!(!pid_file.exists());
+}
@@ -0,0 +1,47 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{spawn_daemon, temp_dir, wait_for};
+use std::fs;
+use std::thread::sleep;
+use std::time::Duration;
+
+#[test]
+fn daemon_exits_when_the_lock_is_held() {
+    let dir = temp_dir("lock-held");
+    let lock = fs::File::create(dir.join("daemon.lock")).unwrap();
+    lock.lock().unwrap();
+
+    let mut daemon = spawn_daemon(&dir);
+    let exited = wait_for(Duration::from_secs(5), || {
+        daemon.try_wait().unwrap().is_some()
+    });
+
+    assert!(exited, "second daemon kept running");
+    assert!(!daemon.wait().unwrap().success());
+    assert!(!dir.join("daemon.pid").exists());
+    let log = fs::read_to_string(dir.join("daemon.log")).unwrap();
+    assert!(log.contains("already running"));
+}
+
+#[test]
+fn only_one_of_two_daemons_survives() {
+    let dir = temp_dir("two-daemons");
+    let mut first = spawn_daemon(&dir);
+    let mut second = spawn_daemon(&dir);
+
+    sleep(Duration::from_millis(1000));
+    let first_alive = first.try_wait().unwrap().is_none();
+    let second_alive = second.try_wait().unwrap().is_none();
+    for daemon in [&mut first, &mut second] {
+        let _ = daemon.kill();
+        let _ = daemon.wait();
+    }
+
+    if !first_alive && !second_alive {
+        eprintln!("skipping: daemon could not start listening for input events");
+        return;
+    }
+    assert!(first_alive != second_alive, "both daemons are running");
+}
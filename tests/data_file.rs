@@ -0,0 +1,35 @@
+mod common;
+
+use common::temp_dir;
+use std::process::Command;
+
+#[test]
+fn data_file_flag_beats_the_data_dir() {
+    let dir = temp_dir("data-file");
+    let other = temp_dir("data-file-other").join("coworker.json");
+    std::fs::write(
+        dir.join("data.json"),
+        r#"{"schema_version": 2, "total_count": 1, "daily_records": []}"#,
+    )
+    .unwrap();
+    std::fs::write(
+        &other,
+        r#"{"schema_version": 2, "total_count": 321, "daily_records": []}"#,
+    )
+    .unwrap();
+
+    let stats = |extra: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_keystr"))
+            .args(["stats", "--json"])
+            .args(extra)
+            .env("KEYSTR_DATA_DIR", &dir)
+            .output()
+            .expect("failed to run keystr");
+        assert!(output.status.success(), "{:?}", output);
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        report["total"].as_u64().unwrap()
+    };
+
+    assert_eq!(stats(&[]), 1);
+    assert_eq!(stats(&["--data-file", other.to_str().unwrap()]), 321);
+}
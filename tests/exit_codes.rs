@@ -0,0 +1,350 @@
+mod common;
+
+use common::temp_dir;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn keystr(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_keystr"))
+        .args(args)
+        .env("KEYSTR_DATA_DIR", dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("failed to run keystr")
+}
+
+#[test]
+fn successful_commands_exit_zero() {
+    let dir = temp_dir("exit-ok");
+    assert!(keystr(&dir, &["stats"]).status.success());
+    assert!(keystr(&dir, &["where"]).status.success());
+}
+
+#[test]
+fn init_creates_files_in_the_data_dir() {
+    let dir = temp_dir("init-env");
+    assert!(keystr(&dir, &["init"]).status.success());
+    assert!(dir.join("data.json").exists());
+    assert!(dir.join("config.toml").exists());
+
+    let paths = String::from_utf8_lossy(&keystr(&dir, &["where"]).stdout).into_owned();
+    assert!(paths.contains(&dir.join("daemon.pid").display().to_string()));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn config_dir_follows_xdg_config_home() {
+    let dir = temp_dir("xdg");
+    let output = Command::new(env!("CARGO_BIN_EXE_keystr"))
+        .arg("where")
+        .env_remove("KEYSTR_DATA_DIR")
+        .env("XDG_CONFIG_HOME", &dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("failed to run keystr");
+    let paths = String::from_utf8_lossy(&output.stdout).into_owned();
+    assert!(paths.contains(&dir.join("keystroke").display().to_string()));
+}
+
+#[test]
+fn failures_exit_non_zero_with_a_message() {
+    let dir = temp_dir("exit-fail");
+
+    let stop = keystr(&dir, &["stop"]);
+    assert_eq!(stop.status.code(), Some(1));
+
+    let import = keystr(
+        &dir,
+        &["import", dir.join("missing.json").to_str().unwrap()],
+    );
+    assert_eq!(import.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&import.stderr).contains("Could not read"));
+
+    let stats = keystr(
+        &dir,
+        &["stats", "--from", "2024-03-05", "--to", "2024-03-01"],
+    );
+    assert_eq!(stats.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&stats.stderr).contains("Invalid range"));
+}
+
+#[test]
+fn uninstall_removes_the_data_directory() {
+    let dir = temp_dir("uninstall");
+    std::fs::write(dir.join("config.toml"), "").unwrap();
+
+    let declined = Command::new(env!("CARGO_BIN_EXE_keystr"))
+        .args(["uninstall"])
+        .env("KEYSTR_DATA_DIR", &dir)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(declined.status.code(), Some(1));
+    assert!(dir.exists());
+
+    let removed = keystr(&dir, &["uninstall", "--yes"]);
+    assert!(removed.status.success());
+    assert!(String::from_utf8_lossy(&removed.stdout).contains("config.toml"));
+    assert!(!dir.exists());
+}
+
+#[test]
+fn dump_needs_an_initialized_data_file() {
+    let dir = temp_dir("dump");
+
+    let missing = keystr(&dir, &["dump"]);
+    assert_eq!(missing.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("keystr init"));
+
+    let content = r#"{"schema_version": 2, "total_count": 7, "daily_records": []}"#;
+    std::fs::write(dir.join("data.json"), content).unwrap();
+    let raw = keystr(&dir, &["dump", "--raw"]);
+    assert!(raw.status.success());
+    assert_eq!(String::from_utf8_lossy(&raw.stdout), content);
+    let summary = keystr(&dir, &["dump"]);
+    assert!(String::from_utf8_lossy(&summary.stdout).contains("Total:    7"));
+}
+
+#[test]
+fn start_reports_why_the_monitor_could_not_listen() {
+    let dir = temp_dir("start-error");
+    let start = keystr(&dir, &["start"]);
+    if start.status.success() {
+        // There's a usable display here, so there's no failure to report.
+        assert!(keystr(&dir, &["stop"]).status.success());
+        return;
+    }
+
+    assert_eq!(start.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&start.stderr);
+    assert!(stderr.contains("Monitor failed to start: "), "{}", stderr);
+    assert!(!dir.join("daemon.pid").exists());
+}
+
+#[test]
+fn completions_cover_every_subcommand() {
+    let dir = temp_dir("completions");
+    let bash = keystr(&dir, &["completions", "bash"]);
+    assert!(bash.status.success());
+    let script = String::from_utf8_lossy(&bash.stdout);
+    assert!(script.contains("_keystr"));
+    assert!(script.contains("doctor"));
+
+    assert!(!keystr(&dir, &["completions", "tcsh"]).status.success());
+}
+
+#[test]
+fn reset_can_drop_a_single_day() {
+    let dir = temp_dir("reset-day");
+    std::fs::write(
+        dir.join("data.json"),
+        r#"{"schema_version": 2, "total_count": 50, "daily_records": [
+            {"date": "19783", "count": 40, "timestamp": 1709251200},
+            {"date": "19784", "count": 10, "timestamp": 1709337600}
+        ]}"#,
+    )
+    .unwrap();
+
+    let reset = keystr(&dir, &["reset", "--day", "2024-03-01", "--yes"]);
+    assert!(reset.status.success(), "{:?}", reset);
+    assert!(String::from_utf8_lossy(&reset.stdout).contains("Removed 1 day(s) and 40 keystrokes"));
+    let saved: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.join("data.json")).unwrap()).unwrap();
+    assert_eq!(saved["total_count"], 10);
+    assert_eq!(saved["daily_records"].as_array().unwrap().len(), 1);
+
+    let again = keystr(&dir, &["reset", "--day", "2024-03-01", "--yes"]);
+    assert!(again.status.success());
+    assert!(String::from_utf8_lossy(&again.stdout).contains("No records"));
+
+    let backwards = keystr(
+        &dir,
+        &["reset", "--from", "2024-03-05", "--to", "2024-03-01"],
+    );
+    assert_eq!(backwards.status.code(), Some(1));
+    assert!(
+        !keystr(&dir, &["reset", "--day", "2024-13-01"])
+            .status
+            .success()
+    );
+}
+
+#[test]
+fn incremental_export_only_writes_new_days() {
+    let dir = temp_dir("export-incremental");
+    let out = dir.join("delta.csv");
+    let out = out.to_str().unwrap();
+    std::fs::write(
+        dir.join("data.json"),
+        r#"{"schema_version": 2, "total_count": 50, "daily_records": [
+            {"date": "19783", "count": 40, "timestamp": 1709251200, "last_seen": 1709280000},
+            {"date": "19784", "count": 10, "timestamp": 1709337600, "last_seen": 1709366400}
+        ]}"#,
+    )
+    .unwrap();
+    let export = |extra: &[&str]| {
+        let mut args = vec![
+            "export",
+            "--format",
+            "csv",
+            "--output",
+            out,
+            "--incremental",
+        ];
+        args.extend_from_slice(extra);
+        let output = keystr(&dir, &args);
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    assert!(export(&[]).contains("Exported 2 day(s)"));
+    std::fs::remove_file(out).unwrap();
+    assert!(export(&[]).contains("Nothing new"));
+    assert!(!Path::new(out).exists());
+    assert!(export(&["--reset-marker"]).contains("Exported 2 day(s)"));
+
+    assert!(!keystr(&dir, &["export", "--reset-marker"]).status.success());
+}
+
+#[test]
+fn convert_switches_between_json_and_bincode() {
+    let dir = temp_dir("convert");
+    std::fs::write(
+        dir.join("data.json"),
+        r#"{"schema_version": 2, "total_count": 50, "daily_records": [
+            {"date": "19783", "count": 40, "timestamp": 1709251200},
+            {"date": "19784", "count": 10, "timestamp": 1709337600}
+        ]}"#,
+    )
+    .unwrap();
+    let total = || {
+        let output = keystr(&dir, &["stats", "--json"]);
+        assert!(output.status.success(), "{:?}", output);
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        report["total"].as_u64().unwrap()
+    };
+
+    let convert = keystr(&dir, &["convert", "--to", "bincode"]);
+    assert!(convert.status.success(), "{:?}", convert);
+    assert!(String::from_utf8_lossy(&convert.stdout).contains("Converted 2 day(s)"));
+    let config = std::fs::read_to_string(dir.join("config.toml")).unwrap();
+    assert!(config.contains(r#"storage = "bincode""#));
+    std::fs::remove_file(dir.join("data.json")).unwrap();
+    assert_eq!(total(), 50);
+
+    let again = keystr(&dir, &["convert", "--to", "bincode"]);
+    assert!(String::from_utf8_lossy(&again.stdout).contains("Already using"));
+
+    assert!(keystr(&dir, &["convert", "--to", "json"]).status.success());
+    std::fs::remove_file(dir.join("data.bin")).unwrap();
+    assert_eq!(total(), 50);
+}
+
+#[test]
+fn prune_refuses_to_keep_zero_days() {
+    let dir = temp_dir("prune-zero");
+    let prune = keystr(&dir, &["prune", "--days", "0"]);
+    assert_eq!(prune.status.code(), Some(2));
+
+    std::fs::write(dir.join("config.toml"), "retention_days = 0\n").unwrap();
+    let prune = keystr(&dir, &["prune"]);
+    assert_eq!(prune.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&prune.stderr).contains("retention_days"));
+}
+
+#[test]
+fn data_rewrites_wait_for_the_daemon_to_stop() {
+    let dir = temp_dir("refuse-running");
+    let backup = dir.join("backup.json");
+    std::fs::write(
+        &backup,
+        r#"{"schema_version": 2, "total_count": 5, "daily_records": []}"#,
+    )
+    .unwrap();
+    // This test process stands in for a running daemon.
+    std::fs::write(dir.join("daemon.pid"), format!("{}\n", std::process::id())).unwrap();
+    let backup = backup.to_str().unwrap();
+
+    let refused = |args: &[&str]| {
+        let output = keystr(&dir, args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", output);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("keystr stop"));
+    };
+    refused(&["import", backup]);
+    assert!(
+        keystr(&dir, &["import", "--dry-run", backup])
+            .status
+            .success()
+    );
+    refused(&["restore", backup, "--yes"]);
+    refused(&["prune", "--days", "30"]);
+    refused(&["reset", "--yes"]);
+    refused(&["reset", "--day", "2024-03-01", "--yes"]);
+    assert!(!dir.join("data.json").exists());
+}
+
+#[test]
+fn restore_needs_yes_without_a_terminal() {
+    let dir = temp_dir("restore-yes");
+    let backup = dir.join("backup.json");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        &backup,
+        r#"{"schema_version": 2, "total_count": 5, "daily_records": []}"#,
+    )
+    .unwrap();
+    let backup = backup.to_str().unwrap();
+
+    let unconfirmed = keystr(&dir, &["restore", backup]);
+    assert_eq!(unconfirmed.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&unconfirmed.stderr).contains("--yes"));
+    assert!(!dir.join("data.json").exists());
+
+    assert!(keystr(&dir, &["restore", backup, "--yes"]).status.success());
+    let restored = std::fs::read_to_string(dir.join("data.json")).unwrap();
+    assert!(restored.contains("\"total_count\": 5"));
+}
+
+#[test]
+fn bincode_data_is_encrypted_when_encrypt_is_on() {
+    let dir = temp_dir("exit-bincode-encrypt");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("config.toml"),
+        "storage = \"bincode\"\nencrypt = true\n",
+    )
+    .unwrap();
+    let with_passphrase = |passphrase: &str, args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_keystr"))
+            .args(args)
+            .env("KEYSTR_DATA_DIR", &dir)
+            .env("KEYSTR_PASSPHRASE", passphrase)
+            .env("NO_COLOR", "1")
+            .output()
+            .expect("failed to run keystr")
+    };
+
+    assert!(with_passphrase("correct horse", &["init"]).status.success());
+    let bytes = std::fs::read(dir.join("data.bin")).unwrap();
+    assert!(bytes.starts_with(b"KEYSTR-ENC1\n"));
+    assert!(
+        with_passphrase("correct horse", &["stats"])
+            .status
+            .success()
+    );
+    assert_eq!(
+        with_passphrase("battery staple", &["stats"]).status.code(),
+        Some(1)
+    );
+}
+
+#[test]
+fn encrypted_data_refuses_auto_start() {
+    let dir = temp_dir("exit-enable-encrypt");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("config.toml"), "encrypt = true\n").unwrap();
+
+    let enable = keystr(&dir, &["enable"]);
+    assert_eq!(enable.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&enable.stderr).contains("passphrase"));
+}